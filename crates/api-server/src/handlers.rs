@@ -8,6 +8,11 @@ use ::storage::{LocalStorageManager, SharedStorageManager, StorageError};
 use container_manager::{ContainerError, ContainerManager, SnapshotManager};
 use models::*;
 
+/// JSON Schema for `ContainerConfig`, for form generators and API clients
+pub async fn get_container_config_schema() -> impl Responder {
+    HttpResponse::Ok().json(container_config_schema())
+}
+
 pub async fn list_containers() -> impl Responder {
     info!("Listing containers");
 
@@ -20,7 +25,7 @@ pub async fn list_containers() -> impl Responder {
                     // Simplified - in production, get from database
                     Container {
                         id: Uuid::new_v4(),
-                        name: name.clone(),
+                        name: ContainerName::new_unchecked(name.as_str()),
                         status: ContainerStatus::Stopped,
                         template: "unknown".to_string(),
                         node_id: None,
@@ -33,7 +38,12 @@ pub async fn list_containers() -> impl Responder {
                             network_interfaces: vec![],
                             rootfs_path: format!("/var/lib/lxc/{}/rootfs", name),
                             environment: vec![],
+                            hostname: None,
+                            cpu_request: None,
+                            memory_request: None,
                         },
+                        labels: Default::default(),
+                        description: None,
                     }
                 })
                 .collect();
@@ -21,6 +21,10 @@ pub fn configure_routes(cfg: &mut web::ServiceConfig) {
                 "/containers/{id}",
                 web::delete().to(handlers::delete_container),
             )
+            .route(
+                "/schema/container-config",
+                web::get().to(handlers::get_container_config_schema),
+            )
             // Snapshot routes
             .route(
                 "/containers/{id}/snapshots",
@@ -7,7 +7,7 @@ use tracing::{info, error};
 use crate::lxc::LxcCommand;
 use crate::config::LxcConfig;
 use crate::error::ContainerError;
-use models::{Container, ContainerStatus, ContainerConfig, CreateContainerRequest};
+use models::{Container, ContainerName, ContainerStatus, ContainerConfig, CreateContainerRequest};
 
 pub struct ContainerManager;
 
@@ -41,7 +41,7 @@ impl ContainerManager {
             "create",
             name,
             "-t",
-            &request.template,
+            request.template.as_str(),
         ]);
 
         match create_result {
@@ -49,13 +49,15 @@ impl ContainerManager {
                 info!("Container created successfully: {}", name);
                 Ok(Container {
                     id: container_id,
-                    name: name.clone(),
+                    name: ContainerName::new_unchecked(name.as_str()),
                     status: ContainerStatus::Stopped,
-                    template: request.template,
+                    template: request.template.into(),
                     node_id: None,
                     created_at: Utc::now(),
                     updated_at: Utc::now(),
                     config: request.config,
+                    labels: Default::default(),
+                    description: request.description,
                 })
             }
             Err(e) => {
@@ -156,17 +158,22 @@ impl ContainerManager {
             network_interfaces: vec![],
             rootfs_path: format!("{}/rootfs", crate::config::LxcConfig::lxc_root().join(name).display()),
             environment: vec![],
+            hostname: None,
+            cpu_request: None,
+            memory_request: None,
         };
 
         Ok(Container {
             id: Uuid::new_v4(), // In production, store this in a database
-            name: name.to_string(),
+            name: ContainerName::new_unchecked(name),
             status,
             template: "unknown".to_string(), // Parse from config
             node_id: None,
             created_at: Utc::now(), // Parse from filesystem
             updated_at: Utc::now(),
             config,
+            labels: Default::default(),
+            description: None,
         })
     }
 }
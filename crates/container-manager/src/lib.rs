@@ -17,11 +17,11 @@ mod tests {
     async fn test_container_creation_request_validation() {
         let request = CreateContainerRequest {
             name: "test-container".to_string(),
-            template: "alpine".to_string(),
+            template: models::Template::try_from("alpine").unwrap(),
             config: ContainerConfig {
                 cpu_limit: Some(2),
                 memory_limit: Some(1024 * 1024 * 1024), // 1GB
-                disk_limit: Some(10 * 1024 * 1024 * 1024), // 10GB
+                disk_limit: Some(models::DiskLimit::Bytes(10 * 1024 * 1024 * 1024)), // 10GB
                 network_interfaces: vec![ContainerNetworkInterface {
                     name: "eth0".to_string(),
                     bridge: "lxcbr0".to_string(),
@@ -34,7 +34,11 @@ mod tests {
                     ("USER".to_string(), "root".to_string()),
                     ("HOME".to_string(), "/root".to_string()),
                 ],
+                hostname: None,
+                cpu_request: None,
+                memory_request: None,
             },
+            description: None,
         };
 
         assert_eq!(request.name, "test-container");
@@ -89,12 +89,16 @@ async fn test_mock_container_create_and_list() {
         network_interfaces: vec![],
         rootfs_path: "".to_string(),
         environment: vec![],
+        hostname: None,
+        cpu_request: None,
+        memory_request: None,
     };
 
     let req = CreateContainerRequest {
         name: "test-container".to_string(),
-        template: "busybox".to_string(),
+        template: models::Template::try_from("busybox").unwrap(),
         config: config.clone(),
+        description: None,
     };
 
     // Call create
@@ -0,0 +1,98 @@
+use chrono::Utc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::DbError;
+use crate::store::{
+    delete_container_rows, format_timestamp, insert_container, invalid, ContainerStore, NameScope,
+    CONTAINER_COLUMNS,
+};
+use models::Container;
+
+impl ContainerStore {
+    /// Move a container out of the live table into `archived_containers`
+    ///
+    /// In one transaction the container is serialized to JSON, stored under
+    /// its id, and deleted along with its events and config history. Returns
+    /// the serialized copy. Bring it back with `restore_archived`.
+    pub async fn archive(&self, name: impl AsRef<str>) -> Result<String, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            if self.name_scope() == NameScope::PerTemplate {
+                return Err(DbError::InvalidData(
+                    "Names are unique per template; look up by name and template".to_string(),
+                ));
+            }
+
+            let mut tx = self.writer()?.begin().await?;
+
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE name = ?1",
+                CONTAINER_COLUMNS
+            ))
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let container = match row {
+                Some(row) => self.to_container(&row)?,
+                None => return Err(DbError::ContainerNotFound(name.to_string())),
+            };
+            let id = container.id.to_string();
+            let data = serde_json::to_string(&container)?;
+
+            sqlx::query(
+                "INSERT OR REPLACE INTO archived_containers (id, data, archived_at) VALUES (?1, ?2, ?3)",
+            )
+            .bind(&id)
+            .bind(&data)
+            .bind(format_timestamp(&Utc::now()))
+            .execute(&mut *tx)
+            .await?;
+            delete_container_rows(&mut tx, &id).await?;
+            tx.commit().await?;
+
+            info!(container.name = %name, container.id = %id, "Archived container");
+            Ok(data)
+        })
+        .await
+    }
+
+    /// Put an archived container back in the live table and drop the archive
+    ///
+    /// Fails with `ContainerAlreadyExists` if its name has been taken since,
+    /// and with `Validation` if the archived copy breaks today's rules, as
+    /// `create` would.
+    pub async fn restore_archived(&self, id: &Uuid) -> Result<Container, DbError> {
+        self.timed(async {
+            let writer = self.writer()?;
+
+            let data: Option<String> =
+                sqlx::query_scalar("SELECT data FROM archived_containers WHERE id = ?1")
+                    .bind(id.to_string())
+                    .fetch_optional(writer)
+                    .await?;
+            let data = data.ok_or_else(|| DbError::ContainerNotFound(id.to_string()))?;
+            let container: Container = serde_json::from_str(&data)?;
+            self.validate_container(&container)
+                .await?
+                .finish()
+                .map_err(invalid)?;
+
+            let mut tx = writer.begin().await?;
+            // Restored concurrently since it was read
+            let removed = sqlx::query("DELETE FROM archived_containers WHERE id = ?1")
+                .bind(id.to_string())
+                .execute(&mut *tx)
+                .await?;
+            if removed.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(id.to_string()));
+            }
+            insert_container(&mut tx, &container).await?;
+            tx.commit().await?;
+
+            info!(container.name = %container.name, container.id = %id, "Restored archived container");
+            Ok(container)
+        })
+        .await
+    }
+}
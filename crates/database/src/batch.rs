@@ -0,0 +1,114 @@
+use std::collections::BTreeSet;
+
+use futures::stream::{self, StreamExt};
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::{insert_container, new_container, ContainerStore};
+use crate::templates::TemplateStore;
+use models::{
+    validate_create_with, validate_disk_for_template, Container, ContainerConfig, MemoryDiskPolicy,
+    ValidationError, ValidationErrors,
+};
+
+/// How many batch entries are validated at once
+pub const BATCH_VALIDATION_CONCURRENCY: usize = 16;
+
+/// Validate `(name, template, config)` specs concurrently
+///
+/// Returns the errors of each failing entry keyed by its index, in index
+/// order; valid entries are omitted. Each entry yields to the runtime before
+/// validating so a large batch does not monopolize the worker.
+pub async fn validate_batch(
+    specs: &[(String, String, ContainerConfig)],
+) -> Vec<(usize, Vec<ValidationError>)> {
+    validate_batch_with(specs, MemoryDiskPolicy::default()).await
+}
+
+/// `validate_batch` with a chosen memory/disk policy
+pub async fn validate_batch_with(
+    specs: &[(String, String, ContainerConfig)],
+    memory_disk: MemoryDiskPolicy,
+) -> Vec<(usize, Vec<ValidationError>)> {
+    let mut failures: Vec<(usize, Vec<ValidationError>)> = stream::iter(specs.iter().enumerate())
+        .map(|(index, (name, template, config))| async move {
+            tokio::task::yield_now().await;
+            validate_create_with(name, template, config, memory_disk)
+                .err()
+                .map(|errors| (index, errors))
+        })
+        .buffer_unordered(BATCH_VALIDATION_CONCURRENCY)
+        .filter_map(|failure| async move { failure })
+        .collect()
+        .await;
+
+    failures.sort_by_key(|(index, _)| *index);
+    failures
+}
+
+impl ContainerStore {
+    /// Insert every `(name, template, config)` spec as a `Stopped` container,
+    /// all or nothing
+    ///
+    /// Each template must be registered in `templates`. Registration and disk
+    /// minimums are looked up once for the whole batch, and every failing
+    /// spec is reported in one `DbError::InvalidData` before anything is
+    /// inserted.
+    pub async fn create_many(
+        &self,
+        templates: &TemplateStore,
+        specs: &[(String, String, ContainerConfig)],
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            if specs.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mut problems: Vec<String> = validate_batch_with(specs, self.memory_disk_policy())
+                .await
+                .into_iter()
+                .map(|(index, errors)| {
+                    format!("Entry {}: {}", specs[index].0, ValidationErrors(errors))
+                })
+                .collect();
+
+            let names: Vec<String> = specs
+                .iter()
+                .map(|(_, template, _)| template.clone())
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            let min_disks = templates.min_disks(&names).await?;
+            for (name, template, config) in specs {
+                match min_disks.get(template) {
+                    None => problems.push(format!(
+                        "Entry {}: template {} is not registered",
+                        name, template
+                    )),
+                    Some(Some(min_disk)) => {
+                        if let Err(e) = validate_disk_for_template(config.disk_limit, *min_disk) {
+                            problems.push(format!("Entry {}: {}", name, ValidationErrors(vec![e])));
+                        }
+                    }
+                    Some(None) => {}
+                }
+            }
+            if !problems.is_empty() {
+                return Err(DbError::InvalidData(problems.join("; ")));
+            }
+
+            let mut tx = self.writer()?.begin().await?;
+            let mut created = Vec::with_capacity(specs.len());
+            for (name, template, config) in specs {
+                let container = new_container(name, template, config.clone());
+                insert_container(&mut tx, &container).await?;
+                created.push(container);
+            }
+            tx.commit().await?;
+
+            info!(created = created.len(), "Created container batch");
+            Ok(created)
+        })
+        .await
+    }
+}
@@ -0,0 +1,78 @@
+use std::collections::HashSet;
+
+use database::{create_pool, ContainerStore, PoolConfig};
+use uuid::Uuid;
+
+const USAGE: &str = "Usage: fsck [--node <uuid>]...";
+
+/// Known node ids from repeated `--node <uuid>` arguments
+fn parse_args() -> Result<Option<HashSet<Uuid>>, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        return Ok(None);
+    }
+
+    let mut nodes = HashSet::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--node", Some(id)) => {
+                nodes.insert(id.parse().map_err(|_| format!("Invalid node id: {}", id))?);
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(Some(nodes))
+}
+
+#[tokio::main]
+async fn main() {
+    let nodes = match parse_args() {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // The check never writes, so open the database read-only
+    let config = PoolConfig {
+        read_only: true,
+        ..PoolConfig::from_env()
+    };
+
+    let pool = match create_pool(&config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Failed to open database {}: {}", config.url, e);
+            std::process::exit(1);
+        }
+    };
+    let store = ContainerStore::from_config(pool, &config);
+
+    let result = match &nodes {
+        Some(nodes) => store.check_integrity_against(nodes).await,
+        None => store.check_integrity().await,
+    };
+    let issues = match result {
+        Ok(issues) => issues,
+        Err(e) => {
+            eprintln!("Integrity check failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for issue in &issues {
+        println!(
+            "{} ({}): {:?}: {}",
+            issue.name, issue.id, issue.kind, issue.detail
+        );
+    }
+
+    if issues.is_empty() {
+        println!("No problems found in {}", config.url);
+    } else {
+        println!("{} problem(s) found", issues.len());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,76 @@
+use database::{create_pool, migrations, PoolConfig};
+
+const USAGE: &str = "Usage: migrate [--up | --to <version> | --status]";
+
+enum Command {
+    Up,
+    To(i64),
+    Status,
+}
+
+fn parse_args() -> Result<Command, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        [] | ["--up"] => Ok(Command::Up),
+        ["--status"] => Ok(Command::Status),
+        ["--to", version] => version
+            .parse()
+            .map(Command::To)
+            .map_err(|_| format!("Invalid version: {}", version)),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let command = match parse_args() {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    // Migrating is how a database comes into being; `--status` only looks
+    let config = PoolConfig {
+        create_if_missing: !matches!(command, Command::Status),
+        ..PoolConfig::from_env()
+    };
+
+    let pool = match create_pool(&config).await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("Failed to open database {}: {}", config.url, e);
+            std::process::exit(1);
+        }
+    };
+
+    let result = match command {
+        Command::Up => migrations::run(&pool).await,
+        Command::To(version) => migrations::run_to(&pool, Some(version)).await,
+        Command::Status => match migrations::status(&pool).await {
+            Ok(status) => {
+                println!("Schema version: {}", status.current_version);
+                for applied in &status.applied {
+                    println!(
+                        "  {:>4}  {}  {}",
+                        applied.version, applied.applied_at, applied.description
+                    );
+                }
+                if !status.pending.is_empty() {
+                    println!("Pending: {:?}", status.pending);
+                }
+                return;
+            }
+            Err(e) => Err(e),
+        },
+    };
+
+    if let Err(e) = result {
+        eprintln!("Migration failed: {}", e);
+        std::process::exit(1);
+    }
+
+    println!("Migrations applied to {}", config.url);
+}
@@ -0,0 +1,129 @@
+#[cfg(feature = "actix")]
+use actix_web::http::StatusCode;
+#[cfg(feature = "actix")]
+use actix_web::{HttpResponse, ResponseError};
+#[cfg(feature = "actix")]
+use models::ErrorBody;
+use models::{ContainerStatus, ValidationError, ValidationErrors};
+use thiserror::Error;
+
+/// Errors from the store, template registry and migrations
+///
+/// With the default `actix` feature, `DbError` is an `actix_web::ResponseError`
+/// that maps to the matching HTTP status:
+///
+/// ```
+/// # #[cfg(feature = "actix")]
+/// # {
+/// use actix_web::ResponseError;
+///
+/// let err = database::DbError::ContainerNotFound("web".to_string());
+/// assert_eq!(err.status_code().as_u16(), 404);
+/// # }
+/// ```
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Container not found: {0}")]
+    ContainerNotFound(String),
+
+    #[error("Container already exists: {0}")]
+    ContainerAlreadyExists(String),
+
+    #[error("Template not found: {0}")]
+    TemplateNotFound(String),
+
+    #[error("Idempotency key reused with a different request: {0}")]
+    IdempotencyConflict(String),
+
+    #[error("Invalid status transition for {name}: {from} -> {to}")]
+    InvalidTransition {
+        name: String,
+        from: ContainerStatus,
+        to: ContainerStatus,
+    },
+
+    #[error("Container {name} is {status}; stop it first")]
+    ContainerBusy {
+        name: String,
+        status: ContainerStatus,
+    },
+
+    #[error("Container name {0} is used under more than one template")]
+    AmbiguousName(String),
+
+    #[error("Concurrent update conflict: {0}")]
+    Conflict(String),
+
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+
+    /// A stored row that cannot be decoded, such as a bad UUID or timestamp
+    #[error("Corrupt stored data: {0}")]
+    Corrupt(String),
+
+    /// A fault on the server side rather than in the request
+    #[error("Internal error: {0}")]
+    Internal(String),
+
+    /// Input rejected by the models validators, one entry per failed field
+    #[error("Validation failed: {}", ValidationErrors(.0.clone()))]
+    Validation(Vec<ValidationError>),
+
+    #[error("Migration error: {0}")]
+    MigrationError(String),
+
+    #[error("Operation timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Database error: {0}")]
+    Sqlx(#[from] sqlx::Error),
+}
+
+impl DbError {
+    /// Whether the operation may succeed if retried unchanged
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DbError::Timeout(_) => true,
+            DbError::Sqlx(sqlx::Error::PoolTimedOut) => true,
+            DbError::Sqlx(sqlx::Error::Database(e)) => {
+                // SQLITE_BUSY (5) and SQLITE_LOCKED (6). SQLite reports
+                // extended codes such as SQLITE_BUSY_SNAPSHOT (517), whose low
+                // byte is the primary code.
+                e.code()
+                    .and_then(|code| code.parse::<i32>().ok())
+                    .is_some_and(|code| matches!(code & 0xff, 5 | 6))
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "actix")]
+impl ResponseError for DbError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            DbError::ContainerNotFound(_) | DbError::TemplateNotFound(_) => StatusCode::NOT_FOUND,
+            DbError::ContainerAlreadyExists(_)
+            | DbError::IdempotencyConflict(_)
+            | DbError::InvalidTransition { .. }
+            | DbError::ContainerBusy { .. }
+            | DbError::AmbiguousName(_)
+            | DbError::Conflict(_) => StatusCode::CONFLICT,
+            DbError::InvalidData(_) | DbError::Validation(_) => StatusCode::BAD_REQUEST,
+            DbError::Corrupt(_) | DbError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            DbError::Timeout(_) => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = ErrorBody::new(self.to_string());
+        if let DbError::Validation(errors) = self {
+            body.fields = errors.clone();
+        }
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
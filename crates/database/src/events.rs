@@ -0,0 +1,407 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqliteConnection};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::error::DbError;
+use crate::store::{
+    format_timestamp, id_for_name, invalid, next_updated_at, one_named, parse_timestamp,
+    parse_uuid, ContainerStore, NameScope, CONTAINER_COLUMNS,
+};
+use models::{validate_reason, Container, ContainerEvent, ContainerStatus};
+
+pub const EVENT_ERROR: &str = "error";
+pub const EVENT_DRAIN: &str = "drain";
+pub const EVENT_STATUS: &str = "status";
+pub const EVENT_APPLY: &str = "apply";
+pub const EVENT_RESET: &str = "reset";
+
+/// A status correction applied by `reconcile_statuses`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusChange {
+    pub name: String,
+    pub from: ContainerStatus,
+    pub to: ContainerStatus,
+}
+
+/// What `reconcile_statuses` found
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReconcileReport {
+    /// Corrections applied, in observation order
+    pub changes: Vec<StatusChange>,
+    /// Observed names with no container on the node, for the caller to act on
+    pub unknown: Vec<String>,
+}
+
+/// A committed status change, as sent to `subscribe_status` receivers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusUpdate {
+    pub name: String,
+    pub status: ContainerStatus,
+}
+
+impl ContainerStore {
+    /// Wait until a container is in `target` and return it
+    ///
+    /// Returns at once if it already is. The subscription is taken before the
+    /// first read, so a change landing in between is not missed; a receiver
+    /// that falls behind re-reads the row instead of trusting the backlog.
+    /// Only changes made through this store's handles wake the wait, and the
+    /// per-operation query timeout does not apply: `timeout` bounds the whole
+    /// wait and fails with `DbError::Timeout`.
+    pub async fn wait_for_status(
+        &self,
+        name: impl AsRef<str>,
+        target: ContainerStatus,
+        timeout: Duration,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        let mut updates = self.subscribe_status();
+
+        let wait = async {
+            loop {
+                let container = self.get_by_name(name).await?;
+                if container.status == target {
+                    return Ok(container);
+                }
+
+                loop {
+                    match updates.recv().await {
+                        Ok(update) if update.name == name && update.status == target => break,
+                        Ok(_) => continue,
+                        Err(broadcast::error::RecvError::Lagged(_)) => break,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            return Err(DbError::Internal("Status updates closed".to_string()))
+                        }
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, wait)
+            .await
+            .map_err(|_| DbError::Timeout(timeout))?
+    }
+
+    /// Mark a container as errored and record why, in one transaction
+    pub async fn set_error(&self, name: impl AsRef<str>, reason: &str) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            validate_reason(reason).map_err(|e| invalid(vec![e]))?;
+
+            let mut tx = self.writer()?.begin().await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+
+            sqlx::query("UPDATE containers SET status = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(ContainerStatus::Error.as_str())
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+
+            record_event(&mut tx, &id, EVENT_ERROR, Some(reason)).await?;
+            tx.commit().await?;
+            self.notify_status(name, ContainerStatus::Error);
+
+            info!(container.name = %name, %reason, "Set container to error");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Return a stopped container to its freshly created state
+    ///
+    /// Clears the node assignment and any desired status and sets the status
+    /// to `Stopped`, recording a reset event, in one transaction. The id,
+    /// name, config, labels and description are kept. Active containers are
+    /// refused with `DbError::ContainerBusy`.
+    pub async fn reset(&self, name: impl AsRef<str>) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let rows: Vec<(String, String)> =
+                sqlx::query_as("SELECT id, status FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, stored) = one_named(name, rows)?;
+            let status = self.decode_status(&stored)?;
+            if status.is_active() {
+                return Err(DbError::ContainerBusy {
+                    name: name.to_string(),
+                    status,
+                });
+            }
+
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let row = sqlx::query(&format!(
+                "UPDATE containers SET status = ?1, node_id = NULL, desired_status = NULL, updated_at = ?2
+                 WHERE id = ?3
+                 RETURNING {}",
+                CONTAINER_COLUMNS
+            ))
+            .bind(ContainerStatus::Stopped.as_str())
+            .bind(&now)
+            .bind(&id)
+            .fetch_all(&mut *tx)
+            .await?
+            .pop()
+            .ok_or_else(|| DbError::ContainerNotFound(name.to_string()))?;
+            let container = self.to_container(&row)?;
+
+            let message = format!("{} -> {}", status, ContainerStatus::Stopped);
+            record_event(&mut tx, &container.id.to_string(), EVENT_RESET, Some(&message)).await?;
+            tx.commit().await?;
+            self.notify_status(name, ContainerStatus::Stopped);
+
+            info!(container.name = %name, from = %status, "Reset container");
+            Ok(container)
+        })
+        .await
+    }
+
+    /// Bring stored statuses in line with what a node agent observed
+    ///
+    /// Only containers assigned to `node_id` are touched. Each drifted row is
+    /// updated and gets a status event, all in one transaction. Observed names
+    /// with no matching container on that node are never created; they come
+    /// back in `ReconcileReport::unknown`.
+    pub async fn reconcile_statuses(
+        &self,
+        node_id: &Uuid,
+        observed: &[(String, ContainerStatus)],
+    ) -> Result<ReconcileReport, DbError> {
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+            let mut changes = Vec::new();
+            let mut unknown = Vec::new();
+
+            for (name, to) in observed {
+                let mut rows: Vec<(String, String)> = sqlx::query_as(
+                    "SELECT id, status FROM containers WHERE name = ?1 AND node_id = ?2 LIMIT 2",
+                )
+                .bind(name)
+                .bind(node_id.to_string())
+                .fetch_all(&mut *tx)
+                .await?;
+                if rows.len() > 1 {
+                    return Err(DbError::AmbiguousName(name.clone()));
+                }
+
+                let Some((id, stored)) = rows.pop() else {
+                    warn!(container.name = %name, %node_id, "Observed container is not on this node");
+                    unknown.push(name.clone());
+                    continue;
+                };
+                let from = self.decode_status(&stored)?;
+                if from == *to {
+                    continue;
+                }
+
+                let now = next_updated_at(&mut *tx, &id).await?;
+                sqlx::query("UPDATE containers SET status = ?1, updated_at = ?2 WHERE id = ?3")
+                    .bind(to.as_str())
+                    .bind(&now)
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+                let message = format!("{} -> {} (observed on node {})", from, to, node_id);
+                record_event(&mut tx, &id, EVENT_STATUS, Some(&message)).await?;
+
+                changes.push(StatusChange {
+                    name: name.clone(),
+                    from,
+                    to: to.clone(),
+                });
+            }
+
+            tx.commit().await?;
+            for change in &changes {
+                self.notify_status(&change.name, change.to.clone());
+            }
+
+            info!(
+                %node_id,
+                changed = changes.len(),
+                unknown = unknown.len(),
+                "Reconciled container statuses"
+            );
+            Ok(ReconcileReport { changes, unknown })
+        })
+        .await
+    }
+
+    /// A container's events, newest first
+    pub async fn list_events(&self, name: impl AsRef<str>) -> Result<Vec<ContainerEvent>, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let container = self.get_by_name(name).await?;
+
+            let rows = sqlx::query(
+                "SELECT id, container_id, event_type, message, created_at FROM container_events
+                 WHERE container_id = ?1 ORDER BY created_at DESC, id DESC",
+            )
+            .bind(container.id.to_string())
+            .fetch_all(self.pool())
+            .await?;
+
+            rows.iter().map(row_to_event).collect()
+        })
+        .await
+    }
+
+    /// Lazily yield every event created at or after `since` as NDJSON
+    ///
+    /// Oldest first; each item is one serialized `ContainerEvent` ending in a
+    /// newline, ready to append to a log shipper's input. Like `stream`, the
+    /// per-operation query timeout does not apply.
+    pub fn stream_events_ndjson(
+        &self,
+        since: DateTime<Utc>,
+    ) -> impl Stream<Item = Result<String, DbError>> + '_ {
+        sqlx::query(
+            "SELECT id, container_id, event_type, message, created_at FROM container_events
+             WHERE created_at >= ?1 ORDER BY created_at ASC, id ASC",
+        )
+        .bind(format_timestamp(&since))
+        .fetch(self.pool())
+        .map(|row| {
+            let event = row_to_event(&row?)?;
+            let mut line = serde_json::to_string(&event)?;
+            line.push('\n');
+            Ok(line)
+        })
+    }
+
+    /// Delete events created before `older_than`, returning how many went
+    pub async fn prune_events(&self, older_than: DateTime<Utc>) -> Result<u64, DbError> {
+        self.timed(async {
+            let result = sqlx::query("DELETE FROM container_events WHERE created_at < ?1")
+                .bind(format_timestamp(&older_than))
+                .execute(self.writer()?)
+                .await?;
+
+            info!(%older_than, deleted = result.rows_affected(), "Pruned container events");
+            Ok(result.rows_affected())
+        })
+        .await
+    }
+
+    /// Delete all but each container's `keep_last` newest events
+    pub async fn prune_events_keep_last(&self, keep_last: usize) -> Result<u64, DbError> {
+        self.timed(async {
+            let result = sqlx::query(
+                "DELETE FROM container_events WHERE id IN (
+                    SELECT id FROM (
+                        SELECT id, ROW_NUMBER() OVER (
+                            PARTITION BY container_id ORDER BY created_at DESC, id DESC
+                        ) AS position
+                        FROM container_events
+                    ) WHERE position > ?1
+                 )",
+            )
+            .bind(keep_last as i64)
+            .execute(self.writer()?)
+            .await?;
+
+            info!(
+                keep_last,
+                deleted = result.rows_affected(),
+                "Pruned container events"
+            );
+            Ok(result.rows_affected())
+        })
+        .await
+    }
+
+    /// A container and its `event_limit` newest events, read in one transaction
+    pub async fn get_with_events(
+        &self,
+        name: impl AsRef<str>,
+        event_limit: i64,
+    ) -> Result<(Container, Vec<ContainerEvent>), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            if event_limit < 0 {
+                return Err(DbError::InvalidData(format!(
+                    "Invalid event limit: {}",
+                    event_limit
+                )));
+            }
+            if self.name_scope() == NameScope::PerTemplate {
+                return Err(DbError::InvalidData(
+                    "Names are unique per template; look up by name and template".to_string(),
+                ));
+            }
+
+            let mut tx = self.pool().begin().await?;
+
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE name = ?1",
+                CONTAINER_COLUMNS
+            ))
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let container = match row {
+                Some(row) => self.to_container(&row)?,
+                None => return Err(DbError::ContainerNotFound(name.to_string())),
+            };
+
+            let rows = sqlx::query(
+                "SELECT id, container_id, event_type, message, created_at FROM container_events
+                 WHERE container_id = ?1 ORDER BY created_at DESC, id DESC LIMIT ?2",
+            )
+            .bind(container.id.to_string())
+            .bind(event_limit)
+            .fetch_all(&mut *tx)
+            .await?;
+            let events = rows.iter().map(row_to_event).collect::<Result<_, _>>()?;
+
+            tx.commit().await?;
+            Ok((container, events))
+        })
+        .await
+    }
+}
+
+/// Append an event row inside the caller's transaction
+pub(crate) async fn record_event(
+    conn: &mut SqliteConnection,
+    container_id: &str,
+    event_type: &str,
+    message: Option<&str>,
+) -> Result<(), DbError> {
+    sqlx::query(
+        "INSERT INTO container_events (container_id, event_type, message, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+    )
+    .bind(container_id)
+    .bind(event_type)
+    .bind(message)
+    .bind(format_timestamp(&Utc::now()))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub(crate) fn row_to_event(row: &SqliteRow) -> Result<ContainerEvent, DbError> {
+    let container_id: String = row.try_get("container_id")?;
+    let created_at: String = row.try_get("created_at")?;
+
+    Ok(ContainerEvent {
+        id: row.try_get("id")?,
+        container_id: parse_uuid(&container_id)?,
+        event_type: row.try_get("event_type")?,
+        message: row.try_get("message")?,
+        created_at: parse_timestamp(&created_at)?,
+    })
+}
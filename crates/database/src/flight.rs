@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use tokio::sync::watch;
+
+/// Coalesces concurrent lookups of the same key into one
+///
+/// The first caller for a key runs the lookup; callers arriving while it is
+/// in flight wait and get a clone of its result. Errors are not shared: if
+/// the running lookup fails or is cancelled, each waiter runs its own.
+pub(crate) struct SingleFlight<T> {
+    inflight: Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+}
+
+enum Role<T> {
+    Leader(watch::Sender<Option<T>>),
+    Waiter(watch::Receiver<Option<T>>),
+}
+
+/// Clears the leader's entry however its lookup ends
+struct InflightGuard<'a, T> {
+    flight: &'a SingleFlight<T>,
+    key: &'a str,
+}
+
+impl<T> Drop for InflightGuard<'_, T> {
+    fn drop(&mut self) {
+        self.flight
+            .inflight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(self.key);
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) async fn run<E, F, Fut>(&self, key: &str, lookup: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let role = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+            match inflight.get(key) {
+                Some(receiver) => Role::Waiter(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    inflight.insert(key.to_string(), receiver);
+                    Role::Leader(sender)
+                }
+            }
+        };
+
+        match role {
+            Role::Leader(sender) => {
+                let _guard = InflightGuard { flight: self, key };
+                let value = lookup().await?;
+                sender.send_replace(Some(value.clone()));
+                Ok(value)
+            }
+            Role::Waiter(mut receiver) => {
+                let shared = receiver
+                    .wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|value| value.clone());
+                match shared {
+                    Some(value) => Ok(value),
+                    None => lookup().await,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::Utc;
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::{format_timestamp, invalid, next_updated_at, one_named, ContainerStore};
+use models::{validate_container_config_with, ContainerConfig};
+
+impl ContainerStore {
+    /// Replace a container's config, keeping the old one so it can be reverted
+    ///
+    /// Only the newest `config_history_depth` entries per container are kept.
+    pub async fn update_config_versioned(
+        &self,
+        name: impl AsRef<str>,
+        config: &ContainerConfig,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            validate_container_config_with(config, self.memory_disk_policy()).map_err(invalid)?;
+
+            let mut tx = self.writer()?.begin().await?;
+            let now = format_timestamp(&Utc::now());
+
+            let current: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, status, previous) = one_named(name, current)?;
+            self.check_limits_unchanged(name, &status, &serde_json::from_str(&previous)?, config)?;
+
+            sqlx::query(
+                "INSERT INTO config_history (container_id, config, replaced_at) VALUES (?1, ?2, ?3)",
+            )
+            .bind(&id)
+            .bind(&previous)
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+
+            let updated_at = next_updated_at(&mut *tx, &id).await?;
+            sqlx::query("UPDATE containers SET config = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(serde_json::to_string(&config.normalized())?)
+                .bind(&updated_at)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(
+                "DELETE FROM config_history WHERE container_id = ?1 AND id NOT IN (
+                    SELECT id FROM config_history WHERE container_id = ?1
+                    ORDER BY id DESC LIMIT ?2
+                 )",
+            )
+            .bind(&id)
+            .bind(self.config_history_depth() as i64)
+            .execute(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            info!(container.name = %name, "Updated container config (versioned)");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Restore the config replaced by the latest `update_config_versioned`
+    ///
+    /// The history entry is consumed, so repeated reverts walk further back.
+    /// Like `update_config`, a revert that would change CPU or memory limits
+    /// is refused while the container is active.
+    pub async fn revert_config(&self, name: impl AsRef<str>) -> Result<ContainerConfig, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let current: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, status, stored) = one_named(name, current)?;
+
+            let entry: Option<(i64, String)> = sqlx::query_as(
+                "SELECT id, config FROM config_history WHERE container_id = ?1
+                 ORDER BY id DESC LIMIT 1",
+            )
+            .bind(&id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let (entry_id, previous) = entry.ok_or_else(|| {
+                DbError::InvalidData(format!("No previous config to revert to for {}", name))
+            })?;
+            let config: ContainerConfig = serde_json::from_str(&previous)?;
+            self.check_limits_unchanged(name, &status, &serde_json::from_str(&stored)?, &config)?;
+
+            let now = next_updated_at(&mut *tx, &id).await?;
+            sqlx::query("UPDATE containers SET config = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(&previous)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query("DELETE FROM config_history WHERE id = ?1")
+                .bind(entry_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            info!(container.name = %name, "Reverted container config");
+            Ok(config)
+        })
+        .await
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::error::DbError;
+use crate::store::{ContainerStore, StatusDecoding, CONTAINER_COLUMNS};
+use models::ContainerStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The row cannot be decoded into a `Container` (bad UUID, JSON, timestamp)
+    Undecodable,
+    /// The stored status is not a known `ContainerStatus`
+    InvalidStatus,
+    /// `node_id` names a node that is not in the known set
+    OrphanedNode,
+}
+
+/// One problem found by `check_integrity`
+#[derive(Debug, Clone, Serialize)]
+pub struct IntegrityIssue {
+    /// Raw `id` column, which may itself be the broken value
+    pub id: String,
+    pub name: String,
+    pub kind: IssueKind,
+    pub detail: String,
+}
+
+impl ContainerStore {
+    /// Scan every row and report what fails to decode, without stopping at
+    /// the first problem
+    pub async fn check_integrity(&self) -> Result<Vec<IntegrityIssue>, DbError> {
+        self.scan_integrity(None).await
+    }
+
+    /// `check_integrity`, also flagging containers placed on nodes outside `nodes`
+    pub async fn check_integrity_against(
+        &self,
+        nodes: &HashSet<Uuid>,
+    ) -> Result<Vec<IntegrityIssue>, DbError> {
+        self.scan_integrity(Some(nodes)).await
+    }
+
+    async fn scan_integrity(
+        &self,
+        nodes: Option<&HashSet<Uuid>>,
+    ) -> Result<Vec<IntegrityIssue>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(self.pool())
+            .await?;
+
+            // Bad statuses are reported as InvalidStatus, not also as Undecodable
+            let lenient = self.clone().with_status_decoding(StatusDecoding::Lenient);
+            let mut issues = Vec::new();
+            for row in &rows {
+                let id: String = row.try_get("id")?;
+                let name: String = row.try_get("name")?;
+                let issue = |kind, detail: String| IntegrityIssue {
+                    id: id.clone(),
+                    name: name.clone(),
+                    kind,
+                    detail,
+                };
+
+                let status: String = row.try_get("status")?;
+                if let Err(e) = status.parse::<ContainerStatus>() {
+                    issues.push(issue(IssueKind::InvalidStatus, e));
+                }
+
+                match lenient.to_container(row) {
+                    Ok(container) => {
+                        if let (Some(nodes), Some(node_id)) = (nodes, container.node_id) {
+                            if !nodes.contains(&node_id) {
+                                issues.push(issue(
+                                    IssueKind::OrphanedNode,
+                                    format!("Unknown node {}", node_id),
+                                ));
+                            }
+                        }
+                    }
+                    Err(e) => issues.push(issue(IssueKind::Undecodable, e.to_string())),
+                }
+            }
+
+            Ok(issues)
+        })
+        .await
+    }
+}
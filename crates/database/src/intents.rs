@@ -0,0 +1,78 @@
+use serde::Serialize;
+use sqlx::Row;
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::{begin_write, id_for_name, next_updated_at, ContainerStore, CONTAINER_COLUMNS};
+use models::{Container, ContainerStatus};
+
+/// A container whose recorded status differs from the one requested for it
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingTransition {
+    pub container: Container,
+    pub desired_status: ContainerStatus,
+}
+
+impl ContainerStore {
+    /// Record the status a container should reach, without waiting for it
+    ///
+    /// The node agent picks this up from `list_pending_transitions` and
+    /// reports progress with `update_status`.
+    pub async fn set_desired_status(
+        &self,
+        name: impl AsRef<str>,
+        status: ContainerStatus,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result = sqlx::query(
+                "UPDATE containers SET desired_status = ?1, updated_at = ?2 WHERE id = ?3",
+            )
+            .bind(status.as_str())
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            info!(container.name = %name, desired_status = %status, "Set desired container status");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Containers not yet in their desired status, least recently updated first
+    ///
+    /// Ordered by `updated_at`, which every write bumps, so a container
+    /// touched after its request was set moves back in the queue.
+    pub async fn list_pending_transitions(&self) -> Result<Vec<PendingTransition>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {}, desired_status FROM containers
+                 WHERE desired_status IS NOT NULL AND desired_status != status
+                 ORDER BY updated_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(self.pool())
+            .await?;
+
+            rows.iter()
+                .map(|row| {
+                    let desired: String = row.try_get("desired_status")?;
+                    Ok(PendingTransition {
+                        container: self.to_container(row)?,
+                        desired_status: self.decode_status(&desired)?,
+                    })
+                })
+                .collect()
+        })
+        .await
+    }
+}
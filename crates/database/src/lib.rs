@@ -0,0 +1,30 @@
+pub mod archive;
+pub mod batch;
+pub mod error;
+pub mod events;
+mod flight;
+pub mod history;
+pub mod integrity;
+pub mod intents;
+pub mod migrations;
+pub mod plan;
+pub mod pool;
+pub mod store;
+pub mod sync;
+pub mod templates;
+pub mod tx;
+
+pub use batch::{validate_batch, validate_batch_with};
+pub use error::*;
+pub use events::{ReconcileReport, StatusChange, StatusUpdate};
+pub use integrity::{IntegrityIssue, IssueKind};
+pub use intents::PendingTransition;
+pub use plan::{ApplyReport, Plan};
+pub use pool::*;
+pub use store::*;
+pub use sync::SyncReport;
+pub use templates::TemplateStore;
+pub use tx::TxContainerStore;
+
+#[cfg(test)]
+mod tests;
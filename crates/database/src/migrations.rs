@@ -0,0 +1,398 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{Executor, SqlitePool};
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::NameScope;
+use models::fnv1a;
+
+/// A single versioned schema change
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    /// Stable fingerprint of `sql`, recorded when the migration is applied
+    ///
+    /// FNV-1a rather than `DefaultHasher`, whose output may change between
+    /// Rust releases.
+    pub fn checksum(&self) -> String {
+        format!("{:016x}", fnv1a(self.sql.as_bytes()))
+    }
+}
+
+/// All schema migrations, in the order they must be applied
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create containers table",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS containers (
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL UNIQUE,
+            status TEXT NOT NULL,
+            template TEXT NOT NULL,
+            node_id TEXT,
+            config TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_containers_status ON containers(status);
+        CREATE INDEX IF NOT EXISTS idx_containers_created_at ON containers(created_at);
+    "#,
+    },
+    Migration {
+        version: 2,
+        description: "add container labels",
+        sql: "ALTER TABLE containers ADD COLUMN labels TEXT NOT NULL DEFAULT '{}'",
+    },
+    Migration {
+        version: 3,
+        description: "add container description",
+        sql: "ALTER TABLE containers ADD COLUMN description TEXT",
+    },
+    Migration {
+        version: 4,
+        description: "create container events table",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS container_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            container_id TEXT NOT NULL REFERENCES containers(id),
+            event_type TEXT NOT NULL,
+            message TEXT,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_container_events_container
+            ON container_events(container_id, created_at);
+    "#,
+    },
+    Migration {
+        version: 5,
+        description: "index containers by template",
+        sql: "CREATE INDEX IF NOT EXISTS idx_containers_template ON containers(template)",
+    },
+    Migration {
+        version: 6,
+        description: "add container idempotency key",
+        sql: r#"
+        ALTER TABLE containers ADD COLUMN idempotency_key TEXT;
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_containers_idempotency_key
+            ON containers(idempotency_key);
+    "#,
+    },
+    Migration {
+        version: 7,
+        description: "move name uniqueness into indexes",
+        // SQLite cannot drop a column constraint, so the table is rebuilt.
+        // Events are set aside first so dropping containers orphans nothing.
+        sql: r#"
+        CREATE TABLE container_events_backup AS SELECT * FROM container_events;
+        DROP TABLE container_events;
+        CREATE TABLE containers_new (
+            id TEXT PRIMARY KEY NOT NULL,
+            name TEXT NOT NULL,
+            status TEXT NOT NULL,
+            template TEXT NOT NULL,
+            node_id TEXT,
+            config TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            labels TEXT NOT NULL DEFAULT '{}',
+            description TEXT,
+            idempotency_key TEXT
+        );
+        INSERT INTO containers_new
+            SELECT id, name, status, template, node_id, config, created_at, updated_at,
+                   labels, description, idempotency_key
+            FROM containers;
+        DROP TABLE containers;
+        ALTER TABLE containers_new RENAME TO containers;
+        CREATE INDEX IF NOT EXISTS idx_containers_status ON containers(status);
+        CREATE INDEX IF NOT EXISTS idx_containers_created_at ON containers(created_at);
+        CREATE INDEX IF NOT EXISTS idx_containers_template ON containers(template);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_containers_idempotency_key
+            ON containers(idempotency_key);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_containers_name_template
+            ON containers(name, template);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_containers_name ON containers(name);
+        CREATE TABLE container_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            container_id TEXT NOT NULL REFERENCES containers(id),
+            event_type TEXT NOT NULL,
+            message TEXT,
+            created_at TEXT NOT NULL
+        );
+        INSERT INTO container_events SELECT * FROM container_events_backup;
+        DROP TABLE container_events_backup;
+        CREATE INDEX IF NOT EXISTS idx_container_events_container
+            ON container_events(container_id, created_at);
+    "#,
+    },
+    Migration {
+        version: 8,
+        description: "create templates registry",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS templates (
+            name TEXT PRIMARY KEY NOT NULL,
+            min_disk_bytes INTEGER,
+            created_at TEXT NOT NULL
+        );
+    "#,
+    },
+    Migration {
+        version: 9,
+        description: "create config history table",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS config_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            container_id TEXT NOT NULL REFERENCES containers(id),
+            config TEXT NOT NULL,
+            replaced_at TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_config_history_container
+            ON config_history(container_id, id);
+    "#,
+    },
+    Migration {
+        version: 10,
+        description: "add container desired status",
+        sql: r#"
+        ALTER TABLE containers ADD COLUMN desired_status TEXT;
+        CREATE INDEX IF NOT EXISTS idx_containers_desired_status
+            ON containers(desired_status) WHERE desired_status IS NOT NULL;
+    "#,
+    },
+    Migration {
+        version: 11,
+        description: "index containers by update time",
+        sql: "CREATE INDEX IF NOT EXISTS idx_containers_updated_at ON containers(updated_at)",
+    },
+    Migration {
+        version: 12,
+        description: "create archived containers table",
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS archived_containers (
+            id TEXT PRIMARY KEY,
+            data TEXT NOT NULL,
+            archived_at TEXT NOT NULL
+        );
+    "#,
+    },
+    // Before `DiskLimit`, a null disk limit meant no cap; it now means the
+    // deployment default, so stored nulls are rewritten to "unlimited"
+    Migration {
+        version: 13,
+        description: "make stored unlimited disk limits explicit",
+        sql: r#"
+        UPDATE containers SET config = json_set(config, '$.disk_limit', 'unlimited')
+            WHERE json_type(config, '$.disk_limit') = 'null';
+        UPDATE config_history SET config = json_set(config, '$.disk_limit', 'unlimited')
+            WHERE json_type(config, '$.disk_limit') = 'null';
+        UPDATE archived_containers SET data = json_set(data, '$.config.disk_limit', 'unlimited')
+            WHERE json_type(data, '$.config.disk_limit') = 'null';
+    "#,
+    },
+];
+
+/// A migration recorded in `schema_migrations`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub description: String,
+    pub applied_at: String,
+}
+
+/// Where a database stands relative to `MIGRATIONS`
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    /// Highest applied version, 0 for a fresh database
+    pub current_version: i64,
+    pub applied: Vec<AppliedMigration>,
+    /// Versions in `MIGRATIONS` that have not been applied
+    pub pending: Vec<i64>,
+}
+
+/// Apply every migration that has not been recorded in `schema_migrations` yet
+pub async fn run(pool: &SqlitePool) -> Result<(), DbError> {
+    run_to(pool, None).await
+}
+
+/// Report the applied and pending migrations
+pub async fn status(pool: &SqlitePool) -> Result<MigrationStatus, DbError> {
+    ensure_migrations_table(pool).await?;
+
+    let applied: Vec<AppliedMigration> = sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT version, description, applied_at FROM schema_migrations ORDER BY version ASC",
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(version, description, applied_at)| AppliedMigration {
+        version,
+        description,
+        applied_at,
+    })
+    .collect();
+
+    let pending = MIGRATIONS
+        .iter()
+        .map(|m| m.version)
+        .filter(|version| !applied.iter().any(|a| a.version == *version))
+        .collect();
+
+    Ok(MigrationStatus {
+        current_version: applied.iter().map(|a| a.version).max().unwrap_or(0),
+        applied,
+        pending,
+    })
+}
+
+/// Apply unapplied migrations up to and including `target`, or all of them
+///
+/// Fails without applying anything if an already-applied migration's SQL no
+/// longer matches the checksum recorded for it.
+pub async fn run_to(pool: &SqlitePool, target: Option<i64>) -> Result<(), DbError> {
+    apply(pool, MIGRATIONS, target).await
+}
+
+pub(crate) async fn apply(
+    pool: &SqlitePool,
+    migrations: &[Migration],
+    target: Option<i64>,
+) -> Result<(), DbError> {
+    if let Some(target) = target {
+        if !migrations.iter().any(|m| m.version == target) {
+            return Err(DbError::MigrationError(format!(
+                "unknown migration version {}",
+                target
+            )));
+        }
+    }
+
+    ensure_migrations_table(pool).await?;
+    verify_checksums(pool, migrations).await?;
+
+    for migration in migrations {
+        if target.is_some_and(|target| migration.version > target) {
+            break;
+        }
+
+        let applied: Option<i64> =
+            sqlx::query_scalar("SELECT version FROM schema_migrations WHERE version = ?1")
+                .bind(migration.version)
+                .fetch_optional(pool)
+                .await?;
+        if applied.is_some() {
+            continue;
+        }
+
+        info!(
+            version = migration.version,
+            description = migration.description,
+            "Applying migration"
+        );
+
+        let mut tx = pool.begin().await?;
+        tx.execute(migration.sql).await.map_err(|e| {
+            DbError::MigrationError(format!("migration {} failed: {}", migration.version, e))
+        })?;
+        sqlx::query(
+            "INSERT INTO schema_migrations (version, description, applied_at, checksum)
+             VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(Utc::now().to_rfc3339())
+        .bind(migration.checksum())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+async fn ensure_migrations_table(pool: &SqlitePool) -> Result<(), DbError> {
+    pool.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY NOT NULL,
+            description TEXT NOT NULL,
+            applied_at TEXT NOT NULL,
+            checksum TEXT
+        )
+        "#,
+    )
+    .await?;
+
+    // Tables created before checksums were recorded lack the column
+    let has_checksum: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('schema_migrations') WHERE name = 'checksum'",
+    )
+    .fetch_one(pool)
+    .await?;
+    if !has_checksum {
+        pool.execute("ALTER TABLE schema_migrations ADD COLUMN checksum TEXT")
+            .await?;
+    }
+    Ok(())
+}
+
+/// Compare recorded checksums with the migrations in code
+///
+/// Rows applied before checksums existed are stamped with the current
+/// checksum instead of being checked.
+async fn verify_checksums(pool: &SqlitePool, migrations: &[Migration]) -> Result<(), DbError> {
+    let recorded: Vec<(i64, Option<String>)> =
+        sqlx::query_as("SELECT version, checksum FROM schema_migrations")
+            .fetch_all(pool)
+            .await?;
+
+    for (version, checksum) in recorded {
+        let Some(migration) = migrations.iter().find(|m| m.version == version) else {
+            continue;
+        };
+        match checksum {
+            Some(checksum) if checksum != migration.checksum() => {
+                return Err(DbError::MigrationError(format!(
+                    "migration {} was modified after it was applied (recorded checksum {}, found {})",
+                    version,
+                    checksum,
+                    migration.checksum()
+                )));
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query("UPDATE schema_migrations SET checksum = ?1 WHERE version = ?2")
+                    .bind(migration.checksum())
+                    .bind(version)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Make the name-uniqueness index match `scope`
+///
+/// `(name, template)` is always unique; `Global` additionally keeps the
+/// unique index on `name`. Switching back to `Global` fails while two
+/// containers share a name.
+pub async fn apply_name_scope(pool: &SqlitePool, scope: NameScope) -> Result<(), DbError> {
+    let sql = match scope {
+        NameScope::Global => {
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_containers_name ON containers(name)"
+        }
+        NameScope::PerTemplate => "DROP INDEX IF EXISTS idx_containers_name",
+    };
+    pool.execute(sql).await.map_err(|e| {
+        DbError::MigrationError(format!("cannot apply name scope {:?}: {}", scope, e))
+    })?;
+
+    info!(?scope, "Applied container name scope");
+    Ok(())
+}
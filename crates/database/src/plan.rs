@@ -0,0 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+use tracing::info;
+
+use crate::error::DbError;
+use crate::events::{record_event, EVENT_APPLY};
+use crate::store::{
+    delete_container_rows, id_for_name, insert_container, new_container, next_updated_at,
+    one_named, ContainerStore, NameScope,
+};
+use models::{Container, ValidationErrors};
+
+/// How to turn the stored containers into a desired set, from `plan`
+///
+/// Entries are whole containers: desired ones for `to_create` and
+/// `to_update`, stored ones for `to_delete`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    pub to_create: Vec<Container>,
+    pub to_update: Vec<Container>,
+    pub to_delete: Vec<Container>,
+}
+
+impl Plan {
+    pub fn is_empty(&self) -> bool {
+        self.to_create.is_empty() && self.to_update.is_empty() && self.to_delete.is_empty()
+    }
+}
+
+/// What `apply` did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ApplyReport {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+impl ContainerStore {
+    /// Compare `desired` against the table by name, without changing anything
+    ///
+    /// A stored container is updated when its template, config (per
+    /// `ContainerConfig::diff`), labels or description differ. Ids, statuses,
+    /// placement and timestamps are runtime state and never cause an update.
+    /// Stored containers missing from `desired` are deleted. Names must be
+    /// globally unique, so a `PerTemplate` store refuses to plan.
+    pub async fn plan(&self, desired: &[Container]) -> Result<Plan, DbError> {
+        self.timed(async {
+            refuse_per_template(self)?;
+
+            let mut seen = HashSet::new();
+            for container in desired {
+                if !seen.insert(container.name.as_str()) {
+                    return Err(DbError::InvalidData(format!(
+                        "Container {} appears more than once in the desired set",
+                        container.name
+                    )));
+                }
+            }
+
+            let current = self.list().await?;
+            let by_name: HashMap<&str, &Container> =
+                current.iter().map(|c| (c.name.as_str(), c)).collect();
+
+            let mut plan = Plan::default();
+            for container in desired {
+                match by_name.get(container.name.as_str()) {
+                    None => plan.to_create.push(container.clone()),
+                    Some(stored) if differs(stored, container) => {
+                        plan.to_update.push(container.clone())
+                    }
+                    Some(_) => {}
+                }
+            }
+            plan.to_delete = current
+                .iter()
+                .filter(|c| !seen.contains(c.name.as_str()))
+                .cloned()
+                .collect();
+
+            info!(
+                create = plan.to_create.len(),
+                update = plan.to_update.len(),
+                delete = plan.to_delete.len(),
+                "Planned containers"
+            );
+            Ok(plan)
+        })
+        .await
+    }
+
+    /// Carry out a `plan` in one transaction
+    ///
+    /// Every create and update is validated as `create` would, labels and
+    /// description included, before anything is written; the first invalid
+    /// entry aborts with its name. Deletes run first so a name
+    /// can be freed and reused, then updates, then creates. Created
+    /// containers get a fresh id and start `Stopped`, like `create`; updated
+    /// ones keep their id, status and placement. Creates and updates record
+    /// an `apply` event. Any failure, including a plan gone stale, rolls the
+    /// whole apply back.
+    pub async fn apply(&self, plan: Plan) -> Result<ApplyReport, DbError> {
+        self.timed(async {
+            refuse_per_template(self)?;
+
+            for container in plan.to_create.iter().chain(&plan.to_update) {
+                self.validate_container(container)
+                    .await?
+                    .finish()
+                    .map_err(|errors| {
+                        DbError::InvalidData(format!(
+                            "Entry {}: {}",
+                            container.name,
+                            ValidationErrors(errors)
+                        ))
+                    })?;
+            }
+
+            let mut tx = self.writer()?.begin().await?;
+
+            for container in &plan.to_delete {
+                let id = id_for_name(&mut *tx, &container.name).await?;
+                delete_container_rows(&mut tx, &id).await?;
+            }
+
+            for container in &plan.to_update {
+                let current: Vec<(String, String, String)> = sqlx::query_as(
+                    "SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2",
+                )
+                .bind(container.name.as_str())
+                .fetch_all(&mut *tx)
+                .await?;
+                let (id, status, previous) = one_named(&container.name, current)?;
+                self.check_limits_unchanged(
+                    &container.name,
+                    &status,
+                    &serde_json::from_str(&previous)?,
+                    &container.config,
+                )?;
+
+                let now = next_updated_at(&mut *tx, &id).await?;
+                sqlx::query(
+                    "UPDATE containers SET template = ?1, config = ?2, labels = ?3,
+                        description = ?4, updated_at = ?5
+                     WHERE id = ?6",
+                )
+                .bind(&container.template)
+                .bind(serde_json::to_string(&container.config.normalized())?)
+                .bind(serde_json::to_string(&container.labels)?)
+                .bind(&container.description)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+                record_event(&mut tx, &id, EVENT_APPLY, Some("updated")).await?;
+            }
+
+            for desired in &plan.to_create {
+                let mut container =
+                    new_container(&desired.name, &desired.template, desired.config.clone());
+                container.labels = desired.labels.clone();
+                container.description = desired.description.clone();
+                insert_container(&mut tx, &container).await?;
+                record_event(
+                    &mut tx,
+                    &container.id.to_string(),
+                    EVENT_APPLY,
+                    Some("created"),
+                )
+                .await?;
+            }
+
+            tx.commit().await?;
+
+            let report = ApplyReport {
+                created: plan.to_create.len(),
+                updated: plan.to_update.len(),
+                deleted: plan.to_delete.len(),
+            };
+            info!(
+                created = report.created,
+                updated = report.updated,
+                deleted = report.deleted,
+                "Applied container plan"
+            );
+            Ok(report)
+        })
+        .await
+    }
+}
+
+/// `plan` and `apply` match containers by bare name
+fn refuse_per_template(store: &ContainerStore) -> Result<(), DbError> {
+    if store.name_scope() == NameScope::PerTemplate {
+        return Err(DbError::InvalidData(
+            "Names are unique per template; plans match containers by name alone".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `desired` changes anything `plan` manages on `stored`
+fn differs(stored: &Container, desired: &Container) -> bool {
+    stored.template != desired.template
+        || stored.labels != desired.labels
+        || stored.description != desired.description
+        || !stored.config.diff(&desired.config).is_empty()
+}
@@ -0,0 +1,287 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use tracing::info;
+
+use crate::error::DbError;
+
+/// Connection pool settings for the container database
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    pub busy_timeout: Duration,
+    /// Upper bound on any single store operation
+    pub query_timeout: Duration,
+    pub wal: bool,
+    /// Open connections with `PRAGMA query_only`, so every write is refused
+    /// by SQLite itself. Migrations cannot run through such a pool.
+    pub read_only: bool,
+    /// Enforce `REFERENCES` constraints. SQLite ignores them unless
+    /// `PRAGMA foreign_keys` is set on every connection.
+    pub foreign_keys: bool,
+    /// Create the database file, and its directory, when it does not exist.
+    /// Off by default so a mistyped path fails instead of opening an empty
+    /// database; installers and tests turn it on.
+    pub create_if_missing: bool,
+    /// Prepared statements kept per connection, keyed by SQL text. Each costs
+    /// some memory in SQLite; in exchange repeated queries skip parsing and
+    /// planning. 0 disables the cache and prepares every query afresh.
+    pub statement_cache_capacity: usize,
+    /// Ping each connection before lending it out, so one left broken by a
+    /// restart is replaced instead of failing the caller's first query. Costs
+    /// a round trip per acquire. Off by default: a local SQLite file has no
+    /// server to lose, so the check buys little.
+    pub test_before_acquire: bool,
+    /// Close connections idle for this long, down to `min_connections`.
+    /// `None` keeps them open.
+    pub idle_timeout: Option<Duration>,
+    /// Replace connections after this long. `None` keeps them for good,
+    /// which an in-memory database needs: its data goes with its connection.
+    pub max_lifetime: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:///tmp/arm-hypervisor/containers.db".to_string(),
+            max_connections: 5,
+            min_connections: 1,
+            acquire_timeout: Duration::from_secs(30),
+            busy_timeout: Duration::from_millis(5000),
+            query_timeout: Duration::from_secs(30),
+            wal: true,
+            read_only: false,
+            foreign_keys: true,
+            create_if_missing: false,
+            statement_cache_capacity: 100,
+            test_before_acquire: false,
+            idle_timeout: Some(Duration::from_secs(10 * 60)),
+            max_lifetime: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+/// Where the database is deployed, for `PoolConfig::for_environment`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Test,
+    Production,
+}
+
+impl PoolConfig {
+    /// Settings suited to `environment`
+    ///
+    /// Development keeps the `/tmp` database of `Default` but creates it on
+    /// first use. Test uses a private in-memory database on a single
+    /// connection, which must stay open for the data to survive, so it is
+    /// never retired for idleness or age. Production
+    /// reads the persistent database under `/var/lib`, which the installer
+    /// creates, with WAL and a larger pool.
+    pub fn for_environment(environment: Environment) -> Self {
+        match environment {
+            Environment::Development => Self {
+                create_if_missing: true,
+                ..Self::default()
+            },
+            Environment::Test => Self {
+                url: "sqlite::memory:".to_string(),
+                max_connections: 1,
+                min_connections: 1,
+                query_timeout: Duration::from_secs(5),
+                wal: false,
+                create_if_missing: true,
+                idle_timeout: None,
+                max_lifetime: None,
+                ..Self::default()
+            },
+            Environment::Production => Self {
+                url: "sqlite:///var/lib/arm-hypervisor/containers.db".to_string(),
+                max_connections: 16,
+                min_connections: 2,
+                wal: true,
+                create_if_missing: false,
+                ..Self::default()
+            },
+        }
+    }
+
+    /// Build a config from defaults overridden by `DATABASE_*` environment variables
+    pub fn from_env() -> Self {
+        Self::default().with_env_overrides()
+    }
+
+    /// Load the `[database]` section of a TOML file
+    ///
+    /// Omitted keys keep their defaults and `DATABASE_*` environment variables
+    /// override whatever the file sets.
+    pub fn from_toml_path(path: &Path) -> Result<Self, DbError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| DbError::InvalidData(format!("Cannot read {}: {}", path.display(), e)))?;
+        let file: ConfigFile = toml::from_str(&content)
+            .map_err(|e| DbError::InvalidData(format!("Invalid {}: {}", path.display(), e)))?;
+
+        let mut config = Self::default();
+        if let Some(section) = file.database {
+            section.apply(&mut config);
+        }
+
+        Ok(config.with_env_overrides())
+    }
+
+    fn with_env_overrides(mut self) -> Self {
+        if let Ok(url) = std::env::var("DATABASE_URL") {
+            self.url = url;
+        }
+        if let Ok(max) = std::env::var("DATABASE_MAX_CONNECTIONS") {
+            if let Ok(max) = max.parse() {
+                self.max_connections = max;
+            }
+        }
+        if let Ok(min) = std::env::var("DATABASE_MIN_CONNECTIONS") {
+            if let Ok(min) = min.parse() {
+                self.min_connections = min;
+            }
+        }
+        if let Ok(ms) = std::env::var("DATABASE_BUSY_TIMEOUT_MS") {
+            if let Ok(ms) = ms.parse() {
+                self.busy_timeout = Duration::from_millis(ms);
+            }
+        }
+        if let Ok(ms) = std::env::var("DATABASE_QUERY_TIMEOUT_MS") {
+            if let Ok(ms) = ms.parse() {
+                self.query_timeout = Duration::from_millis(ms);
+            }
+        }
+
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    database: Option<DatabaseSection>,
+}
+
+/// On-disk shape of `[database]`; every key is optional
+#[derive(Debug, Deserialize)]
+struct DatabaseSection {
+    url: Option<String>,
+    max_connections: Option<u32>,
+    min_connections: Option<u32>,
+    acquire_timeout_secs: Option<u64>,
+    busy_timeout_ms: Option<u64>,
+    query_timeout_ms: Option<u64>,
+    wal: Option<bool>,
+    read_only: Option<bool>,
+    foreign_keys: Option<bool>,
+    create_if_missing: Option<bool>,
+    statement_cache_capacity: Option<usize>,
+    test_before_acquire: Option<bool>,
+}
+
+impl DatabaseSection {
+    fn apply(self, config: &mut PoolConfig) {
+        if let Some(url) = self.url {
+            config.url = url;
+        }
+        if let Some(max) = self.max_connections {
+            config.max_connections = max;
+        }
+        if let Some(min) = self.min_connections {
+            config.min_connections = min;
+        }
+        if let Some(secs) = self.acquire_timeout_secs {
+            config.acquire_timeout = Duration::from_secs(secs);
+        }
+        if let Some(ms) = self.busy_timeout_ms {
+            config.busy_timeout = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.query_timeout_ms {
+            config.query_timeout = Duration::from_millis(ms);
+        }
+        if let Some(wal) = self.wal {
+            config.wal = wal;
+        }
+        if let Some(read_only) = self.read_only {
+            config.read_only = read_only;
+        }
+        if let Some(foreign_keys) = self.foreign_keys {
+            config.foreign_keys = foreign_keys;
+        }
+        if let Some(create_if_missing) = self.create_if_missing {
+            config.create_if_missing = create_if_missing;
+        }
+        if let Some(capacity) = self.statement_cache_capacity {
+            config.statement_cache_capacity = capacity;
+        }
+        if let Some(test_before_acquire) = self.test_before_acquire {
+            config.test_before_acquire = test_before_acquire;
+        }
+    }
+}
+
+/// Open a SQLite connection pool
+///
+/// A missing database file is an error unless `create_if_missing` is set.
+pub async fn create_pool(config: &PoolConfig) -> Result<SqlitePool, DbError> {
+    let options = connect_options(config)?;
+
+    // Check for, or make room for, on-disk databases
+    let filename = options.clone().get_filename();
+    if filename != std::path::Path::new(":memory:") {
+        if !config.create_if_missing {
+            if !filename.exists() {
+                return Err(DbError::InvalidData(format!(
+                    "Database {} does not exist; set create_if_missing to create it",
+                    filename.display()
+                )));
+            }
+        } else if let Some(parent) = filename.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent).map_err(|e| {
+                    DbError::InvalidData(format!("Cannot create {}: {}", parent.display(), e))
+                })?;
+            }
+        }
+    }
+
+    info!(url = %config.url, "Opening database");
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .test_before_acquire(config.test_before_acquire)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+        .connect_with(options)
+        .await?;
+
+    Ok(pool)
+}
+
+/// Per-connection settings derived from `config`
+pub(crate) fn connect_options(config: &PoolConfig) -> Result<SqliteConnectOptions, DbError> {
+    let mut options = SqliteConnectOptions::from_str(&config.url)?
+        .create_if_missing(config.create_if_missing)
+        .busy_timeout(config.busy_timeout)
+        .statement_cache_capacity(config.statement_cache_capacity)
+        // Applied as a pragma as each connection opens; stated explicitly
+        // so turning it off is a visible choice
+        .foreign_keys(config.foreign_keys);
+
+    if config.wal {
+        options = options.journal_mode(SqliteJournalMode::Wal);
+    }
+    if config.read_only {
+        options = options.pragma("query_only", "ON");
+    }
+    Ok(options)
+}
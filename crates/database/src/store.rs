@@ -0,0 +1,2685 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::{Stream, StreamExt};
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqliteRow};
+use sqlx::Row;
+use tokio::sync::broadcast;
+use tracing::{info, warn, Instrument};
+use uuid::Uuid;
+
+use crate::error::DbError;
+use crate::events::{record_event, StatusUpdate, EVENT_DRAIN};
+use crate::flight::SingleFlight;
+use crate::pool::PoolConfig;
+use models::{
+    check_node_container_count, config_fingerprint, merge_environment,
+    validate_container_config_with, validate_create_with, validate_description,
+    validate_disk_for_template, validate_ip_address, validate_label, validate_template,
+    ConfigPatch, Container, ContainerConfig, ContainerName, ContainerStatus,
+    CreateContainerRequest, MemoryDiskPolicy, Template, ValidationError, Validator,
+};
+
+/// Default nesting limit for stored config JSON; real configs are a few levels deep
+pub const MAX_CONFIG_DEPTH: usize = 32;
+
+/// Default number of replaced configs kept per container for `revert_config`
+pub const CONFIG_HISTORY_DEPTH: usize = 10;
+
+/// How many times `transition_status` re-reads after losing a race
+pub const MAX_TRANSITION_ATTEMPTS: u32 = 5;
+
+/// Default namespace for `get_or_create_deterministic` ids
+pub const DEFAULT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6f1d_6a3c_9b2e_4c8a_a5d0_3e7f_1b92_c441);
+
+/// Status updates buffered per subscriber before it starts lagging
+const STATUS_UPDATE_CAPACITY: usize = 256;
+
+/// Column list shared by every query that materializes a `Container`
+pub(crate) const CONTAINER_COLUMNS: &str =
+    "id, name, status, template, node_id, config, labels, description, created_at, updated_at";
+
+/// A page of results together with the size of the full result set
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// Criteria for `query`; unset fields do not filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerFilter {
+    pub status: Option<ContainerStatus>,
+    pub template: Option<String>,
+    /// A single label `(key, value)` the container must carry
+    pub label: Option<(String, String)>,
+    pub node_id: Option<Uuid>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Default for ContainerFilter {
+    /// Everything, first page of 100
+    fn default() -> Self {
+        Self {
+            status: None,
+            template: None,
+            label: None,
+            node_id: None,
+            limit: 100,
+            offset: 0,
+        }
+    }
+}
+
+/// A stored container that `list_invalid` flags
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InvalidContainer {
+    /// Decodes, but today's validation rules reject it
+    Rejected {
+        container: Box<Container>,
+        errors: Vec<ValidationError>,
+    },
+    /// The row cannot be decoded into a `Container` at all
+    Undecodable {
+        /// Raw `id` column, which may itself be the broken value
+        id: String,
+        name: String,
+        detail: String,
+    },
+}
+
+/// Resources committed by every container that is not stopped, from `fleet_totals`
+///
+/// Unset limits count as zero. Requests fall back to the limit when unset,
+/// so they sum what the scheduler has reserved. Unlimited disks add nothing
+/// to `disk_bytes` and are counted in `unlimited_disks` instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct FleetTotals {
+    pub containers: i64,
+    pub cpus: i64,
+    pub cpu_requests: i64,
+    pub memory_bytes: i64,
+    pub memory_request_bytes: i64,
+    pub disk_bytes: i64,
+    pub unlimited_disks: i64,
+}
+
+/// A row `list_lenient` could not decode
+#[derive(Debug)]
+pub struct RowError {
+    /// Raw `id` column, which may itself be the broken value
+    pub id: String,
+    pub name: String,
+    pub error: DbError,
+}
+
+/// Numeric `ContainerConfig` fields that `list_where_config` can filter on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigField {
+    CpuLimit,
+    MemoryLimit,
+    DiskLimit,
+}
+
+impl ConfigField {
+    fn json_path(self) -> &'static str {
+        match self {
+            ConfigField::CpuLimit => "$.cpu_limit",
+            ConfigField::MemoryLimit => "$.memory_limit",
+            ConfigField::DiskLimit => "$.disk_limit",
+        }
+    }
+}
+
+/// Comparison applied by `list_where_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn sql(self) -> &'static str {
+        match self {
+            CmpOp::Eq => "=",
+            CmpOp::Ne => "!=",
+            CmpOp::Lt => "<",
+            CmpOp::Le => "<=",
+            CmpOp::Gt => ">",
+            CmpOp::Ge => ">=",
+        }
+    }
+}
+
+/// Which containers a name must be unique among
+///
+/// `Global` (the default) lets a name identify a container on its own.
+/// `PerTemplate` allows `web` from `alpine` next to `web` from `ubuntu`, at
+/// the cost of name-only lookups: `get_by_name` is refused and callers use
+/// `get_by_name_and_template` instead. Other name-keyed methods such as
+/// `update_status` and `delete` act on every container with that name, so
+/// deployments choosing `PerTemplate` should avoid reusing names where those
+/// are needed. The database index must match; see
+/// `migrations::apply_name_scope`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameScope {
+    #[default]
+    Global,
+    PerTemplate,
+}
+
+/// What decoding does with a stored status it does not recognize
+///
+/// `Strict` (the default) fails with `DbError::Corrupt` so corruption is
+/// noticed. `Lenient` reads such rows as `Error`, for tools that must load
+/// whatever is there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusDecoding {
+    #[default]
+    Strict,
+    Lenient,
+}
+
+impl StatusDecoding {
+    /// Parse a stored status under this policy
+    pub fn decode(self, stored: &str) -> Result<ContainerStatus, DbError> {
+        match (stored.parse(), self) {
+            (Ok(status), _) => Ok(status),
+            (Err(_), StatusDecoding::Lenient) => Ok(ContainerStatus::Error),
+            (Err(_), StatusDecoding::Strict) => Err(DbError::Corrupt(format!(
+                "Unknown container status: {}",
+                stored
+            ))),
+        }
+    }
+}
+
+/// Decides whether a high-frequency operation, named by method, logs at
+/// `info!`; rejected ones log at `debug!`
+pub type LogSampler = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// `info!` when the store's sampler accepts `$operation`, `debug!` otherwise
+macro_rules! sampled {
+    ($store:expr, $operation:expr, $($arg:tt)+) => {
+        if $store.log_sampled($operation) {
+            tracing::info!($($arg)+)
+        } else {
+            tracing::debug!($($arg)+)
+        }
+    };
+}
+
+/// Persistent container metadata backed by SQLite
+#[derive(Clone)]
+pub struct ContainerStore {
+    pool: SqlitePool,
+    query_timeout: Duration,
+    read_only: bool,
+    max_config_depth: usize,
+    name_scope: NameScope,
+    request_id: Option<Uuid>,
+    config_history_depth: usize,
+    id_namespace: Uuid,
+    status_decoding: StatusDecoding,
+    memory_disk_policy: MemoryDiskPolicy,
+    max_containers_per_node: Option<i64>,
+    log_sampler: Option<LogSampler>,
+    single_flight: Option<Arc<SingleFlight<Option<Container>>>>,
+    /// Shared by every clone, so any handle can watch another's writes
+    status_updates: broadcast::Sender<StatusUpdate>,
+    /// Queries run by `try_get_by_name`, for single-flight tests
+    #[cfg(test)]
+    pub(crate) name_lookups: Arc<std::sync::atomic::AtomicUsize>,
+}
+
+impl ContainerStore {
+    /// A store on `pool` with default settings; prefer `from_config` when the
+    /// pool was built from a `PoolConfig`
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            query_timeout: PoolConfig::default().query_timeout,
+            read_only: false,
+            max_config_depth: MAX_CONFIG_DEPTH,
+            name_scope: NameScope::default(),
+            request_id: None,
+            config_history_depth: CONFIG_HISTORY_DEPTH,
+            id_namespace: DEFAULT_ID_NAMESPACE,
+            status_decoding: StatusDecoding::default(),
+            memory_disk_policy: MemoryDiskPolicy::default(),
+            max_containers_per_node: None,
+            log_sampler: None,
+            single_flight: None,
+            status_updates: broadcast::channel(STATUS_UPDATE_CAPACITY).0,
+            #[cfg(test)]
+            name_lookups: Default::default(),
+        }
+    }
+
+    /// A store on `pool` that takes its query timeout from `config`, the
+    /// settings the pool was created with
+    pub fn from_config(pool: SqlitePool, config: &PoolConfig) -> Self {
+        Self::new(pool).with_query_timeout(config.query_timeout)
+    }
+
+    /// Namespace that `get_or_create_deterministic` derives ids from
+    pub fn with_id_namespace(mut self, namespace: Uuid) -> Self {
+        self.id_namespace = namespace;
+        self
+    }
+
+    /// The id `get_or_create_deterministic` gives a container, a UUIDv5
+    ///
+    /// Derived from the name, or from `template/name` when names are only
+    /// unique per template.
+    pub fn deterministic_id(&self, name: impl AsRef<str>, template: &str) -> Uuid {
+        let name = name.as_ref();
+        match self.name_scope {
+            NameScope::Global => Uuid::new_v5(&self.id_namespace, name.as_bytes()),
+            NameScope::PerTemplate => Uuid::new_v5(
+                &self.id_namespace,
+                format!("{}/{}", template, name).as_bytes(),
+            ),
+        }
+    }
+
+    /// Override how many replaced configs are kept per container
+    pub fn with_config_history_depth(mut self, depth: usize) -> Self {
+        self.config_history_depth = depth;
+        self
+    }
+
+    pub fn config_history_depth(&self) -> usize {
+        self.config_history_depth
+    }
+
+    /// A handle that tags every log line it emits with `request_id`
+    pub fn with_request_id(&self, request_id: Uuid) -> Self {
+        Self {
+            request_id: Some(request_id),
+            ..self.clone()
+        }
+    }
+
+    /// Set how container names are scoped; the schema must agree
+    pub fn with_name_scope(mut self, name_scope: NameScope) -> Self {
+        self.name_scope = name_scope;
+        self
+    }
+
+    pub fn name_scope(&self) -> NameScope {
+        self.name_scope
+    }
+
+    /// Override the nesting limit applied when decoding stored configs
+    pub fn with_max_config_depth(mut self, max_config_depth: usize) -> Self {
+        self.max_config_depth = max_config_depth;
+        self
+    }
+
+    /// Cap how many containers `assign_node` and `claim_next_unassigned` may
+    /// place on one node; unlimited by default
+    pub fn with_max_containers_per_node(mut self, max: i64) -> Self {
+        self.max_containers_per_node = Some(max);
+        self
+    }
+
+    /// Route `update_status` and `transition_status` logs through `sampler`
+    ///
+    /// Status heartbeats from node agents dominate the log at scale; a
+    /// sampler can demote all or most of them to `debug!`. Rare operations
+    /// such as create and delete always log at `info!`.
+    pub fn with_log_sampler(
+        mut self,
+        sampler: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.log_sampler = Some(Arc::new(sampler));
+        self
+    }
+
+    /// Receive every status change this store, or any clone of it, commits
+    ///
+    /// Writes made through other pools or processes are not seen.
+    pub fn subscribe_status(&self) -> broadcast::Receiver<StatusUpdate> {
+        self.status_updates.subscribe()
+    }
+
+    /// Publish a committed status change; dropped when nobody is listening
+    pub(crate) fn notify_status(&self, name: &str, status: ContainerStatus) {
+        let _ = self.status_updates.send(StatusUpdate {
+            name: name.to_string(),
+            status,
+        });
+    }
+
+    pub(crate) fn log_sampled(&self, operation: &str) -> bool {
+        self.log_sampler
+            .as_ref()
+            .is_none_or(|sampler| sampler(operation))
+    }
+
+    /// Coalesce concurrent `get_by_name` calls for the same name into one
+    /// query, handing every caller a copy of the result
+    ///
+    /// Off by default. Handles cloned from this one share the in-flight
+    /// lookups, so settings that change decoding should be applied first.
+    pub fn with_single_flight(mut self, enabled: bool) -> Self {
+        self.single_flight = enabled.then(|| Arc::new(SingleFlight::new()));
+        self
+    }
+
+    /// Set how unrecognized stored statuses are decoded
+    pub fn with_status_decoding(mut self, status_decoding: StatusDecoding) -> Self {
+        self.status_decoding = status_decoding;
+        self
+    }
+
+    /// Set whether writes reject a disk limit below the memory limit or
+    /// accept it; rejecting is the default
+    pub fn with_memory_disk_policy(mut self, memory_disk_policy: MemoryDiskPolicy) -> Self {
+        self.memory_disk_policy = memory_disk_policy;
+        self
+    }
+
+    pub fn memory_disk_policy(&self) -> MemoryDiskPolicy {
+        self.memory_disk_policy
+    }
+
+    /// A handle on the same pool whose mutating methods fail with
+    /// `DbError::Internal`, for reports and dashboards
+    pub fn read_only_view(&self) -> Self {
+        Self {
+            read_only: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Override the per-operation timeout, normally `PoolConfig::query_timeout`
+    pub fn with_query_timeout(mut self, query_timeout: Duration) -> Self {
+        self.query_timeout = query_timeout;
+        self
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub(crate) fn to_container(&self, row: &SqliteRow) -> Result<Container, DbError> {
+        row_to_container_with(row, self.max_config_depth, self.status_decoding)
+    }
+
+    /// Parse a stored status under the store's `StatusDecoding`
+    pub(crate) fn decode_status(&self, stored: &str) -> Result<ContainerStatus, DbError> {
+        self.status_decoding.decode(stored)
+    }
+
+    /// Refuse CPU or memory limit changes while a container is active
+    ///
+    /// A frozen container's memory is suspended and a running one's limits are
+    /// in force, so both must be stopped first. Other fields may change freely.
+    pub(crate) fn check_limits_unchanged(
+        &self,
+        name: &str,
+        status: &str,
+        current: &ContainerConfig,
+        next: &ContainerConfig,
+    ) -> Result<(), DbError> {
+        let status = self.decode_status(status)?;
+        let limits_changed =
+            current.cpu_limit != next.cpu_limit || current.memory_limit != next.memory_limit;
+        if limits_changed && status.is_active() {
+            return Err(DbError::ContainerBusy {
+                name: name.to_string(),
+                status,
+            });
+        }
+        Ok(())
+    }
+
+    /// The pool to use for writes, or an error on a read-only view
+    pub(crate) fn writer(&self) -> Result<&SqlitePool, DbError> {
+        if self.read_only {
+            return Err(DbError::Internal("Store is read-only".to_string()));
+        }
+        Ok(&self.pool)
+    }
+
+    /// Run a store operation, failing with `DbError::Timeout` if it overruns
+    pub(crate) async fn timed<T>(
+        &self,
+        operation: impl Future<Output = Result<T, DbError>>,
+    ) -> Result<T, DbError> {
+        let span = tracing::info_span!("store", request_id = tracing::field::Empty);
+        if let Some(request_id) = self.request_id {
+            span.record("request_id", tracing::field::display(request_id));
+        }
+
+        tokio::time::timeout(self.query_timeout, operation)
+            .instrument(span)
+            .await
+            .map_err(|_| DbError::Timeout(self.query_timeout))?
+    }
+
+    /// `validate_create` under the store's memory/disk policy, plus the
+    /// registered template's disk minimum, if any
+    async fn validate_new(
+        &self,
+        name: &str,
+        template: &str,
+        config: &ContainerConfig,
+    ) -> Result<Validator, DbError> {
+        let mut validator = Validator::new();
+        validator.check_all(validate_create_with(
+            name,
+            template,
+            config,
+            self.memory_disk_policy,
+        ));
+
+        let min_disk: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT min_disk_bytes FROM templates WHERE name = ?1")
+                .bind(template)
+                .fetch_optional(&self.pool)
+                .await?;
+        if let Some(Some(min_disk)) = min_disk {
+            validator.check(validate_disk_for_template(
+                config.disk_limit,
+                min_disk as u64,
+            ));
+        }
+
+        Ok(validator)
+    }
+
+    /// `validate_new` plus the labels and description a whole container carries
+    pub(crate) async fn validate_container(
+        &self,
+        container: &Container,
+    ) -> Result<Validator, DbError> {
+        let mut validator = self
+            .validate_new(&container.name, &container.template, &container.config)
+            .await?;
+        for (key, value) in &container.labels {
+            validator.check(validate_label(key, value));
+        }
+        if let Some(description) = &container.description {
+            validator.check(validate_description(description));
+        }
+        Ok(validator)
+    }
+
+    /// Stored containers that today's validation rules would reject, with
+    /// their errors, oldest first
+    ///
+    /// Rows written before a rule was tightened stay readable; this finds
+    /// them for cleanup. The same checks as `create` run, apart from name
+    /// uniqueness. Rows that fail to decode are reported as `Undecodable`
+    /// entries rather than failing the whole scan.
+    pub async fn list_invalid(&self) -> Result<Vec<InvalidContainer>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            let mut failing = Vec::new();
+            for row in &rows {
+                let container = match self.to_container(row) {
+                    Ok(container) => container,
+                    Err(e) => {
+                        failing.push(InvalidContainer::Undecodable {
+                            id: row.try_get("id")?,
+                            name: row.try_get("name")?,
+                            detail: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                let result = self
+                    .validate_new(&container.name, &container.template, &container.config)
+                    .await?
+                    .finish();
+                if let Err(errors) = result {
+                    failing.push(InvalidContainer::Rejected {
+                        container: Box::new(container),
+                        errors,
+                    });
+                }
+            }
+            Ok(failing)
+        })
+        .await
+    }
+
+    /// Dry-run `create`: report every problem with the proposed container
+    /// without writing anything
+    ///
+    /// A name that is already taken comes back as a `NAME_TAKEN` validation
+    /// error next to any config errors. The outer `DbError` only reports a
+    /// failure to run the checks themselves.
+    pub async fn validate_create(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+        config: &ContainerConfig,
+    ) -> Result<Result<(), Vec<ValidationError>>, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut validator = self.validate_new(name, template, config).await?;
+
+            let taken: i64 = match self.name_scope {
+                NameScope::Global => {
+                    sqlx::query_scalar("SELECT COUNT(*) FROM containers WHERE name = ?1")
+                        .bind(name)
+                        .fetch_one(&self.pool)
+                        .await?
+                }
+                NameScope::PerTemplate => {
+                    sqlx::query_scalar(
+                        "SELECT COUNT(*) FROM containers WHERE name = ?1 AND template = ?2",
+                    )
+                    .bind(name)
+                    .bind(template)
+                    .fetch_one(&self.pool)
+                    .await?
+                }
+            };
+            if taken > 0 {
+                validator.check(Err(ValidationError::new(
+                    "name",
+                    "NAME_TAKEN",
+                    format!("Container name {} is already taken", name),
+                )));
+            }
+
+            Ok(validator.finish())
+        })
+        .await
+    }
+
+    /// `create` with a template already known to be well-formed
+    pub async fn create_with_template(
+        &self,
+        name: impl AsRef<str>,
+        template: &Template,
+        config: &ContainerConfig,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.create(name, template.as_str(), config).await
+    }
+
+    /// Insert a new container in the `Stopped` state
+    pub async fn create(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+        config: &ContainerConfig,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            self.validate_new(name, template, config)
+                .await?
+                .finish()
+                .map_err(invalid)?;
+
+            let container = new_container(name, template, config.clone());
+            self.insert(&container).await?;
+            Ok(container)
+        })
+        .await
+    }
+
+    /// Create a container from an API request, including its description
+    pub async fn create_from_request(
+        &self,
+        request: &CreateContainerRequest,
+    ) -> Result<Container, DbError> {
+        self.timed(async {
+            let mut validator = self
+                .validate_new(&request.name, request.template.as_str(), &request.config)
+                .await?;
+            if let Some(description) = &request.description {
+                validator.check(validate_description(description));
+            }
+            validator.finish().map_err(invalid)?;
+
+            let mut container = new_container(
+                &request.name,
+                request.template.as_str(),
+                request.config.clone(),
+            );
+            container.description = request.description.clone();
+            self.insert(&container).await?;
+            Ok(container)
+        })
+        .await
+    }
+
+    /// Insert or overwrite a fully-formed container, keyed by its id
+    ///
+    /// Used by import, clone and replication paths that already carry an id
+    /// and timestamps; every column is written verbatim.
+    pub async fn upsert(&self, container: &Container) -> Result<(), DbError> {
+        self.timed(async {
+            self.validate_container(container)
+                .await?
+                .finish()
+                .map_err(invalid)?;
+
+            sqlx::query(
+                "INSERT INTO containers (id, name, status, template, node_id, config, labels, description, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                 ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    status = excluded.status,
+                    template = excluded.template,
+                    node_id = excluded.node_id,
+                    config = excluded.config,
+                    labels = excluded.labels,
+                    description = excluded.description,
+                    created_at = excluded.created_at,
+                    updated_at = excluded.updated_at",
+            )
+            .bind(container.id.to_string())
+            .bind(container.name.as_str())
+            .bind(container.status.as_str())
+            .bind(&container.template)
+            .bind(container.node_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&container.config.normalized())?)
+            .bind(serde_json::to_string(&container.labels)?)
+            .bind(&container.description)
+            .bind(format_timestamp(&container.created_at))
+            .bind(format_timestamp(&container.updated_at))
+            .execute(self.writer()?)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                    DbError::ContainerAlreadyExists(container.name.to_string())
+                }
+                e => e.into(),
+            })?;
+
+            info!(container.name = %container.name, "Upserted container");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Write a fully-formed container row
+    async fn insert(&self, container: &Container) -> Result<(), DbError> {
+        let mut conn = self.writer()?.acquire().await?;
+        insert_container(&mut conn, container).await?;
+
+        info!(container.name = %container.name, "Created container");
+        Ok(())
+    }
+
+    /// Return the existing container with this name, or create it
+    ///
+    /// The insert uses `ON CONFLICT DO NOTHING RETURNING`, so concurrent
+    /// callers racing on the same name all observe the single row that won.
+    pub async fn get_or_create(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+        config: ContainerConfig,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.get_or_create_placed(Uuid::new_v4(), name, template, config, None)
+            .await
+    }
+
+    /// `get_or_create` with the id from `deterministic_id`
+    ///
+    /// Recreating a deleted container gives it the same id again. Fails with
+    /// `Conflict` if another container already holds that id, e.g. after a
+    /// rename.
+    pub async fn get_or_create_deterministic(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+        config: ContainerConfig,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        let id = self.deterministic_id(name, template);
+        match self
+            .get_or_create_placed(id, name, template, config, None)
+            .await
+        {
+            Err(DbError::ContainerNotFound(_)) => Err(DbError::Conflict(format!(
+                "Id {} for {} belongs to another container",
+                id, name
+            ))),
+            result => result,
+        }
+    }
+
+    /// `get_or_create`, placing a newly created container on `node_id`
+    ///
+    /// An existing container is returned as-is, wherever it is placed.
+    pub async fn get_or_create_on_node(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+        config: ContainerConfig,
+        node_id: Uuid,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.get_or_create_placed(Uuid::new_v4(), name, template, config, Some(node_id))
+            .await
+    }
+
+    async fn get_or_create_placed(
+        &self,
+        id: Uuid,
+        name: &str,
+        template: &str,
+        config: ContainerConfig,
+        node_id: Option<Uuid>,
+    ) -> Result<Container, DbError> {
+        self.timed(async {
+            self.validate_new(name, template, &config)
+                .await?
+                .finish()
+                .map_err(invalid)?;
+
+            let now = format_timestamp(&Utc::now());
+
+            let row = sqlx::query(&format!(
+                "INSERT INTO containers (id, name, status, template, node_id, config, created_at, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+                 ON CONFLICT DO NOTHING
+                 RETURNING {}",
+                CONTAINER_COLUMNS
+            ))
+            .bind(id.to_string())
+            .bind(name)
+            .bind(ContainerStatus::Stopped.as_str())
+            .bind(template)
+            .bind(node_id.map(|id| id.to_string()))
+            .bind(serde_json::to_string(&config.normalized())?)
+            .bind(&now)
+            // fetch_all, not fetch_optional: the INSERT must run to completion
+            // or it stays uncommitted while the connection sits in the pool
+            .fetch_all(self.writer()?)
+            .await?
+            .pop();
+
+            match row {
+                Some(row) => {
+                    info!(container.name = %name, "Created container");
+                    self.to_container(&row)
+                }
+                // Conflict: the row already exists, so this read cannot miss
+                None => match self.name_scope {
+                    NameScope::Global => self.get_by_name(name).await,
+                    NameScope::PerTemplate => self.get_by_name_and_template(name, template).await,
+                },
+            }
+        })
+        .await
+    }
+
+    /// Create a container once per idempotency key
+    ///
+    /// A retry carrying the same key and the same name, template and config
+    /// returns the container created by the first attempt. Reusing the key for
+    /// a different request fails with `IdempotencyConflict`.
+    pub async fn create_idempotent(
+        &self,
+        key: &str,
+        name: impl AsRef<str>,
+        template: &str,
+        config: &ContainerConfig,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            self.validate_new(name, template, config)
+                .await?
+                .finish()
+                .map_err(invalid)?;
+            if key.is_empty() {
+                return Err(DbError::InvalidData(
+                    "Idempotency key cannot be empty".to_string(),
+                ));
+            }
+
+            let now = format_timestamp(&Utc::now());
+            let row = sqlx::query(&format!(
+                "INSERT INTO containers (id, name, status, template, node_id, config, created_at, updated_at, idempotency_key)
+                 VALUES (?1, ?2, ?3, ?4, NULL, ?5, ?6, ?6, ?7)
+                 ON CONFLICT(idempotency_key) DO NOTHING
+                 RETURNING {}",
+                CONTAINER_COLUMNS
+            ))
+            .bind(Uuid::new_v4().to_string())
+            .bind(name)
+            .bind(ContainerStatus::Stopped.as_str())
+            .bind(template)
+            .bind(serde_json::to_string(&config.normalized())?)
+            .bind(&now)
+            .bind(key)
+            .fetch_all(self.writer()?)
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                    DbError::ContainerAlreadyExists(name.to_string())
+                }
+                e => e.into(),
+            })?
+            .pop();
+
+            if let Some(row) = row {
+                info!(container.name = %name, "Created container");
+                return self.to_container(&row);
+            }
+
+            // The key was seen before: replay the original result if the payload matches
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE idempotency_key = ?1",
+                CONTAINER_COLUMNS
+            ))
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| DbError::IdempotencyConflict(key.to_string()))?;
+            let existing = self.to_container(&row)?;
+
+            let same_request = existing.name == name
+                && existing.template == template
+                && serde_json::to_value(&existing.config)?
+                    == serde_json::to_value(config.normalized())?;
+            if !same_request {
+                return Err(DbError::IdempotencyConflict(key.to_string()));
+            }
+
+            Ok(existing)
+        })
+        .await
+    }
+
+    /// Look up a container by name; refused under `NameScope::PerTemplate`
+    ///
+    /// Takes a plain `&str` or a `ContainerName`.
+    pub async fn get_by_name(&self, name: impl AsRef<str>) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.try_get_by_name(name)
+            .await?
+            .ok_or_else(|| DbError::ContainerNotFound(name.to_string()))
+    }
+
+    /// Like `get_by_name`, but a missing container is `Ok(None)`
+    pub async fn try_get_by_name(
+        &self,
+        name: impl AsRef<str>,
+    ) -> Result<Option<Container>, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            if self.name_scope == NameScope::PerTemplate {
+                return Err(DbError::InvalidData(
+                    "Names are unique per template; look up by name and template".to_string(),
+                ));
+            }
+
+            match &self.single_flight {
+                Some(flight) => flight.run(name, || self.fetch_by_name(name)).await,
+                None => self.fetch_by_name(name).await,
+            }
+        })
+        .await
+    }
+
+    async fn fetch_by_name(&self, name: &str) -> Result<Option<Container>, DbError> {
+        #[cfg(test)]
+        self.name_lookups
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+        // Built once; the text is also the statement cache key
+        static SQL: OnceLock<String> = OnceLock::new();
+        let sql = SQL.get_or_init(|| {
+            format!(
+                "SELECT {} FROM containers WHERE name = ?1",
+                CONTAINER_COLUMNS
+            )
+        });
+
+        let row = sqlx::query(sql)
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|row| self.to_container(&row)).transpose()
+    }
+
+    /// Look up a container by name within a template; works under either scope
+    pub async fn get_by_name_and_template(
+        &self,
+        name: impl AsRef<str>,
+        template: &str,
+    ) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE name = ?1 AND template = ?2",
+                CONTAINER_COLUMNS
+            ))
+            .bind(name)
+            .bind(template)
+            .fetch_optional(&self.pool)
+            .await?;
+
+            match row {
+                Some(row) => self.to_container(&row),
+                None => Err(DbError::ContainerNotFound(format!(
+                    "{} ({})",
+                    name, template
+                ))),
+            }
+        })
+        .await
+    }
+
+    pub async fn get_by_id(&self, id: &Uuid) -> Result<Container, DbError> {
+        self.timed(async {
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE id = ?1",
+                CONTAINER_COLUMNS
+            ))
+            .bind(id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+            match row {
+                Some(row) => self.to_container(&row),
+                None => Err(DbError::ContainerNotFound(id.to_string())),
+            }
+        })
+        .await
+    }
+
+    pub async fn exists(&self, name: impl AsRef<str>) -> Result<bool, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM containers WHERE name = ?1")
+                .bind(name)
+                .fetch_one(&self.pool)
+                .await?;
+            Ok(count > 0)
+        })
+        .await
+    }
+
+    /// The subset of `names` that already exist, in one query
+    pub async fn existing_names(&self, names: &[String]) -> Result<HashSet<String>, DbError> {
+        self.timed(async {
+            if names.is_empty() {
+                return Ok(HashSet::new());
+            }
+
+            let sql = format!(
+                "SELECT name FROM containers WHERE name IN ({})",
+                placeholders(names.len())
+            );
+            let mut query = sqlx::query_scalar::<_, String>(&sql);
+            for name in names {
+                query = query.bind(name);
+            }
+
+            Ok(query.fetch_all(&self.pool).await?.into_iter().collect())
+        })
+        .await
+    }
+
+    /// List every container, ordered by creation time
+    pub async fn list(&self) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Like `list`, but a row that fails to decode is reported instead of
+    /// failing the whole listing
+    pub async fn list_lenient(&self) -> Result<(Vec<Container>, Vec<RowError>), DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            let mut containers = Vec::with_capacity(rows.len());
+            let mut errors = Vec::new();
+            for row in &rows {
+                match self.to_container(row) {
+                    Ok(container) => containers.push(container),
+                    Err(error) => errors.push(RowError {
+                        id: row.try_get("id").unwrap_or_default(),
+                        name: row.try_get("name").unwrap_or_default(),
+                        error,
+                    }),
+                }
+            }
+
+            if !errors.is_empty() {
+                warn!(failed = errors.len(), "Skipped undecodable container rows");
+            }
+            Ok((containers, errors))
+        })
+        .await
+    }
+
+    /// One page of containers plus the total row count, read in one transaction
+    pub async fn list_page(&self, limit: i64, offset: i64) -> Result<Page<Container>, DbError> {
+        self.timed(async {
+            if limit <= 0 || offset < 0 {
+                return Err(DbError::InvalidData(format!(
+                    "Invalid page: limit {} offset {}",
+                    limit, offset
+                )));
+            }
+
+            let mut tx = self.pool.begin().await?;
+
+            let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM containers")
+                .fetch_one(&mut *tx)
+                .await?;
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC, id ASC LIMIT ?1 OFFSET ?2",
+                CONTAINER_COLUMNS
+            ))
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&mut *tx)
+            .await?;
+
+            tx.commit().await?;
+
+            Ok(Page {
+                items: rows
+                    .iter()
+                    .map(|row| self.to_container(row))
+                    .collect::<Result<_, _>>()?,
+                total,
+                limit,
+                offset,
+            })
+        })
+        .await
+    }
+
+    /// One page of containers matching every criterion set in `filter`
+    ///
+    /// Only the given criteria become clauses, and every value is bound as a
+    /// parameter. Ordered like `list_page`; `total` counts all matches.
+    pub async fn query(&self, filter: ContainerFilter) -> Result<Page<Container>, DbError> {
+        self.timed(async {
+            if filter.limit <= 0 || filter.offset < 0 {
+                return Err(DbError::InvalidData(format!(
+                    "Invalid page: limit {} offset {}",
+                    filter.limit, filter.offset
+                )));
+            }
+
+            let mut clauses = Vec::new();
+            let mut binds = Vec::new();
+            if let Some(status) = &filter.status {
+                binds.push(status.as_str().to_string());
+                clauses.push(format!("status = ?{}", binds.len()));
+            }
+            if let Some(template) = &filter.template {
+                binds.push(template.clone());
+                clauses.push(format!("template = ?{}", binds.len()));
+            }
+            if let Some((key, value)) = &filter.label {
+                validate_label(key, value).map_err(|e| invalid(vec![e]))?;
+                binds.push(label_path(key));
+                binds.push(value.clone());
+                clauses.push(format!(
+                    "json_extract(labels, ?{}) = ?{}",
+                    binds.len() - 1,
+                    binds.len()
+                ));
+            }
+            if let Some(node_id) = &filter.node_id {
+                binds.push(node_id.to_string());
+                clauses.push(format!("node_id = ?{}", binds.len()));
+            }
+            let where_clause = if clauses.is_empty() {
+                "1 = 1".to_string()
+            } else {
+                clauses.join(" AND ")
+            };
+
+            let mut tx = self.pool.begin().await?;
+
+            let count_sql = format!("SELECT COUNT(*) FROM containers WHERE {}", where_clause);
+            let mut count = sqlx::query_scalar(&count_sql);
+            for bind in &binds {
+                count = count.bind(bind);
+            }
+            let total: i64 = count.fetch_one(&mut *tx).await?;
+
+            let sql = format!(
+                "SELECT {} FROM containers WHERE {} ORDER BY created_at ASC, id ASC LIMIT ?{} OFFSET ?{}",
+                CONTAINER_COLUMNS,
+                where_clause,
+                binds.len() + 1,
+                binds.len() + 2
+            );
+            let mut query = sqlx::query(&sql);
+            for bind in &binds {
+                query = query.bind(bind);
+            }
+            let rows = query
+                .bind(filter.limit)
+                .bind(filter.offset)
+                .fetch_all(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+
+            Ok(Page {
+                items: rows
+                    .iter()
+                    .map(|row| self.to_container(row))
+                    .collect::<Result<_, _>>()?,
+                total,
+                limit: filter.limit,
+                offset: filter.offset,
+            })
+        })
+        .await
+    }
+
+    /// Lazily yield every container, ordered by creation time
+    ///
+    /// Rows are fetched and deserialized one at a time so memory stays bounded
+    /// on large tables. The per-operation query timeout does not apply here.
+    pub fn stream(&self) -> impl Stream<Item = Result<Container, DbError>> + '_ {
+        static SQL: OnceLock<String> = OnceLock::new();
+        let sql = SQL.get_or_init(|| {
+            format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            )
+        });
+
+        sqlx::query(sql)
+            .fetch(&self.pool)
+            .map(|row| self.to_container(&row?))
+    }
+
+    pub async fn list_by_status(&self, status: ContainerStatus) -> Result<Vec<Container>, DbError> {
+        self.list_by_statuses(&[status]).await
+    }
+
+    /// Statuses of the named containers, without decoding their configs
+    ///
+    /// Names that do not exist are absent from the result.
+    pub async fn statuses(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, ContainerStatus>, DbError> {
+        self.timed(async {
+            if names.is_empty() {
+                return Ok(HashMap::new());
+            }
+
+            let sql = format!(
+                "SELECT name, status FROM containers WHERE name IN ({})",
+                placeholders(names.len())
+            );
+            let mut query = sqlx::query_as::<_, (String, String)>(&sql);
+            for name in names {
+                query = query.bind(name);
+            }
+            let rows = query.fetch_all(&self.pool).await?;
+
+            rows.into_iter()
+                .map(|(name, status)| Ok((name, self.decode_status(&status)?)))
+                .collect()
+        })
+        .await
+    }
+
+    /// Containers in any of the given statuses
+    pub async fn list_by_statuses(
+        &self,
+        statuses: &[ContainerStatus],
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            if statuses.is_empty() {
+                return Err(DbError::InvalidData(
+                    "At least one status is required".to_string(),
+                ));
+            }
+
+            let sql = format!(
+                "SELECT {} FROM containers WHERE status IN ({}) ORDER BY created_at ASC",
+                CONTAINER_COLUMNS,
+                placeholders(statuses.len())
+            );
+            let mut query = sqlx::query(&sql);
+            for status in statuses {
+                query = query.bind(status.as_str());
+            }
+            let rows = query.fetch_all(&self.pool).await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    pub async fn list_by_node(&self, node_id: &Uuid) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE node_id = ?1 ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .bind(node_id.to_string())
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Containers not yet placed on a node, oldest first
+    pub async fn list_unassigned(&self) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE node_id IS NULL ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Containers whose config `field` compares to `value` under `op`
+    ///
+    /// Only the fixed SQL for the enum variants is interpolated; `value` is
+    /// bound. Containers with the field unset or not a number, such as an
+    /// `"unlimited"` disk, never match.
+    pub async fn list_where_config(
+        &self,
+        field: ConfigField,
+        op: CmpOp,
+        value: i64,
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let sql = format!(
+                "SELECT {} FROM containers
+                 WHERE json_type(config, '{path}') = 'integer' AND json_extract(config, '{path}') {} ?1
+                 ORDER BY created_at ASC",
+                CONTAINER_COLUMNS,
+                op.sql(),
+                path = field.json_path()
+            );
+            let rows = sqlx::query(&sql).bind(value).fetch_all(&self.pool).await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Number of containers carrying every label in `selector`
+    ///
+    /// An empty selector counts all containers.
+    pub async fn count_by_selector(&self, selector: &[(String, String)]) -> Result<i64, DbError> {
+        self.timed(async {
+            let sql = format!(
+                "SELECT COUNT(*) FROM containers WHERE {}",
+                selector_clause(selector)?
+            );
+            let mut query = sqlx::query_scalar(&sql);
+            for (key, value) in selector {
+                query = query.bind(label_path(key)).bind(value);
+            }
+
+            Ok(query.fetch_one(&self.pool).await?)
+        })
+        .await
+    }
+
+    /// Number of containers assigned to `node_id`
+    pub async fn count_by_node(&self, node_id: &Uuid) -> Result<i64, DbError> {
+        self.timed(async {
+            Ok(
+                sqlx::query_scalar("SELECT COUNT(*) FROM containers WHERE node_id = ?1")
+                    .bind(node_id.to_string())
+                    .fetch_one(&self.pool)
+                    .await?,
+            )
+        })
+        .await
+    }
+
+    /// Containers carrying every label in `selector`, oldest first
+    ///
+    /// An empty selector lists all containers.
+    pub async fn list_by_selector(
+        &self,
+        selector: &[(String, String)],
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let sql = format!(
+                "SELECT {} FROM containers WHERE {} ORDER BY created_at ASC",
+                CONTAINER_COLUMNS,
+                selector_clause(selector)?
+            );
+            let mut query = sqlx::query(&sql);
+            for (key, value) in selector {
+                query = query.bind(label_path(key)).bind(value);
+            }
+            let rows = query.fetch_all(&self.pool).await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// The container with an interface using `ip`, the oldest if several do
+    ///
+    /// Matches stored addresses with or without a prefix length, so
+    /// `10.0.0.5` finds an interface configured as `10.0.0.5/24`.
+    pub async fn find_by_ip(&self, ip: &str) -> Result<Option<Container>, DbError> {
+        self.timed(async {
+            let ip = validate_ip_address(ip).map_err(|e| invalid(vec![e]))?;
+            let path = if ip.is_ipv4() { "$.ipv4" } else { "$.ipv6" };
+
+            let row = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE EXISTS (
+                     SELECT 1 FROM json_each(containers.config, '$.network_interfaces') AS iface
+                     WHERE json_extract(iface.value, ?1) = ?2
+                        OR json_extract(iface.value, ?1) LIKE ?2 || '/%'
+                 )
+                 ORDER BY created_at ASC LIMIT 1",
+                CONTAINER_COLUMNS
+            ))
+            .bind(path)
+            .bind(ip.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+
+            row.map(|row| self.to_container(&row)).transpose()
+        })
+        .await
+    }
+
+    /// Templates used by at least one container, sorted
+    pub async fn distinct_templates(&self) -> Result<Vec<String>, DbError> {
+        self.timed(async {
+            Ok(
+                sqlx::query_scalar("SELECT DISTINCT template FROM containers ORDER BY template")
+                    .fetch_all(&self.pool)
+                    .await?,
+            )
+        })
+        .await
+    }
+
+    /// Point every container using template `old` at `new`, returning how
+    /// many moved
+    ///
+    /// Runs in one transaction. The template registry is not touched. Under
+    /// `NameScope::PerTemplate` a name already used with `new` fails the
+    /// whole rename with `ContainerAlreadyExists`.
+    pub async fn rename_template(&self, old: &str, new: &str) -> Result<u64, DbError> {
+        self.timed(async {
+            validate_template(new).map_err(|e| invalid(vec![e]))?;
+
+            let mut tx = self.writer()?.begin().await?;
+            let rows: Vec<(String, String)> =
+                sqlx::query_as("SELECT id, name FROM containers WHERE template = ?1")
+                    .bind(old)
+                    .fetch_all(&mut *tx)
+                    .await?;
+
+            for (id, name) in &rows {
+                let now = next_updated_at(&mut *tx, id).await?;
+                sqlx::query("UPDATE containers SET template = ?1, updated_at = ?2 WHERE id = ?3")
+                    .bind(new)
+                    .bind(&now)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| match e {
+                        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                            DbError::ContainerAlreadyExists(name.clone())
+                        }
+                        e => e.into(),
+                    })?;
+            }
+            tx.commit().await?;
+
+            info!(%old, %new, count = rows.len(), "Renamed template");
+            Ok(rows.len() as u64)
+        })
+        .await
+    }
+
+    pub async fn list_by_template(&self, template: &str) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            validate_template(template).map_err(|e| invalid(vec![e]))?;
+
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE template = ?1 ORDER BY created_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .bind(template)
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Groups of two or more containers with the same `config_fingerprint`
+    ///
+    /// Names, ids, templates and timestamps are ignored. Groups and their
+    /// members are ordered by creation time.
+    pub async fn find_duplicates(&self) -> Result<Vec<Vec<Container>>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers ORDER BY created_at ASC, id ASC",
+                CONTAINER_COLUMNS
+            ))
+            .fetch_all(&self.pool)
+            .await?;
+
+            let mut groups: HashMap<String, Vec<Container>> = HashMap::new();
+            for row in &rows {
+                let container = self.to_container(row)?;
+                groups
+                    .entry(config_fingerprint(&container.config))
+                    .or_default()
+                    .push(container);
+            }
+
+            let mut duplicates: Vec<Vec<Container>> = groups
+                .into_values()
+                .filter(|group| group.len() > 1)
+                .collect();
+            duplicates.sort_by(|a, b| (a[0].created_at, a[0].id).cmp(&(b[0].created_at, b[0].id)));
+            Ok(duplicates)
+        })
+        .await
+    }
+
+    /// Number of containers in each status
+    ///
+    /// Statuses with no containers are omitted. An unrecognized stored status
+    /// is counted as `Error` under lenient decoding and fails under strict.
+    pub async fn counts_by_status(&self) -> Result<HashMap<ContainerStatus, i64>, DbError> {
+        self.timed(async {
+            let rows: Vec<(String, i64)> =
+                sqlx::query_as("SELECT status, COUNT(*) FROM containers GROUP BY status")
+                    .fetch_all(&self.pool)
+                    .await?;
+
+            let mut counts = HashMap::new();
+            for (status, count) in rows {
+                let status = self.decode_status(&status)?;
+                *counts.entry(status).or_insert(0) += count;
+            }
+            Ok(counts)
+        })
+        .await
+    }
+
+    /// Cluster-wide resource totals over all containers that are not stopped
+    pub async fn fleet_totals(&self) -> Result<FleetTotals, DbError> {
+        self.timed(async {
+            // Requests fall back to their limit, as in `effective_cpu_request`
+            let cpu = config_integer("$.cpu_limit");
+            let cpu_request = config_integer("$.cpu_request");
+            let memory = config_integer("$.memory_limit");
+            let memory_request = config_integer("$.memory_request");
+            let disk = config_integer("$.disk_limit");
+            let row = sqlx::query(&format!(
+                "SELECT COUNT(*) AS containers,
+                        COALESCE(SUM({cpu}), 0) AS cpus,
+                        COALESCE(SUM(COALESCE({cpu_request}, {cpu})), 0) AS cpu_requests,
+                        COALESCE(SUM({memory}), 0) AS memory_bytes,
+                        COALESCE(SUM(COALESCE({memory_request}, {memory})), 0) AS memory_request_bytes,
+                        COALESCE(SUM({disk}), 0) AS disk_bytes,
+                        COALESCE(SUM(json_extract(config, '$.disk_limit') = 'unlimited'), 0) AS unlimited_disks
+                 FROM containers WHERE status != ?1"
+            ))
+            .bind(ContainerStatus::Stopped.as_str())
+            .fetch_one(&self.pool)
+            .await?;
+
+            Ok(FleetTotals {
+                containers: row.try_get("containers")?,
+                cpus: row.try_get("cpus")?,
+                cpu_requests: row.try_get("cpu_requests")?,
+                memory_bytes: row.try_get("memory_bytes")?,
+                memory_request_bytes: row.try_get("memory_request_bytes")?,
+                disk_bytes: row.try_get("disk_bytes")?,
+                unlimited_disks: row.try_get("unlimited_disks")?,
+            })
+        })
+        .await
+    }
+
+    pub async fn update_status(
+        &self,
+        name: impl AsRef<str>,
+        status: ContainerStatus,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result =
+                sqlx::query("UPDATE containers SET status = ?1, updated_at = ?2 WHERE id = ?3")
+                    .bind(status.as_str())
+                    .bind(&now)
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            sampled!(self, "update_status", container.name = %name, %status, "Updated container status");
+            self.notify_status(name, status);
+            Ok(())
+        })
+        .await
+    }
+
+    /// Move a container to `to` if its lifecycle allows it, returning the old status
+    ///
+    /// Unlike `update_status`, the current status is checked first. The write
+    /// only lands if the status is still the one that was read; when another
+    /// writer got there first, the status is re-read and the transition
+    /// re-evaluated, with backoff, up to `MAX_TRANSITION_ATTEMPTS` times
+    /// before failing with `DbError::Conflict`.
+    pub async fn transition_status(
+        &self,
+        name: impl AsRef<str>,
+        to: ContainerStatus,
+    ) -> Result<ContainerStatus, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let pool = self.writer()?;
+
+            for attempt in 0..MAX_TRANSITION_ATTEMPTS {
+                if attempt > 0 {
+                    tokio::time::sleep(Duration::from_millis(10 << attempt)).await;
+                }
+
+                let current: Vec<(String, String)> =
+                    sqlx::query_as("SELECT id, status FROM containers WHERE name = ?1 LIMIT 2")
+                        .bind(name)
+                        .fetch_all(pool)
+                        .await?;
+                let (id, current) = one_named(name, current)?;
+                let from = self.decode_status(&current)?;
+
+                if !from.can_transition_to(&to) {
+                    return Err(DbError::InvalidTransition {
+                        name: name.to_string(),
+                        from,
+                        to,
+                    });
+                }
+
+                let written = async {
+                    let mut tx = begin_write(pool).await?;
+                    let now = next_updated_at(&mut *tx, &id).await?;
+                    let result = sqlx::query(
+                        "UPDATE containers SET status = ?1, updated_at = ?2 WHERE id = ?3 AND status = ?4",
+                    )
+                    .bind(to.as_str())
+                    .bind(&now)
+                    .bind(&id)
+                    .bind(&current)
+                    .execute(&mut *tx)
+                    .await?;
+                    tx.commit().await?;
+                    Ok::<_, DbError>(result.rows_affected())
+                }
+                .await;
+
+                match written {
+                    Ok(rows) if rows > 0 => {
+                        sampled!(
+                            self,
+                            "transition_status",
+                            container.name = %name, %from, %to, "Transitioned container status"
+                        );
+                        self.notify_status(name, to);
+                        return Ok(from);
+                    }
+                    // Someone else changed the status since it was read
+                    Ok(_) => continue,
+                    Err(e) if e.is_retryable() => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            Err(DbError::Conflict(format!(
+                "status of {} kept changing after {} attempts",
+                name, MAX_TRANSITION_ATTEMPTS
+            )))
+        })
+        .await
+    }
+
+    pub async fn update_config(
+        &self,
+        name: impl AsRef<str>,
+        config: &ContainerConfig,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            validate_container_config_with(config, self.memory_disk_policy).map_err(invalid)?;
+
+            let mut tx = self.writer()?.begin().await?;
+
+            let current: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, status, previous) = one_named(name, current)?;
+            self.check_limits_unchanged(name, &status, &serde_json::from_str(&previous)?, config)?;
+
+            let now = next_updated_at(&mut *tx, &id).await?;
+            sqlx::query("UPDATE containers SET config = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(serde_json::to_string(&config.normalized())?)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            info!(container.name = %name, "Updated container config");
+            Ok(())
+        })
+        .await
+    }
+
+    /// The stored config JSON exactly as written, unknown fields included
+    pub async fn get_config_json(&self, name: impl AsRef<str>) -> Result<String, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let configs: Vec<String> =
+                sqlx::query_scalar("SELECT config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&self.pool)
+                    .await?;
+
+            one_named(name, configs)
+        })
+        .await
+    }
+
+    /// Replace the stored config JSON verbatim, without normalizing it
+    ///
+    /// The text must parse as JSON. Unless `skip_validation` is set, it must
+    /// also decode to a `ContainerConfig` that passes the same checks as
+    /// `update_config`. Skipping is meant for migrations and repairs that
+    /// have to write configs current code would reject.
+    pub async fn set_config_json(
+        &self,
+        name: impl AsRef<str>,
+        json: &str,
+        skip_validation: bool,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let value: serde_json::Value = serde_json::from_str(json)?;
+
+            let mut tx = self.writer()?.begin().await?;
+
+            let current: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, status, previous) = one_named(name, current)?;
+
+            if !skip_validation {
+                let config: ContainerConfig = serde_json::from_value(value)?;
+                validate_container_config_with(&config, self.memory_disk_policy)
+                    .map_err(invalid)?;
+                self.check_limits_unchanged(
+                    name,
+                    &status,
+                    &serde_json::from_str(&previous)?,
+                    &config,
+                )?;
+            }
+
+            let now = next_updated_at(&mut *tx, &id).await?;
+            sqlx::query("UPDATE containers SET config = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(json)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            if skip_validation {
+                warn!(container.name = %name, "Wrote raw container config without validation");
+            } else {
+                info!(container.name = %name, "Wrote raw container config");
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Apply a partial update to a container's config and return the result
+    ///
+    /// Environment entries are merged into the stored list with
+    /// `merge_environment` rather than replacing it.
+    pub async fn patch_config(
+        &self,
+        name: impl AsRef<str>,
+        patch: &ConfigPatch,
+    ) -> Result<ContainerConfig, DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let current: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, status, config FROM containers WHERE name = ?1 LIMIT 2")
+                    .bind(name)
+                    .fetch_all(&mut *tx)
+                    .await?;
+            let (id, status, config) = one_named(name, current)?;
+            let previous: ContainerConfig = serde_json::from_str(&config)?;
+            let mut config = previous.clone();
+
+            if let Some(cpu_limit) = patch.cpu_limit {
+                config.cpu_limit = Some(cpu_limit);
+            }
+            if let Some(memory_limit) = patch.memory_limit {
+                config.memory_limit = Some(memory_limit);
+            }
+            if let Some(disk_limit) = patch.disk_limit {
+                config.disk_limit = Some(disk_limit);
+            }
+            if let Some(environment) = &patch.environment {
+                config.environment = merge_environment(&config.environment, environment)
+                    .map_err(|e| invalid(vec![e]))?;
+            }
+            validate_container_config_with(&config, self.memory_disk_policy).map_err(invalid)?;
+            self.check_limits_unchanged(name, &status, &previous, &config)?;
+            let config = config.normalized();
+
+            let now = next_updated_at(&mut *tx, &id).await?;
+            sqlx::query("UPDATE containers SET config = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(serde_json::to_string(&config)?)
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+
+            info!(container.name = %name, "Patched container config");
+            Ok(config)
+        })
+        .await
+    }
+
+    /// Place a container on a node, or clear its placement with `None`
+    pub async fn assign_node(
+        &self,
+        name: impl AsRef<str>,
+        node_id: Option<Uuid>,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+            self.assign_node_on(&mut tx, name, node_id).await?;
+            tx.commit().await?;
+
+            info!(container.name = %name, ?node_id, "Assigned container to node");
+            Ok(())
+        })
+        .await
+    }
+
+    /// `assign_node` on the caller's connection or transaction
+    pub(crate) async fn assign_node_on(
+        &self,
+        conn: &mut SqliteConnection,
+        name: &str,
+        node_id: Option<Uuid>,
+    ) -> Result<(), DbError> {
+        let id = id_for_name(&mut *conn, name).await?;
+        if let Some(node_id) = node_id {
+            self.check_node_capacity(conn, &node_id, Some(&id)).await?;
+        }
+
+        let now = next_updated_at(&mut *conn, &id).await?;
+        let result =
+            sqlx::query("UPDATE containers SET node_id = ?1, updated_at = ?2 WHERE id = ?3")
+                .bind(node_id.map(|id| id.to_string()))
+                .bind(&now)
+                .bind(&id)
+                .execute(conn)
+                .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::ContainerNotFound(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Assign the oldest unassigned container to `node_id` and return it
+    ///
+    /// Selection and assignment are one statement, so concurrent claimants
+    /// never get the same container. `Ok(None)` when nothing is unassigned.
+    pub async fn claim_next_unassigned(&self, node_id: Uuid) -> Result<Option<Container>, DbError> {
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+            let Some(container) = self.claim_next_unassigned_on(&mut tx, node_id).await? else {
+                return Ok(None);
+            };
+            tx.commit().await?;
+
+            info!(container.name = %container.name, %node_id, "Claimed container");
+            Ok(Some(container))
+        })
+        .await
+    }
+
+    /// `claim_next_unassigned` on the caller's connection or transaction
+    pub(crate) async fn claim_next_unassigned_on(
+        &self,
+        conn: &mut SqliteConnection,
+        node_id: Uuid,
+    ) -> Result<Option<Container>, DbError> {
+        self.check_node_capacity(conn, &node_id, None).await?;
+
+        // RETURNING must be stepped to completion; see `get_or_create_placed`
+        let claimed: Option<String> = sqlx::query_scalar(
+            "UPDATE containers SET node_id = ?1
+             WHERE id = (
+                 SELECT id FROM containers WHERE node_id IS NULL
+                 ORDER BY created_at ASC, id ASC LIMIT 1
+             ) AND node_id IS NULL
+             RETURNING id",
+        )
+        .bind(node_id.to_string())
+        .fetch_all(&mut *conn)
+        .await?
+        .pop();
+        let Some(id) = claimed else {
+            return Ok(None);
+        };
+
+        // The claim holds the write lock, so the row cannot change in between
+        let now = next_updated_at(&mut *conn, &id).await?;
+        let row = sqlx::query(&format!(
+            "UPDATE containers SET updated_at = ?1 WHERE id = ?2 RETURNING {}",
+            CONTAINER_COLUMNS
+        ))
+        .bind(&now)
+        .bind(&id)
+        .fetch_all(conn)
+        .await?
+        .pop()
+        .ok_or_else(|| DbError::ContainerNotFound(id.clone()))?;
+
+        self.to_container(&row).map(Some)
+    }
+
+    /// Spread every unassigned container across `node_ids` round-robin
+    ///
+    /// Oldest containers are placed first, starting with the first node. All
+    /// placements commit together; if any node would exceed
+    /// `max_containers_per_node`, none do. Returns `(container_id, node_id)`
+    /// pairs in placement order.
+    pub async fn distribute_unassigned(
+        &self,
+        node_ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, Uuid)>, DbError> {
+        self.timed(async {
+            if node_ids.is_empty() {
+                return Err(DbError::InvalidData(
+                    "Cannot distribute containers across zero nodes".to_string(),
+                ));
+            }
+
+            let mut tx = self.writer()?.begin().await?;
+
+            let ids: Vec<String> = sqlx::query_scalar(
+                "SELECT id FROM containers WHERE node_id IS NULL ORDER BY created_at ASC, id ASC",
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+
+            let mut placements = Vec::with_capacity(ids.len());
+            for (id, node_id) in ids.iter().zip(node_ids.iter().cycle()) {
+                self.check_node_capacity(&mut tx, node_id, None).await?;
+
+                let now = next_updated_at(&mut *tx, id).await?;
+                sqlx::query("UPDATE containers SET node_id = ?1, updated_at = ?2 WHERE id = ?3")
+                    .bind(node_id.to_string())
+                    .bind(&now)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                placements.push((parse_uuid(id)?, *node_id));
+            }
+            tx.commit().await?;
+
+            info!(
+                nodes = node_ids.len(),
+                count = placements.len(),
+                "Distributed unassigned containers"
+            );
+            Ok(placements)
+        })
+        .await
+    }
+
+    /// Refuse placement on a node already at `max_containers_per_node`
+    ///
+    /// `moving`, a container id, is left out of the count so reassigning a
+    /// container to the node it is already on never fails.
+    async fn check_node_capacity(
+        &self,
+        conn: &mut SqliteConnection,
+        node_id: &Uuid,
+        moving: Option<&str>,
+    ) -> Result<(), DbError> {
+        let Some(max) = self.max_containers_per_node else {
+            return Ok(());
+        };
+        let current: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM containers WHERE node_id = ?1 AND id IS NOT ?2",
+        )
+        .bind(node_id.to_string())
+        .bind(moving)
+        .fetch_one(conn)
+        .await?;
+        check_node_container_count(current, max).map_err(|e| invalid(vec![e]))
+    }
+
+    /// Exchange the names of two containers, e.g. for a blue/green cutover
+    ///
+    /// Runs in one transaction, parking `a` on a temporary name so the
+    /// unique name index holds at every step.
+    pub async fn swap_names(&self, a: impl AsRef<str>, b: impl AsRef<str>) -> Result<(), DbError> {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let mut ids = Vec::with_capacity(2);
+            for name in [a, b] {
+                ids.push(id_for_name(&mut *tx, name).await?);
+            }
+            if a == b {
+                return Ok(());
+            }
+
+            let parked = format!("swap-{}", Uuid::new_v4());
+            for (id, name) in [(&ids[0], parked.as_str()), (&ids[1], a), (&ids[0], b)] {
+                let now = next_updated_at(&mut *tx, id).await?;
+                sqlx::query("UPDATE containers SET name = ?1, updated_at = ?2 WHERE id = ?3")
+                    .bind(name)
+                    .bind(&now)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+
+            info!(container.a = %a, container.b = %b, "Swapped container names");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Unassign every container on a node, recording a drain event for each
+    ///
+    /// Statuses are left alone. Returns how many containers were moved off.
+    pub async fn drain_node(&self, node_id: &Uuid) -> Result<u64, DbError> {
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let ids: Vec<String> =
+                sqlx::query_scalar("SELECT id FROM containers WHERE node_id = ?1")
+                    .bind(node_id.to_string())
+                    .fetch_all(&mut *tx)
+                    .await?;
+
+            let message = format!("Drained from node {}", node_id);
+            for id in &ids {
+                let now = next_updated_at(&mut *tx, id).await?;
+                sqlx::query("UPDATE containers SET node_id = NULL, updated_at = ?1 WHERE id = ?2")
+                    .bind(&now)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                record_event(&mut tx, id, EVENT_DRAIN, Some(&message)).await?;
+            }
+            tx.commit().await?;
+
+            info!(%node_id, count = ids.len(), "Drained node");
+            Ok(ids.len() as u64)
+        })
+        .await
+    }
+
+    /// Set or clear (`None`) the free-text description
+    pub async fn set_description(
+        &self,
+        name: impl AsRef<str>,
+        description: Option<&str>,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            if let Some(description) = description {
+                validate_description(description).map_err(|e| invalid(vec![e]))?;
+            }
+
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result = sqlx::query(
+                "UPDATE containers SET description = ?1, updated_at = ?2 WHERE id = ?3",
+            )
+            .bind(description)
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            info!(container.name = %name, "Updated container description");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Add or overwrite a single label without touching the others
+    pub async fn set_label(
+        &self,
+        name: impl AsRef<str>,
+        key: &str,
+        value: &str,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            validate_label(key, value).map_err(|e| invalid(vec![e]))?;
+
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result = sqlx::query(
+                "UPDATE containers SET labels = json_set(labels, ?1, ?2), updated_at = ?3 WHERE id = ?4",
+            )
+            .bind(label_path(key))
+            .bind(value)
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            info!(container.name = %name, label.key = %key, label.value = %value, "Set container label");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Remove a single label; removing an absent key is not an error
+    pub async fn remove_label(&self, name: impl AsRef<str>, key: &str) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            validate_label(key, "").map_err(|e| invalid(vec![e]))?;
+
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result = sqlx::query(
+                "UPDATE containers SET labels = json_remove(labels, ?1), updated_at = ?2 WHERE id = ?3",
+            )
+            .bind(label_path(key))
+            .bind(&now)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            info!(container.name = %name, label.key = %key, "Removed container label");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Record that a container is still alive without changing its status
+    pub async fn touch(&self, name: impl AsRef<str>) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = begin_write(self.writer()?).await?;
+            let id = id_for_name(&mut *tx, name).await?;
+            let now = next_updated_at(&mut *tx, &id).await?;
+            let result = sqlx::query("UPDATE containers SET updated_at = ?1 WHERE id = ?2")
+                .bind(&now)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?;
+
+            if result.rows_affected() == 0 {
+                return Err(DbError::ContainerNotFound(name.to_string()));
+            }
+            tx.commit().await?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Containers changed after `since`, least recently updated first
+    ///
+    /// For incremental sync: pass the newest `updated_at` from the previous
+    /// pull to get only what changed since.
+    pub async fn list_updated_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE updated_at > ?1 ORDER BY updated_at ASC, id ASC",
+                CONTAINER_COLUMNS
+            ))
+            .bind(format_timestamp(&since))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Running containers that have not been touched within `older_than`
+    pub async fn list_stale(&self, older_than: Duration) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let cutoff = Utc::now()
+                .checked_sub_signed(to_chrono(older_than)?)
+                .ok_or_else(|| {
+                    DbError::Internal(format!("Duration out of range: {:?}", older_than))
+                })?;
+
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE status = ?1 AND updated_at < ?2 ORDER BY updated_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .bind(ContainerStatus::Running.as_str())
+            .bind(format_timestamp(&cutoff))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// Containers still `Starting` after `threshold`, likely stuck
+    ///
+    /// Age is measured from `updated_at`, i.e. from when the container
+    /// entered `Starting` unless something else touched it since.
+    pub async fn list_stuck_starting(
+        &self,
+        threshold: Duration,
+    ) -> Result<Vec<Container>, DbError> {
+        self.timed(async {
+            let cutoff = Utc::now()
+                .checked_sub_signed(to_chrono(threshold)?)
+                .ok_or_else(|| {
+                    DbError::Internal(format!("Duration out of range: {:?}", threshold))
+                })?;
+
+            let rows = sqlx::query(&format!(
+                "SELECT {} FROM containers WHERE status = ?1 AND updated_at < ?2 ORDER BY updated_at ASC",
+                CONTAINER_COLUMNS
+            ))
+            .bind(ContainerStatus::Starting.as_str())
+            .bind(format_timestamp(&cutoff))
+            .fetch_all(&self.pool)
+            .await?;
+
+            rows.iter().map(|row| self.to_container(row)).collect()
+        })
+        .await
+    }
+
+    /// How many containers in `status` have been unchanged for at least each
+    /// of `buckets`
+    ///
+    /// Age is measured from `updated_at`. Buckets are cumulative: a container
+    /// stopped for ten days counts towards both a one-day and a seven-day
+    /// bucket. Results come back in the order the buckets were given.
+    pub async fn status_age_buckets(
+        &self,
+        status: ContainerStatus,
+        buckets: &[Duration],
+    ) -> Result<Vec<(Duration, i64)>, DbError> {
+        self.timed(async {
+            if buckets.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let now = Utc::now();
+            let columns = (1..=buckets.len())
+                .map(|i| format!("COALESCE(SUM(updated_at <= ?{}), 0)", i + 1))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let sql = format!("SELECT {} FROM containers WHERE status = ?1", columns);
+
+            let mut query = sqlx::query(&sql).bind(status.as_str());
+            for bucket in buckets {
+                let cutoff = now.checked_sub_signed(to_chrono(*bucket)?).ok_or_else(|| {
+                    DbError::Internal(format!("Duration out of range: {:?}", bucket))
+                })?;
+                query = query.bind(format_timestamp(&cutoff));
+            }
+            let row = query.fetch_one(&self.pool).await?;
+
+            buckets
+                .iter()
+                .enumerate()
+                .map(|(i, bucket)| Ok((*bucket, row.try_get::<i64, _>(i)?)))
+                .collect()
+        })
+        .await
+    }
+
+    /// Fold the WAL back into the main database file and truncate it
+    ///
+    /// Meant for a periodic maintenance task. On a database that is not in WAL
+    /// mode SQLite reports nothing to do and this is a no-op.
+    pub async fn checkpoint(&self) -> Result<(), DbError> {
+        self.timed(async {
+            let row = sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+                .fetch_one(&self.pool)
+                .await?;
+
+            let busy: i64 = row.try_get(0)?;
+            let log_frames: i64 = row.try_get(1)?;
+            let checkpointed: i64 = row.try_get(2)?;
+            info!(busy, log_frames, checkpointed, "WAL checkpoint");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Refresh query planner statistics and optionally reclaim free pages
+    ///
+    /// `VACUUM` rewrites the whole file and holds an exclusive lock while it
+    /// runs, so only pass `vacuum = true` from a scheduled maintenance window.
+    pub async fn optimize(&self, vacuum: bool) -> Result<(), DbError> {
+        self.timed(async {
+            sqlx::query("PRAGMA optimize")
+                .execute(self.writer()?)
+                .await?;
+            if vacuum {
+                sqlx::query("VACUUM").execute(self.writer()?).await?;
+            }
+
+            info!(vacuum, "Optimized database");
+            Ok(())
+        })
+        .await
+    }
+
+    /// Delete a container together with its events and config history
+    ///
+    /// Dependent rows are removed explicitly in the same transaction rather
+    /// than relying on `ON DELETE CASCADE`, which the existing tables lack.
+    pub async fn delete(&self, name: impl AsRef<str>) -> Result<(), DbError> {
+        let name = name.as_ref();
+        self.timed(async {
+            let mut tx = self.writer()?.begin().await?;
+
+            let id = id_for_name(&mut *tx, name).await?;
+            delete_container_rows(&mut tx, &id).await?;
+            tx.commit().await?;
+
+            info!(container.name = %name, "Deleted container");
+            Ok(())
+        })
+        .await
+    }
+}
+
+/// Remove a container row and everything keyed to it, on the caller's transaction
+pub(crate) async fn delete_container_rows(
+    conn: &mut SqliteConnection,
+    id: &str,
+) -> Result<(), DbError> {
+    for sql in [
+        "DELETE FROM container_events WHERE container_id = ?1",
+        "DELETE FROM config_history WHERE container_id = ?1",
+        "DELETE FROM containers WHERE id = ?1",
+    ] {
+        sqlx::query(sql).bind(id).execute(&mut *conn).await?;
+    }
+    Ok(())
+}
+
+/// Insert `container` as a new row on the caller's connection or transaction
+pub(crate) async fn insert_container(
+    conn: &mut SqliteConnection,
+    container: &Container,
+) -> Result<(), DbError> {
+    let result = sqlx::query(
+        "INSERT INTO containers (id, name, status, template, node_id, config, labels, description, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    )
+    .bind(container.id.to_string())
+    .bind(container.name.as_str())
+    .bind(container.status.as_str())
+    .bind(&container.template)
+    .bind(container.node_id.map(|id| id.to_string()))
+    .bind(serde_json::to_string(&container.config.normalized())?)
+    .bind(serde_json::to_string(&container.labels)?)
+    .bind(&container.description)
+    .bind(format_timestamp(&container.created_at))
+    .bind(format_timestamp(&container.updated_at))
+    .execute(conn)
+    .await;
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Err(DbError::ContainerAlreadyExists(container.name.to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A freshly created, unplaced container in the `Stopped` state
+pub(crate) fn new_container(name: &str, template: &str, config: ContainerConfig) -> Container {
+    let now = Utc::now();
+    Container {
+        id: Uuid::new_v4(),
+        name: ContainerName::new_unchecked(name),
+        status: ContainerStatus::Stopped,
+        template: template.to_string(),
+        node_id: None,
+        created_at: now,
+        updated_at: now,
+        config,
+        labels: Default::default(),
+        description: None,
+    }
+}
+
+/// Convert a `containers` row into a `Container`
+pub fn row_to_container(row: &SqliteRow) -> Result<Container, DbError> {
+    row_to_container_with_depth(row, MAX_CONFIG_DEPTH)
+}
+
+/// Convert a row, rejecting config JSON nested deeper than `max_depth`
+pub fn row_to_container_with_depth(
+    row: &SqliteRow,
+    max_depth: usize,
+) -> Result<Container, DbError> {
+    row_to_container_with(row, max_depth, StatusDecoding::Strict)
+}
+
+/// Convert a row, handling unrecognized statuses as `status_decoding` says
+pub fn row_to_container_with(
+    row: &SqliteRow,
+    max_depth: usize,
+    status_decoding: StatusDecoding,
+) -> Result<Container, DbError> {
+    let id: String = row.try_get("id")?;
+    let node_id: Option<String> = row.try_get("node_id")?;
+    let status: String = row.try_get("status")?;
+    let config: String = row.try_get("config")?;
+    let labels: String = row.try_get("labels")?;
+
+    let depth = json_depth(&config);
+    if depth > max_depth {
+        return Err(DbError::Corrupt(format!(
+            "Config JSON nesting depth {} exceeds the limit of {}",
+            depth, max_depth
+        )));
+    }
+    let status = status_decoding.decode(&status)?;
+    let created_at: String = row.try_get("created_at")?;
+    let updated_at: String = row.try_get("updated_at")?;
+
+    Ok(Container {
+        id: parse_uuid(&id)?,
+        // Checked when written, possibly under a looser `NamePolicy`
+        name: ContainerName::new_unchecked(row.try_get::<String, _>("name")?),
+        status,
+        template: row.try_get("template")?,
+        node_id: node_id.as_deref().map(parse_uuid).transpose()?,
+        created_at: parse_timestamp(&created_at)?,
+        updated_at: parse_timestamp(&updated_at)?,
+        config: serde_json::from_str(&config)?,
+        labels: serde_json::from_str(&labels)?,
+        description: row.try_get("description")?,
+    })
+}
+
+/// `?1, ?2, ...` for an `IN (...)` clause with `count` bound values
+pub(crate) fn placeholders(count: usize) -> String {
+    (1..=count)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// SQL for the integer at `path` in `config`, or NULL when unset or not an integer
+fn config_integer(path: &str) -> String {
+    format!(
+        "CASE WHEN json_type(config, '{path}') = 'integer' THEN json_extract(config, '{path}') END"
+    )
+}
+
+/// AND of one `json_extract` comparison per selector entry, binding path then value
+fn selector_clause(selector: &[(String, String)]) -> Result<String, DbError> {
+    let mut validator = Validator::new();
+    for (key, value) in selector {
+        validator.check(validate_label(key, value));
+    }
+    validator.finish().map_err(invalid)?;
+
+    if selector.is_empty() {
+        return Ok("1 = 1".to_string());
+    }
+    Ok((0..selector.len())
+        .map(|i| format!("json_extract(labels, ?{}) = ?{}", 2 * i + 1, 2 * i + 2))
+        .collect::<Vec<_>>()
+        .join(" AND "))
+}
+
+/// JSON path for a label key; quoted so keys containing `.` or `/` stay one member
+fn label_path(key: &str) -> String {
+    format!("$.\"{}\"", key)
+}
+
+/// Carry validation failures, field by field, in a single `DbError`
+pub(crate) fn invalid(errors: Vec<ValidationError>) -> DbError {
+    DbError::Validation(errors)
+}
+
+/// Maximum array/object nesting depth of a JSON document, ignoring brackets
+/// inside string literals. Runs before deserializing so hostile input is
+/// rejected without recursing into it.
+pub(crate) fn json_depth(json: &str) -> usize {
+    let mut depth = 0usize;
+    let mut max_depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for byte in json.bytes() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                max_depth = max_depth.max(depth);
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    max_depth
+}
+
+/// Fixed-width RFC 3339 so that stored timestamps sort and compare as text
+pub(crate) fn format_timestamp(ts: &DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// Begin a transaction that already holds the database write lock
+///
+/// sqlx only issues a deferred `BEGIN`, so a transaction that reads before it
+/// writes can be overtaken by another writer in between. A no-op write first
+/// takes the lock up front, as `BEGIN IMMEDIATE` would: reads inside see the
+/// latest commit and nobody else writes until this one ends.
+pub(crate) async fn begin_write(
+    pool: &SqlitePool,
+) -> Result<sqlx::Transaction<'static, sqlx::Sqlite>, DbError> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM containers WHERE 0")
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
+}
+
+/// The id of the one container called `name`
+///
+/// Under `NameScope::PerTemplate` a name may be taken once per template;
+/// writers keyed by name alone then refuse it with `DbError::AmbiguousName`
+/// instead of touching every match.
+pub(crate) async fn id_for_name<'e, E>(executor: E, name: &str) -> Result<String, DbError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let ids: Vec<String> = sqlx::query_scalar("SELECT id FROM containers WHERE name = ?1 LIMIT 2")
+        .bind(name)
+        .fetch_all(executor)
+        .await?;
+    one_named(name, ids)
+}
+
+/// The only row fetched for `name`, or why there is not exactly one
+pub(crate) fn one_named<T>(name: &str, mut rows: Vec<T>) -> Result<T, DbError> {
+    if rows.len() > 1 {
+        return Err(DbError::AmbiguousName(name.to_string()));
+    }
+    rows.pop()
+        .ok_or_else(|| DbError::ContainerNotFound(name.to_string()))
+}
+
+/// `updated_at` to write for the row `id` is about to be updated
+///
+/// Normally now. If this host's clock is behind the stored value, e.g. it
+/// was last written by a node whose clock runs ahead, the stored value plus
+/// 1µs is used instead so `updated_at` never goes backwards for a row. A
+/// missing row gets now and is left for the caller's write to report.
+pub(crate) async fn next_updated_at<'e, E>(executor: E, id: &str) -> Result<String, DbError>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+{
+    let stored: Option<String> =
+        sqlx::query_scalar("SELECT updated_at FROM containers WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(executor)
+            .await?;
+
+    Ok(monotonic_timestamp(stored.as_deref(), Utc::now()))
+}
+
+pub(crate) fn monotonic_timestamp(stored: Option<&str>, now: DateTime<Utc>) -> String {
+    let Some(stored) = stored.and_then(|stored| parse_timestamp(stored).ok()) else {
+        return format_timestamp(&now);
+    };
+    if now > stored {
+        return format_timestamp(&now);
+    }
+
+    warn!(%stored, %now, "Clock is behind the stored updated_at, writing just past it");
+    format_timestamp(&(stored + chrono::Duration::microseconds(1)))
+}
+
+pub(crate) fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, DbError> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|ts| ts.with_timezone(&Utc))
+        .map_err(|e| DbError::Corrupt(format!("Invalid timestamp '{}': {}", s, e)))
+}
+
+pub(crate) fn parse_uuid(s: &str) -> Result<Uuid, DbError> {
+    Uuid::parse_str(s).map_err(|e| DbError::Corrupt(format!("Invalid UUID '{}': {}", s, e)))
+}
+
+pub(crate) fn to_chrono(duration: Duration) -> Result<chrono::Duration, DbError> {
+    chrono::Duration::from_std(duration)
+        .map_err(|_| DbError::Internal(format!("Duration out of range: {:?}", duration)))
+}
@@ -0,0 +1,164 @@
+use serde::Serialize;
+use sqlx::{Row, SqliteConnection};
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::{format_timestamp, ContainerStore};
+use models::{validate_create_with, Container, ValidationErrors};
+
+/// What `sync` did with each container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// A container as the column values `sync` writes
+#[derive(PartialEq)]
+struct RowValues {
+    name: String,
+    status: String,
+    template: String,
+    node_id: Option<String>,
+    config: serde_json::Value,
+    labels: serde_json::Value,
+    description: Option<String>,
+    created_at: String,
+    updated_at: String,
+}
+
+impl RowValues {
+    fn from_container(container: &Container) -> Result<Self, DbError> {
+        Ok(Self {
+            name: container.name.to_string(),
+            status: container.status.as_str().to_string(),
+            template: container.template.clone(),
+            node_id: container.node_id.map(|id| id.to_string()),
+            config: serde_json::to_value(container.config.normalized())?,
+            labels: serde_json::to_value(&container.labels)?,
+            description: container.description.clone(),
+            created_at: format_timestamp(&container.created_at),
+            updated_at: format_timestamp(&container.updated_at),
+        })
+    }
+
+    async fn load(conn: &mut SqliteConnection, id: &str) -> Result<Option<Self>, DbError> {
+        let row = sqlx::query(
+            "SELECT name, status, template, node_id, config, labels, description, created_at, updated_at
+             FROM containers WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(conn)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let config: String = row.try_get("config")?;
+        let labels: String = row.try_get("labels")?;
+
+        Ok(Some(Self {
+            name: row.try_get("name")?,
+            status: row.try_get("status")?,
+            template: row.try_get("template")?,
+            node_id: row.try_get("node_id")?,
+            config: serde_json::from_str(&config)?,
+            labels: serde_json::from_str(&labels)?,
+            description: row.try_get("description")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        }))
+    }
+}
+
+impl ContainerStore {
+    /// Make the store match `containers`, keyed by id, in one transaction
+    ///
+    /// Rows are written verbatim, as with `upsert`, so re-running the same
+    /// sync reports everything unchanged. Every entry is validated before
+    /// anything is written; the first invalid one aborts the sync and is
+    /// named by its index. Containers not in `containers` are left alone.
+    pub async fn sync(&self, containers: &[Container]) -> Result<SyncReport, DbError> {
+        self.timed(async {
+            for (index, container) in containers.iter().enumerate() {
+                validate_create_with(
+                    &container.name,
+                    &container.template,
+                    &container.config,
+                    self.memory_disk_policy(),
+                )
+                .map_err(
+                    |errors| {
+                        DbError::InvalidData(format!(
+                            "Entry {} ({}): {}",
+                            index,
+                            container.name,
+                            ValidationErrors(errors)
+                        ))
+                    },
+                )?;
+            }
+
+            let mut tx = self.writer()?.begin().await?;
+            let mut report = SyncReport::default();
+
+            for container in containers {
+                let id = container.id.to_string();
+                let values = RowValues::from_container(container)?;
+                match RowValues::load(&mut tx, &id).await? {
+                    Some(stored) if stored == values => {
+                        report.unchanged += 1;
+                        continue;
+                    }
+                    Some(_) => report.updated += 1,
+                    None => report.created += 1,
+                }
+
+                sqlx::query(
+                    "INSERT INTO containers (id, name, status, template, node_id, config, labels, description, created_at, updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                     ON CONFLICT(id) DO UPDATE SET
+                        name = excluded.name,
+                        status = excluded.status,
+                        template = excluded.template,
+                        node_id = excluded.node_id,
+                        config = excluded.config,
+                        labels = excluded.labels,
+                        description = excluded.description,
+                        created_at = excluded.created_at,
+                        updated_at = excluded.updated_at",
+                )
+                .bind(&id)
+                .bind(&values.name)
+                .bind(&values.status)
+                .bind(&values.template)
+                .bind(&values.node_id)
+                .bind(values.config.to_string())
+                .bind(values.labels.to_string())
+                .bind(&values.description)
+                .bind(&values.created_at)
+                .bind(&values.updated_at)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+                        DbError::ContainerAlreadyExists(container.name.to_string())
+                    }
+                    e => e.into(),
+                })?;
+            }
+
+            tx.commit().await?;
+
+            info!(
+                created = report.created,
+                updated = report.updated,
+                unchanged = report.unchanged,
+                "Synced containers"
+            );
+            Ok(report)
+        })
+        .await
+    }
+}
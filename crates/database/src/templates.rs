@@ -0,0 +1,132 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use sqlx::Row;
+use tracing::info;
+
+use crate::error::DbError;
+use crate::store::{format_timestamp, invalid, parse_timestamp, placeholders};
+use models::{validate_disk_limit, validate_template, DiskLimit, TemplateInfo};
+
+/// Registry of known templates and their provisioning requirements
+#[derive(Clone)]
+pub struct TemplateStore {
+    pool: SqlitePool,
+}
+
+impl TemplateStore {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Register a template, or update the minimum disk of an existing one
+    pub async fn register(
+        &self,
+        name: &str,
+        min_disk_bytes: Option<u64>,
+    ) -> Result<TemplateInfo, DbError> {
+        validate_template(name).map_err(|e| invalid(vec![e]))?;
+        if let Some(min) = min_disk_bytes {
+            validate_disk_limit(&DiskLimit::Bytes(min)).map_err(|e| invalid(vec![e]))?;
+        }
+
+        // fetch_all steps the upsert to completion; stopping after the first
+        // returned row can leave the update uncommitted
+        let row = sqlx::query(
+            "INSERT INTO templates (name, min_disk_bytes, created_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET min_disk_bytes = excluded.min_disk_bytes
+             RETURNING name, min_disk_bytes, created_at",
+        )
+        .bind(name)
+        .bind(min_disk_bytes.map(|min| min as i64))
+        .bind(format_timestamp(&Utc::now()))
+        .fetch_all(&self.pool)
+        .await?
+        .pop()
+        .ok_or_else(|| DbError::TemplateNotFound(name.to_string()))?;
+
+        info!(template = %name, ?min_disk_bytes, "Registered template");
+        row_to_template(&row)
+    }
+
+    pub async fn get(&self, name: &str) -> Result<TemplateInfo, DbError> {
+        let row =
+            sqlx::query("SELECT name, min_disk_bytes, created_at FROM templates WHERE name = ?1")
+                .bind(name)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        match row {
+            Some(row) => row_to_template(&row),
+            None => Err(DbError::TemplateNotFound(name.to_string())),
+        }
+    }
+
+    /// Which of `names` are registered, in one query
+    pub async fn existing(&self, names: &[String]) -> Result<HashSet<String>, DbError> {
+        Ok(self.min_disks(names).await?.into_keys().collect())
+    }
+
+    /// The disk minimum of each registered template in `names`, in one query
+    ///
+    /// Unregistered names are absent; templates without a minimum map to `None`.
+    pub async fn min_disks(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, Option<u64>>, DbError> {
+        if names.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let sql = format!(
+            "SELECT name, min_disk_bytes FROM templates WHERE name IN ({})",
+            placeholders(names.len())
+        );
+        let mut query = sqlx::query_as::<_, (String, Option<i64>)>(&sql);
+        for name in names {
+            query = query.bind(name);
+        }
+
+        Ok(query
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|(name, min)| (name, min.map(|min| min as u64)))
+            .collect())
+    }
+
+    pub async fn list(&self) -> Result<Vec<TemplateInfo>, DbError> {
+        let rows =
+            sqlx::query("SELECT name, min_disk_bytes, created_at FROM templates ORDER BY name ASC")
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.iter().map(row_to_template).collect()
+    }
+
+    pub async fn remove(&self, name: &str) -> Result<(), DbError> {
+        let result = sqlx::query("DELETE FROM templates WHERE name = ?1")
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(DbError::TemplateNotFound(name.to_string()));
+        }
+
+        info!(template = %name, "Removed template");
+        Ok(())
+    }
+}
+
+fn row_to_template(row: &SqliteRow) -> Result<TemplateInfo, DbError> {
+    let min_disk_bytes: Option<i64> = row.try_get("min_disk_bytes")?;
+    let created_at: String = row.try_get("created_at")?;
+
+    Ok(TemplateInfo {
+        name: row.try_get("name")?,
+        min_disk_bytes: min_disk_bytes.map(|min| min as u64),
+        created_at: parse_timestamp(&created_at)?,
+    })
+}
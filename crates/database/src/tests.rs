@@ -0,0 +1,3449 @@
+use std::time::Duration;
+
+use models::{
+    Container, ContainerConfig, ContainerName, ContainerStatus, CreateContainerRequest, DiskLimit,
+    MemoryDiskPolicy, Template,
+};
+use uuid::Uuid;
+
+use crate::{
+    create_pool, migrations, ApplyReport, CmpOp, ConfigField, ContainerFilter, ContainerStore,
+    DbError, Environment, FleetTotals, InvalidContainer, NameScope, Page, PoolConfig,
+    StatusDecoding, TemplateStore,
+};
+
+/// Pool settings pointing at a throwaway on-disk database
+fn test_pool_config() -> PoolConfig {
+    let path = std::env::temp_dir().join(format!("arm_hypervisor_db_{}.sqlite", Uuid::new_v4()));
+    PoolConfig {
+        url: format!("sqlite://{}", path.display()),
+        create_if_missing: true,
+        ..PoolConfig::default()
+    }
+}
+
+async fn test_store_with(config: &PoolConfig) -> ContainerStore {
+    let pool = create_pool(config).await.expect("create pool");
+    migrations::run(&pool).await.expect("run migrations");
+    ContainerStore::from_config(pool, config)
+}
+
+/// Fresh store backed by a throwaway on-disk database
+async fn test_store() -> ContainerStore {
+    test_store_with(&test_pool_config()).await
+}
+
+fn test_config() -> ContainerConfig {
+    ContainerConfig {
+        cpu_limit: Some(2),
+        memory_limit: Some(512 * 1024 * 1024),
+        disk_limit: Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024)),
+        network_interfaces: vec![],
+        rootfs_path: "/var/lib/lxc/test/rootfs".to_string(),
+        environment: vec![("HOME".to_string(), "/root".to_string())],
+        hostname: None,
+        cpu_request: None,
+        memory_request: None,
+    }
+}
+
+/// Push a container's `updated_at` into the past
+async fn backdate(store: &ContainerStore, name: &str, age: Duration) {
+    let ts = chrono::Utc::now() - chrono::Duration::from_std(age).unwrap();
+    sqlx::query("UPDATE containers SET updated_at = ?1 WHERE name = ?2")
+        .bind(crate::store::format_timestamp(&ts))
+        .bind(name)
+        .execute(store.pool())
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_create_and_get() {
+    let store = test_store().await;
+    let created = store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let fetched = store.get_by_name("web").await.unwrap();
+    assert_eq!(fetched.id, created.id);
+    assert_eq!(fetched.status, ContainerStatus::Stopped);
+    assert_eq!(fetched.config.cpu_limit, Some(2));
+
+    assert!(matches!(
+        store.create("web", "alpine", &test_config()).await,
+        Err(DbError::ContainerAlreadyExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_get_or_create_returns_existing() {
+    let store = test_store().await;
+    let first = store
+        .get_or_create("web", "alpine", test_config())
+        .await
+        .unwrap();
+    let second = store
+        .get_or_create("web", "alpine", test_config())
+        .await
+        .unwrap();
+    assert_eq!(first.id, second.id);
+}
+
+#[tokio::test]
+async fn test_update_and_delete() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().status,
+        ContainerStatus::Running
+    );
+
+    store.delete("web").await.unwrap();
+    assert!(!store.exists("web").await.unwrap());
+    assert!(matches!(
+        store.update_status("web", ContainerStatus::Stopped).await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_touch_moves_updated_at() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    backdate(&store, "web", Duration::from_secs(60)).await;
+    let before = store.get_by_name("web").await.unwrap();
+
+    store.touch("web").await.unwrap();
+
+    let after = store.get_by_name("web").await.unwrap();
+    assert!(after.updated_at > before.updated_at);
+    assert_eq!(after.status, before.status);
+    assert_eq!(after.created_at, before.created_at);
+
+    assert!(matches!(
+        store.touch("missing").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_list_stale() {
+    let store = test_store().await;
+    for name in ["fresh", "stale", "stopped"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    store
+        .update_status("fresh", ContainerStatus::Running)
+        .await
+        .unwrap();
+    store
+        .update_status("stale", ContainerStatus::Running)
+        .await
+        .unwrap();
+    backdate(&store, "stale", Duration::from_secs(600)).await;
+    backdate(&store, "stopped", Duration::from_secs(600)).await;
+
+    let stale = store.list_stale(Duration::from_secs(300)).await.unwrap();
+    let names: Vec<_> = stale.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["stale"]);
+
+    // Fits a chrono::Duration but reaches back past the earliest DateTime
+    let ages_ago = Duration::from_secs(300_000 * 365 * 24 * 3600);
+    assert!(matches!(
+        store.list_stale(ages_ago).await,
+        Err(DbError::Internal(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_etag_tracks_changes() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let etag = store.get_by_name("web").await.unwrap().etag();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+    assert_eq!(store.get_by_name("web").await.unwrap().etag(), etag);
+
+    store
+        .update_status("web", ContainerStatus::Starting)
+        .await
+        .unwrap();
+    let changed = store.get_by_name("web").await.unwrap().etag();
+    assert_ne!(changed, etag);
+    assert_eq!(store.get_by_name("web").await.unwrap().etag(), changed);
+}
+
+#[tokio::test]
+async fn test_wait_for_status() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let writer = store.clone();
+    let flip = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        writer
+            .update_status("web", ContainerStatus::Starting)
+            .await
+            .unwrap();
+        writer
+            .update_status("web", ContainerStatus::Running)
+            .await
+            .unwrap();
+    });
+
+    let container = store
+        .wait_for_status("web", ContainerStatus::Running, Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(container.status, ContainerStatus::Running);
+    flip.await.unwrap();
+
+    // Already there: no waiting
+    store
+        .wait_for_status("web", ContainerStatus::Running, Duration::from_millis(1))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        store
+            .wait_for_status("web", ContainerStatus::Stopped, Duration::from_millis(50))
+            .await,
+        Err(DbError::Timeout(_))
+    ));
+    assert!(matches!(
+        store
+            .wait_for_status("missing", ContainerStatus::Running, Duration::from_secs(1))
+            .await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_list_stuck_starting() {
+    let store = test_store().await;
+    for name in ["stuck", "recent", "running"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    for name in ["stuck", "recent"] {
+        store
+            .update_status(name, ContainerStatus::Starting)
+            .await
+            .unwrap();
+    }
+    store
+        .update_status("running", ContainerStatus::Running)
+        .await
+        .unwrap();
+    backdate(&store, "stuck", Duration::from_secs(600)).await;
+    backdate(&store, "running", Duration::from_secs(600)).await;
+
+    let stuck = store
+        .list_stuck_starting(Duration::from_secs(300))
+        .await
+        .unwrap();
+    let names: Vec<_> = stuck.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, vec!["stuck"]);
+
+    let huge = Duration::from_secs(300_000 * 365 * 24 * 3600);
+    assert!(matches!(
+        store.list_stuck_starting(huge).await,
+        Err(DbError::Internal(_))
+    ));
+}
+
+/// Regression guard for the read-then-insert race: every caller must see the
+/// same row, never a unique-constraint error
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_get_or_create_concurrent_same_name() {
+    let config = PoolConfig {
+        max_connections: 8,
+        ..test_pool_config()
+    };
+    let store = test_store_with(&config).await;
+
+    let handles: Vec<_> = (0..50)
+        .map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move { store.get_or_create("race", "alpine", test_config()).await })
+        })
+        .collect();
+
+    let mut ids = std::collections::HashSet::new();
+    for handle in handles {
+        ids.insert(handle.await.unwrap().unwrap().id);
+    }
+    assert_eq!(ids.len(), 1);
+    assert_eq!(store.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_query_timeout() {
+    let config = PoolConfig {
+        max_connections: 1,
+        query_timeout: Duration::from_millis(50),
+        ..test_pool_config()
+    };
+    let store = test_store_with(&config).await;
+
+    // Hold the only connection so the next read cannot make progress
+    let _held = store.pool().acquire().await.unwrap();
+
+    let err = store.get_by_name("web").await.unwrap_err();
+    assert!(matches!(err, DbError::Timeout(_)));
+    assert!(err.is_retryable());
+}
+
+/// A driver error carrying only an SQLite result code
+#[derive(Debug)]
+struct SqliteCode(&'static str);
+
+impl std::fmt::Display for SqliteCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sqlite error {}", self.0)
+    }
+}
+
+impl std::error::Error for SqliteCode {}
+
+impl sqlx::error::DatabaseError for SqliteCode {
+    fn message(&self) -> &str {
+        "sqlite error"
+    }
+
+    fn code(&self) -> Option<std::borrow::Cow<'_, str>> {
+        Some(self.0.into())
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> sqlx::error::ErrorKind {
+        sqlx::error::ErrorKind::Other
+    }
+}
+
+#[test]
+fn test_extended_busy_codes_are_retryable() {
+    let error = |code| DbError::Sqlx(sqlx::Error::Database(Box::new(SqliteCode(code))));
+    // BUSY, LOCKED, BUSY_RECOVERY, LOCKED_SHAREDCACHE, BUSY_SNAPSHOT, BUSY_TIMEOUT
+    for code in ["5", "6", "261", "262", "517", "773"] {
+        assert!(error(code).is_retryable(), "{}", code);
+    }
+    // CONSTRAINT_UNIQUE and a non-numeric code
+    for code in ["2067", "HY000"] {
+        assert!(!error(code).is_retryable(), "{}", code);
+    }
+}
+
+fn write_temp_toml(content: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("arm_hypervisor_db_{}.toml", Uuid::new_v4()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn test_pool_config_from_complete_toml() {
+    let path = write_temp_toml(
+        r#"
+        [database]
+        url = "sqlite:///var/lib/arm-hypervisor/containers.db"
+        max_connections = 20
+        min_connections = 4
+        acquire_timeout_secs = 10
+        busy_timeout_ms = 2500
+        query_timeout_ms = 750
+        wal = false
+        foreign_keys = false
+        create_if_missing = true
+        statement_cache_capacity = 25
+        test_before_acquire = true
+        "#,
+    );
+
+    let config = PoolConfig::from_toml_path(&path).unwrap();
+    assert_eq!(config.url, "sqlite:///var/lib/arm-hypervisor/containers.db");
+    assert_eq!(config.max_connections, 20);
+    assert_eq!(config.min_connections, 4);
+    assert_eq!(config.acquire_timeout, Duration::from_secs(10));
+    assert_eq!(config.busy_timeout, Duration::from_millis(2500));
+    assert_eq!(config.query_timeout, Duration::from_millis(750));
+    assert!(!config.wal);
+    assert!(!config.foreign_keys);
+    assert!(config.create_if_missing);
+    assert_eq!(config.statement_cache_capacity, 25);
+    assert!(config.test_before_acquire);
+}
+
+#[test]
+fn test_pool_config_from_partial_toml() {
+    let path = write_temp_toml(
+        r#"
+        [database]
+        max_connections = 12
+        "#,
+    );
+
+    let config = PoolConfig::from_toml_path(&path).unwrap();
+    let defaults = PoolConfig::default();
+    assert_eq!(config.max_connections, 12);
+    assert_eq!(config.min_connections, defaults.min_connections);
+    assert_eq!(config.busy_timeout, defaults.busy_timeout);
+    assert_eq!(config.wal, defaults.wal);
+
+    let bad = write_temp_toml("[database]\nmax_connections = \"many\"\n");
+    assert!(matches!(
+        PoolConfig::from_toml_path(&bad),
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_pool_config_for_environment() {
+    let development = PoolConfig::for_environment(Environment::Development);
+    let test = PoolConfig::for_environment(Environment::Test);
+    let production = PoolConfig::for_environment(Environment::Production);
+
+    assert_eq!(development.url, PoolConfig::default().url);
+    assert!(development.create_if_missing);
+
+    assert_eq!(test.url, "sqlite::memory:");
+    assert_eq!(test.max_connections, 1);
+    assert!(!test.wal);
+    assert_eq!(test.idle_timeout, None);
+    assert_eq!(test.max_lifetime, None);
+    assert!(development.idle_timeout.is_some());
+
+    assert!(production.url.starts_with("sqlite:///var/lib/"));
+    assert!(production.wal);
+    assert!(!production.create_if_missing);
+    assert!(production.max_connections > development.max_connections);
+
+    // The in-memory database lives as long as the pool's one connection
+    let store = test_store_with(&test).await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    assert!(store.exists("web").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_create_rejects_invalid_input() {
+    let store = test_store().await;
+    let mut config = test_config();
+    config.cpu_limit = Some(0);
+
+    let err = store
+        .create("Bad Name", "alpine", &config)
+        .await
+        .unwrap_err();
+    match err {
+        DbError::Validation(errors) => {
+            let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+            assert_eq!(fields, ["name", "cpu_limit"]);
+        }
+        other => panic!("expected Validation, got {:?}", other),
+    }
+    assert!(store.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_list_page() {
+    let store = test_store().await;
+    for i in 0..5 {
+        store
+            .create(&format!("web-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+
+    let page = store.list_page(2, 2).await.unwrap();
+    assert_eq!(page.items.len(), 2);
+    assert_eq!(page.total, 5);
+    assert_eq!((page.limit, page.offset), (2, 2));
+    assert_eq!(page.items[0].name, "web-2");
+
+    let tail = store.list_page(2, 4).await.unwrap();
+    assert_eq!(tail.items.len(), 1);
+    assert!(matches!(
+        store.list_page(0, 0).await,
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+/// web-0/alpine running, web-1/ubuntu running, db-0/alpine created (env=prod)
+async fn filter_fixture() -> ContainerStore {
+    let store = test_store().await;
+    for (name, template) in [("web-0", "alpine"), ("web-1", "ubuntu"), ("db-0", "alpine")] {
+        store.create(name, template, &test_config()).await.unwrap();
+    }
+    for name in ["web-0", "web-1"] {
+        store
+            .update_status(name, ContainerStatus::Running)
+            .await
+            .unwrap();
+    }
+    store.set_label("db-0", "env", "prod").await.unwrap();
+    store
+}
+
+fn names(page: &Page<Container>) -> Vec<&str> {
+    page.items.iter().map(|c| c.name.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_query_single_filter() {
+    let store = filter_fixture().await;
+
+    let running = store
+        .query(ContainerFilter {
+            status: Some(ContainerStatus::Running),
+            ..ContainerFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&running), vec!["web-0", "web-1"]);
+    assert_eq!(running.total, 2);
+
+    let labelled = store
+        .query(ContainerFilter {
+            label: Some(("env".to_string(), "prod".to_string())),
+            ..ContainerFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&labelled), vec!["db-0"]);
+}
+
+#[tokio::test]
+async fn test_query_status_and_template() {
+    let store = filter_fixture().await;
+
+    let page = store
+        .query(ContainerFilter {
+            status: Some(ContainerStatus::Running),
+            template: Some("alpine".to_string()),
+            ..ContainerFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&page), vec!["web-0"]);
+    assert_eq!(page.total, 1);
+}
+
+#[tokio::test]
+async fn test_query_empty_filter_returns_all() {
+    let store = filter_fixture().await;
+
+    let page = store.query(ContainerFilter::default()).await.unwrap();
+    assert_eq!(names(&page), vec!["web-0", "web-1", "db-0"]);
+    assert_eq!(page.total, 3);
+
+    let second = store
+        .query(ContainerFilter {
+            limit: 2,
+            offset: 2,
+            ..ContainerFilter::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(names(&second), vec!["db-0"]);
+    assert_eq!(second.total, 3);
+}
+
+#[tokio::test]
+async fn test_fleet_totals() {
+    let store = test_store().await;
+    let gib = 1024 * 1024 * 1024;
+
+    let mut small = test_config();
+    small.cpu_limit = Some(1);
+    small.memory_limit = Some(gib);
+    small.disk_limit = Some(DiskLimit::Bytes(10 * gib));
+    let mut large = test_config();
+    large.cpu_limit = Some(4);
+    large.cpu_request = Some(2);
+    large.memory_limit = Some(8 * gib);
+    large.memory_request = Some(4 * gib);
+    large.disk_limit = Some(DiskLimit::Bytes(100 * gib));
+    let mut unset = test_config();
+    unset.cpu_limit = None;
+    unset.memory_limit = None;
+    unset.disk_limit = Some(DiskLimit::Unlimited);
+
+    for (name, config) in [("small", &small), ("large", &large), ("unset", &unset)] {
+        store.create(name, "alpine", config).await.unwrap();
+        store
+            .update_status(name, ContainerStatus::Running)
+            .await
+            .unwrap();
+    }
+    // Stopped containers commit nothing
+    store.create("idle", "alpine", &large).await.unwrap();
+
+    let totals = store.fleet_totals().await.unwrap();
+    assert_eq!(
+        totals,
+        FleetTotals {
+            containers: 3,
+            cpus: 5,
+            cpu_requests: 3,
+            memory_bytes: 9 * gib as i64,
+            memory_request_bytes: 5 * gib as i64,
+            disk_bytes: 110 * gib as i64,
+            unlimited_disks: 1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_plan_categorizes_changes() {
+    let store = test_store().await;
+    let kept = store
+        .create("kept", "alpine", &test_config())
+        .await
+        .unwrap();
+    let changed = store
+        .create("changed", "alpine", &test_config())
+        .await
+        .unwrap();
+    store
+        .create("removed", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let mut changed_desired = changed.clone();
+    changed_desired.config.cpu_limit = Some(4);
+    let mut added = kept.clone();
+    added.id = Uuid::new_v4();
+    added.name = ContainerName::new("added").unwrap();
+
+    let plan = store
+        .plan(&[kept.clone(), changed_desired, added])
+        .await
+        .unwrap();
+    let names = |containers: &[Container]| -> Vec<String> {
+        containers.iter().map(|c| c.name.to_string()).collect()
+    };
+    assert_eq!(names(&plan.to_create), vec!["added"]);
+    assert_eq!(names(&plan.to_update), vec!["changed"]);
+    assert_eq!(names(&plan.to_delete), vec!["removed"]);
+    assert_eq!(plan.to_update[0].config.cpu_limit, Some(4));
+
+    // Planning never writes
+    assert_eq!(store.list().await.unwrap().len(), 3);
+    assert_eq!(
+        store.get_by_name("changed").await.unwrap().config.cpu_limit,
+        Some(2)
+    );
+
+    assert!(matches!(
+        store.plan(&[kept.clone(), kept]).await,
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_apply_plan() {
+    let store = test_store().await;
+    let kept = store
+        .create("kept", "alpine", &test_config())
+        .await
+        .unwrap();
+    let changed = store
+        .create("changed", "alpine", &test_config())
+        .await
+        .unwrap();
+    store
+        .create("removed", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let mut changed_desired = changed.clone();
+    changed_desired.config.cpu_limit = Some(4);
+    changed_desired
+        .labels
+        .insert("env".to_string(), "prod".to_string());
+    let mut added = kept.clone();
+    added.name = ContainerName::new("added").unwrap();
+
+    let plan = store.plan(&[kept, changed_desired, added]).await.unwrap();
+    let report = store.apply(plan).await.unwrap();
+    assert_eq!(
+        report,
+        ApplyReport {
+            created: 1,
+            updated: 1,
+            deleted: 1,
+        }
+    );
+
+    let changed = store.get_by_name("changed").await.unwrap();
+    assert_eq!(changed.config.cpu_limit, Some(4));
+    assert_eq!(changed.labels.get("env").map(String::as_str), Some("prod"));
+    assert!(!store.exists("removed").await.unwrap());
+    assert_eq!(
+        store.list_events("added").await.unwrap()[0].event_type,
+        "apply"
+    );
+    assert_eq!(store.list_events("changed").await.unwrap().len(), 1);
+
+    // Applying a fresh plan for the same state is a no-op
+    let current = store.list().await.unwrap();
+    assert!(store.plan(&current).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_apply_invalid_entry_rolls_back() {
+    let store = test_store().await;
+    let kept = store
+        .create("kept", "alpine", &test_config())
+        .await
+        .unwrap();
+    store
+        .create("removed", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let mut valid = kept.clone();
+    valid.name = ContainerName::new("added").unwrap();
+    let mut invalid_entry = kept.clone();
+    invalid_entry.name = ContainerName::new("broken").unwrap();
+    invalid_entry.config.cpu_limit = Some(0);
+
+    let plan = store.plan(&[kept, valid, invalid_entry]).await.unwrap();
+    assert!(matches!(
+        store.apply(plan).await,
+        Err(DbError::InvalidData(_))
+    ));
+
+    let mut names: Vec<_> = store
+        .list()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["kept", "removed"]);
+
+    // A plan gone stale fails mid-transaction and leaves nothing behind
+    let plan = store.plan(&[]).await.unwrap();
+    store.delete("removed").await.unwrap();
+    assert!(matches!(
+        store.apply(plan).await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+    assert!(store.exists("kept").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_apply_validates_like_create() {
+    let store = test_store().await;
+    let gib = 1024 * 1024 * 1024;
+    TemplateStore::new(store.pool().clone())
+        .register("ubuntu", Some(2 * gib))
+        .await
+        .unwrap();
+    let base = store
+        .create("base", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let mut small_disk = base.clone();
+    small_disk.name = ContainerName::new("small").unwrap();
+    small_disk.template = "ubuntu".to_string();
+    small_disk.config.disk_limit = Some(DiskLimit::Bytes(gib));
+    let mut bad_label = base.clone();
+    bad_label.name = ContainerName::new("labelled").unwrap();
+    bad_label.labels.insert("-bad".to_string(), "x".to_string());
+    let mut long_description = base.clone();
+    long_description.name = ContainerName::new("described").unwrap();
+    long_description.description = Some("x".repeat(100_000));
+
+    for entry in [small_disk, bad_label, long_description] {
+        let name = entry.name.clone();
+        let plan = store.plan(&[base.clone(), entry]).await.unwrap();
+        match store.apply(plan).await {
+            Err(DbError::InvalidData(message)) => {
+                assert!(message.starts_with(&format!("Entry {}", name)))
+            }
+            other => panic!("expected InvalidData for {}, got {:?}", name, other),
+        }
+    }
+    assert_eq!(store.list().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_set_and_remove_labels() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    store.set_label("web", "env", "prod").await.unwrap();
+    store
+        .set_label("web", "app.io/team", "infra")
+        .await
+        .unwrap();
+    store.set_label("web", "env", "staging").await.unwrap();
+    store.remove_label("web", "app.io/team").await.unwrap();
+
+    let labels = store.get_by_name("web").await.unwrap().labels;
+    assert_eq!(labels.len(), 1);
+    assert_eq!(labels.get("env").map(String::as_str), Some("staging"));
+
+    assert!(matches!(
+        store.set_label("web", "bad key", "x").await,
+        Err(DbError::Validation(_))
+    ));
+    assert!(matches!(
+        store.set_label("missing", "env", "prod").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+    assert!(matches!(
+        store.remove_label("missing", "env").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_description() {
+    let store = test_store().await;
+    let request = CreateContainerRequest {
+        name: "web".to_string(),
+        template: Template::try_from("alpine").unwrap(),
+        config: test_config(),
+        description: Some("staging web frontend".to_string()),
+    };
+    store.create_from_request(&request).await.unwrap();
+    assert_eq!(
+        store
+            .get_by_name("web")
+            .await
+            .unwrap()
+            .description
+            .as_deref(),
+        Some("staging web frontend")
+    );
+
+    store
+        .set_description("web", Some("production web"))
+        .await
+        .unwrap();
+    assert_eq!(
+        store
+            .get_by_name("web")
+            .await
+            .unwrap()
+            .description
+            .as_deref(),
+        Some("production web")
+    );
+
+    store.set_description("web", None).await.unwrap();
+    assert_eq!(store.get_by_name("web").await.unwrap().description, None);
+
+    let too_long = "a".repeat(257);
+    assert!(matches!(
+        store.set_description("web", Some(&too_long)).await,
+        Err(DbError::Validation(_))
+    ));
+    let request = CreateContainerRequest {
+        name: "api".to_string(),
+        description: Some(too_long),
+        ..request
+    };
+    assert!(matches!(
+        store.create_from_request(&request).await,
+        Err(DbError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_checkpoint() {
+    let store = test_store().await;
+    for i in 0..10 {
+        store
+            .create(&format!("web-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+    store.checkpoint().await.unwrap();
+
+    let config = PoolConfig {
+        wal: false,
+        ..test_pool_config()
+    };
+    let rollback_store = test_store_with(&config).await;
+    rollback_store.checkpoint().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_optimize_after_deletes() {
+    let store = test_store().await;
+    for i in 0..20 {
+        store
+            .create(&format!("web-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+    for i in 0..20 {
+        store.delete(&format!("web-{}", i)).await.unwrap();
+    }
+
+    store.optimize(false).await.unwrap();
+    store.optimize(true).await.unwrap();
+    assert!(store.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_stream_matches_list() {
+    use futures::TryStreamExt;
+
+    let store = test_store().await;
+    for i in 0..5 {
+        store
+            .create(&format!("web-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+
+    let streamed: Vec<_> = store.stream().try_collect().await.unwrap();
+    let listed = store.list().await.unwrap();
+    let ids =
+        |containers: &[models::Container]| containers.iter().map(|c| c.id).collect::<Vec<_>>();
+    assert_eq!(ids(&streamed), ids(&listed));
+}
+
+#[tokio::test]
+async fn test_list_by_statuses() {
+    let store = test_store().await;
+    for (name, status) in [
+        ("web", ContainerStatus::Running),
+        ("api", ContainerStatus::Starting),
+        ("db", ContainerStatus::Stopped),
+        ("cache", ContainerStatus::Error),
+    ] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+        store.update_status(name, status).await.unwrap();
+    }
+
+    let active = store
+        .list_by_statuses(&[ContainerStatus::Running, ContainerStatus::Starting])
+        .await
+        .unwrap();
+    let mut names: Vec<_> = active.iter().map(|c| c.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, vec!["api", "web"]);
+
+    assert_eq!(
+        store
+            .list_by_status(ContainerStatus::Stopped)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(matches!(
+        store.list_by_statuses(&[]).await,
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+#[cfg(feature = "actix")]
+#[test]
+fn test_db_error_status_codes() {
+    use actix_web::http::StatusCode;
+    use actix_web::ResponseError;
+
+    assert_eq!(
+        DbError::ContainerNotFound("web".to_string()).status_code(),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        DbError::ContainerAlreadyExists("web".to_string()).status_code(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        DbError::InvalidData("bad".to_string()).status_code(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        DbError::Corrupt("bad".to_string()).status_code(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(
+        DbError::Internal("Store is read-only".to_string()).status_code(),
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+    assert_eq!(
+        DbError::Validation(vec![models::ValidationError::new(
+            "name",
+            "NAME_EMPTY",
+            "must not be empty"
+        )])
+        .status_code(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        DbError::IdempotencyConflict("req-1".to_string()).status_code(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        DbError::Conflict("web".to_string()).status_code(),
+        StatusCode::CONFLICT
+    );
+    assert_eq!(
+        DbError::ContainerBusy {
+            name: "web".to_string(),
+            status: ContainerStatus::Frozen,
+        }
+        .status_code(),
+        StatusCode::CONFLICT
+    );
+}
+
+#[tokio::test]
+async fn test_upsert() {
+    let store = test_store().await;
+    let mut container = store.create("web", "alpine", &test_config()).await.unwrap();
+    store.delete("web").await.unwrap();
+
+    store.upsert(&container).await.unwrap();
+    assert_eq!(store.get_by_name("web").await.unwrap().id, container.id);
+
+    container.status = ContainerStatus::Running;
+    container.config.cpu_limit = Some(4);
+    container
+        .labels
+        .insert("env".to_string(), "prod".to_string());
+    store.upsert(&container).await.unwrap();
+
+    let stored = store.get_by_id(&container.id).await.unwrap();
+    assert_eq!(stored.status, ContainerStatus::Running);
+    assert_eq!(stored.config.cpu_limit, Some(4));
+    assert_eq!(stored.labels.get("env").map(String::as_str), Some("prod"));
+    assert_eq!(store.list().await.unwrap().len(), 1);
+
+    container.config.cpu_limit = Some(0);
+    assert!(matches!(
+        store.upsert(&container).await,
+        Err(DbError::Validation(_))
+    ));
+    container.config.cpu_limit = Some(4);
+
+    // Labels, description and the template's minimum disk are checked as in create
+    let mut bad_label = container.clone();
+    bad_label.labels.insert("-bad".to_string(), "x".to_string());
+    let mut long_description = container.clone();
+    long_description.description = Some("x".repeat(100_000));
+    let gib = 1024 * 1024 * 1024;
+    TemplateStore::new(store.pool().clone())
+        .register("ubuntu", Some(2 * gib))
+        .await
+        .unwrap();
+    let mut small_disk = container.clone();
+    small_disk.template = "ubuntu".to_string();
+    small_disk.config.disk_limit = Some(DiskLimit::Bytes(gib));
+    for rejected in [bad_label, long_description, small_disk] {
+        assert!(matches!(
+            store.upsert(&rejected).await,
+            Err(DbError::Validation(_))
+        ));
+    }
+    assert_eq!(
+        store.get_by_id(&container.id).await.unwrap().labels.len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_read_only_view_rejects_writes() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let view = store.read_only_view();
+    assert!(view.is_read_only());
+    assert_eq!(view.get_by_name("web").await.unwrap().name, "web");
+    assert!(matches!(
+        view.update_status("web", ContainerStatus::Running).await,
+        Err(DbError::Internal(_))
+    ));
+    assert!(matches!(
+        view.create("api", "alpine", &test_config()).await,
+        Err(DbError::Internal(_))
+    ));
+    assert!(matches!(
+        view.delete("web").await,
+        Err(DbError::Internal(_))
+    ));
+    assert!(store.exists("web").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_read_only_pool_rejects_writes() {
+    let config = test_pool_config();
+    let store = test_store_with(&config).await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let read_only = PoolConfig {
+        read_only: true,
+        ..config
+    };
+    let pool = create_pool(&read_only).await.unwrap();
+    let reader = ContainerStore::new(pool);
+
+    assert_eq!(reader.list().await.unwrap().len(), 1);
+    assert!(matches!(
+        reader.update_status("web", ContainerStatus::Running).await,
+        Err(DbError::Sqlx(_))
+    ));
+}
+
+/// Insert a row directly, bypassing validation and serialization
+async fn insert_raw(store: &ContainerStore, name: &str, status: &str, config: &str) {
+    let now = crate::store::format_timestamp(&chrono::Utc::now());
+    sqlx::query(
+        "INSERT INTO containers (id, name, status, template, node_id, config, created_at, updated_at)
+         VALUES (?1, ?2, ?3, 'alpine', NULL, ?4, ?5, ?5)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(name)
+    .bind(status)
+    .bind(config)
+    .bind(now)
+    .execute(store.pool())
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_deeply_nested_config_rejected() {
+    let store = test_store().await;
+    let nested = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+    insert_raw(&store, "bomb", "stopped", &nested).await;
+
+    assert!(matches!(
+        store.get_by_name("bomb").await,
+        Err(DbError::Corrupt(_))
+    ));
+
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    let strict = store.clone().with_max_config_depth(1);
+    assert!(matches!(
+        strict.get_by_name("web").await,
+        Err(DbError::Corrupt(_))
+    ));
+    assert!(store.get_by_name("web").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_unknown_status_strict_and_lenient() {
+    let store = test_store().await;
+    let config = serde_json::to_string(&test_config()).unwrap();
+    insert_raw(&store, "bogus", "exploded", &config).await;
+
+    assert!(matches!(
+        store.get_by_name("bogus").await,
+        Err(DbError::Corrupt(_))
+    ));
+
+    let names = vec!["bogus".to_string()];
+    assert!(matches!(
+        store.statuses(&names).await,
+        Err(DbError::Corrupt(_))
+    ));
+    assert!(matches!(
+        store.counts_by_status().await,
+        Err(DbError::Corrupt(_))
+    ));
+    assert!(matches!(
+        store
+            .transition_status("bogus", ContainerStatus::Stopped)
+            .await,
+        Err(DbError::Corrupt(_))
+    ));
+    let mut raised = test_config();
+    raised.cpu_limit = Some(4);
+    assert!(matches!(
+        store.update_config("bogus", &raised).await,
+        Err(DbError::Corrupt(_))
+    ));
+
+    let lenient = store.clone().with_status_decoding(StatusDecoding::Lenient);
+    let container = lenient.get_by_name("bogus").await.unwrap();
+    assert_eq!(container.status, ContainerStatus::Error);
+    lenient.update_config("bogus", &raised).await.unwrap();
+    assert_eq!(
+        lenient.statuses(&names).await.unwrap()["bogus"],
+        ContainerStatus::Error
+    );
+    assert_eq!(
+        lenient.counts_by_status().await.unwrap()[&ContainerStatus::Error],
+        1
+    );
+}
+
+#[test]
+fn test_json_depth_ignores_strings() {
+    use crate::store::json_depth;
+
+    assert_eq!(json_depth("{}"), 1);
+    assert_eq!(json_depth(r#"{"a":[[1],{"b":2}]}"#), 3);
+    assert_eq!(json_depth(r#"{"a":"[[[[\"]]]]"}"#), 1);
+}
+
+#[tokio::test]
+async fn test_reset_clears_placement_and_status() {
+    let store = test_store().await;
+    let created = store.create("web", "alpine", &test_config()).await.unwrap();
+    let node_id = Uuid::new_v4();
+    store.assign_node("web", Some(node_id)).await.unwrap();
+    store
+        .set_desired_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    store.set_error("web", "lxc-start failed").await.unwrap();
+
+    let reset = store.reset("web").await.unwrap();
+    assert_eq!(reset.id, created.id);
+    assert_eq!(reset.name, "web");
+    assert!(reset.config.diff(&created.config).is_empty());
+    assert_eq!(reset.status, ContainerStatus::Stopped);
+    assert_eq!(reset.node_id, None);
+    assert!(store.list_pending_transitions().await.unwrap().is_empty());
+
+    let events = store.list_events("web").await.unwrap();
+    assert_eq!(events[0].event_type, crate::events::EVENT_RESET);
+
+    store.assign_node("web", Some(node_id)).await.unwrap();
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    assert!(matches!(
+        store.reset("web").await,
+        Err(DbError::ContainerBusy {
+            status: ContainerStatus::Running,
+            ..
+        })
+    ));
+    let running = store.get_by_name("web").await.unwrap();
+    assert_eq!(running.status, ContainerStatus::Running);
+    assert_eq!(running.node_id, Some(node_id));
+
+    assert!(matches!(
+        store.reset("missing").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+
+    // An unknown stored status is only reset under lenient decoding
+    let config = serde_json::to_string(&test_config()).unwrap();
+    insert_raw(&store, "bogus", "exploded", &config).await;
+    assert!(matches!(
+        store.reset("bogus").await,
+        Err(DbError::Corrupt(_))
+    ));
+    let lenient = store.clone().with_status_decoding(StatusDecoding::Lenient);
+    assert_eq!(
+        lenient.reset("bogus").await.unwrap().status,
+        ContainerStatus::Stopped
+    );
+}
+
+#[tokio::test]
+async fn test_set_error_records_event() {
+    let store = test_store().await;
+    let created = store.create("web", "alpine", &test_config()).await.unwrap();
+
+    store
+        .set_error("web", "lxc-start exited with status 1")
+        .await
+        .unwrap();
+
+    let container = store.get_by_name("web").await.unwrap();
+    assert_eq!(container.status, ContainerStatus::Error);
+    assert!(container.updated_at > created.updated_at);
+
+    let events = store.list_events("web").await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].container_id, created.id);
+    assert_eq!(events[0].event_type, crate::events::EVENT_ERROR);
+    assert_eq!(
+        events[0].message.as_deref(),
+        Some("lxc-start exited with status 1")
+    );
+
+    assert!(matches!(
+        store.set_error("web", "").await,
+        Err(DbError::Validation(_))
+    ));
+    assert!(matches!(
+        store.set_error("missing", "boom").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+    assert_eq!(store.list_events("web").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_list_by_template() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store.create("db", "debian", &test_config()).await.unwrap();
+    store
+        .create("cache", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let alpine: Vec<String> = store
+        .list_by_template("alpine")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name.into())
+        .collect();
+    assert_eq!(alpine, vec!["web", "cache"]);
+
+    let debian = store.list_by_template("debian").await.unwrap();
+    assert_eq!(debian.len(), 1);
+    assert_eq!(debian[0].name, "db");
+
+    assert!(store.list_by_template("ubuntu").await.unwrap().is_empty());
+    assert!(matches!(
+        store.list_by_template("Alpine Linux").await,
+        Err(DbError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_counts_by_status() {
+    let store = test_store().await;
+    for name in ["a", "b", "c", "d", "e"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    store
+        .update_status("a", ContainerStatus::Running)
+        .await
+        .unwrap();
+    store
+        .update_status("b", ContainerStatus::Running)
+        .await
+        .unwrap();
+    store
+        .update_status("c", ContainerStatus::Frozen)
+        .await
+        .unwrap();
+
+    let counts = store.counts_by_status().await.unwrap();
+    assert_eq!(counts.len(), 3);
+    assert_eq!(counts[&ContainerStatus::Running], 2);
+    assert_eq!(counts[&ContainerStatus::Frozen], 1);
+    assert_eq!(counts[&ContainerStatus::Stopped], 2);
+    assert!(!counts.contains_key(&ContainerStatus::Error));
+}
+
+#[tokio::test]
+async fn test_create_idempotent_retry_returns_same_container() {
+    let store = test_store().await;
+    let first = store
+        .create_idempotent("req-1", "web", "alpine", &test_config())
+        .await
+        .unwrap();
+    let retry = store
+        .create_idempotent("req-1", "web", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    assert_eq!(retry.id, first.id);
+    assert_eq!(store.list().await.unwrap().len(), 1);
+
+    // A new key for an existing name is an ordinary duplicate
+    assert!(matches!(
+        store
+            .create_idempotent("req-2", "web", "alpine", &test_config())
+            .await,
+        Err(DbError::ContainerAlreadyExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_create_idempotent_rejects_changed_payload() {
+    let store = test_store().await;
+    store
+        .create_idempotent("req-1", "web", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let mut changed = test_config();
+    changed.cpu_limit = Some(4);
+    assert!(matches!(
+        store
+            .create_idempotent("req-1", "web", "alpine", &changed)
+            .await,
+        Err(DbError::IdempotencyConflict(_))
+    ));
+    assert!(matches!(
+        store
+            .create_idempotent("req-1", "api", "alpine", &test_config())
+            .await,
+        Err(DbError::IdempotencyConflict(_))
+    ));
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        test_config().cpu_limit
+    );
+}
+
+#[tokio::test]
+async fn test_statuses() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store.create("db", "alpine", &test_config()).await.unwrap();
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    let names = vec!["web".to_string(), "db".to_string(), "missing".to_string()];
+    let statuses = store.statuses(&names).await.unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses["web"], ContainerStatus::Running);
+    assert_eq!(statuses["db"], ContainerStatus::Stopped);
+    assert!(!statuses.contains_key("missing"));
+
+    assert!(store.statuses(&[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_global_name_scope_rejects_same_name() {
+    let store = test_store().await;
+    assert_eq!(store.name_scope(), NameScope::Global);
+
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    assert!(matches!(
+        store.create("web", "ubuntu", &test_config()).await,
+        Err(DbError::ContainerAlreadyExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_per_template_name_scope() {
+    let store = test_store().await;
+    migrations::apply_name_scope(store.pool(), NameScope::PerTemplate)
+        .await
+        .unwrap();
+    let store = store.with_name_scope(NameScope::PerTemplate);
+
+    let alpine = store.create("web", "alpine", &test_config()).await.unwrap();
+    let ubuntu = store.create("web", "ubuntu", &test_config()).await.unwrap();
+    assert_ne!(alpine.id, ubuntu.id);
+    assert!(matches!(
+        store.create("web", "alpine", &test_config()).await,
+        Err(DbError::ContainerAlreadyExists(_))
+    ));
+
+    assert_eq!(
+        store
+            .get_by_name_and_template("web", "ubuntu")
+            .await
+            .unwrap()
+            .id,
+        ubuntu.id
+    );
+    assert!(matches!(
+        store.get_by_name("web").await,
+        Err(DbError::InvalidData(_))
+    ));
+    let existing = store
+        .get_or_create("web", "alpine", test_config())
+        .await
+        .unwrap();
+    assert_eq!(existing.id, alpine.id);
+
+    // Plans match by bare name, which this scope does not make unique
+    assert!(matches!(
+        store.plan(std::slice::from_ref(&alpine)).await,
+        Err(DbError::InvalidData(_))
+    ));
+    assert!(matches!(
+        store.apply(Default::default()).await,
+        Err(DbError::InvalidData(_))
+    ));
+
+    // Writers keyed by name alone refuse a shared name instead of hitting both
+    assert!(matches!(
+        store.update_status("web", ContainerStatus::Running).await,
+        Err(DbError::AmbiguousName(_))
+    ));
+    assert!(matches!(
+        store.set_label("web", "tier", "frontend").await,
+        Err(DbError::AmbiguousName(_))
+    ));
+    assert!(matches!(
+        store.delete("web").await,
+        Err(DbError::AmbiguousName(_))
+    ));
+    for id in [alpine.id, ubuntu.id] {
+        let container = store.get_by_id(&id).await.unwrap();
+        assert_eq!(container.status, ContainerStatus::Stopped);
+        assert!(container.labels.is_empty());
+    }
+
+    // A name used under one template only is still unambiguous
+    store.create("db", "alpine", &test_config()).await.unwrap();
+    store
+        .update_status("db", ContainerStatus::Running)
+        .await
+        .unwrap();
+    store.delete("db").await.unwrap();
+
+    // Going back to global uniqueness is refused while names are shared
+    assert!(matches!(
+        migrations::apply_name_scope(store.pool(), NameScope::Global).await,
+        Err(DbError::MigrationError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_migration_status() {
+    let pool = create_pool(&test_pool_config()).await.unwrap();
+    let latest = migrations::MIGRATIONS.last().unwrap().version;
+
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.current_version, 0);
+    assert!(status.applied.is_empty());
+    assert_eq!(status.pending.len(), migrations::MIGRATIONS.len());
+
+    migrations::run_to(&pool, Some(2)).await.unwrap();
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.current_version, 2);
+    assert_eq!(
+        status.applied.iter().map(|m| m.version).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(status.pending.first(), Some(&3));
+
+    migrations::run(&pool).await.unwrap();
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.current_version, latest);
+    assert!(status.pending.is_empty());
+
+    assert!(matches!(
+        migrations::run_to(&pool, Some(latest + 1)).await,
+        Err(DbError::MigrationError(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_migration_makes_null_disk_limits_unlimited() {
+    let config = test_pool_config();
+    let pool = create_pool(&config).await.unwrap();
+    migrations::run_to(&pool, Some(12)).await.unwrap();
+
+    // Rows as written while disk_limit was a plain Option<u64>
+    let now = "2024-01-01T00:00:00.000000Z";
+    for (name, disk) in [("shared", "null"), ("capped", "10737418240")] {
+        sqlx::query(
+            "INSERT INTO containers (id, name, status, template, config, created_at, updated_at)
+             VALUES (?1, ?2, 'stopped', 'alpine', ?3, ?4, ?4)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(name)
+        .bind(format!(
+            r#"{{"cpu_limit":2,"memory_limit":536870912,"disk_limit":{},"network_interfaces":[],"rootfs_path":"/var/lib/lxc/{}/rootfs","environment":[]}}"#,
+            disk, name
+        ))
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+
+    migrations::run(&pool).await.unwrap();
+    let store = ContainerStore::new(pool);
+    assert_eq!(
+        store.get_by_name("shared").await.unwrap().config.disk_limit,
+        Some(DiskLimit::Unlimited)
+    );
+    assert_eq!(
+        store.get_by_name("capped").await.unwrap().config.disk_limit,
+        Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024))
+    );
+}
+
+#[tokio::test]
+async fn test_migration_checksum_guard() {
+    use migrations::Migration;
+
+    let pool = create_pool(&test_pool_config()).await.unwrap();
+    let original = [Migration {
+        version: 1,
+        description: "create widgets",
+        sql: "CREATE TABLE widgets (id INTEGER PRIMARY KEY)",
+    }];
+    migrations::apply(&pool, &original, None).await.unwrap();
+    migrations::apply(&pool, &original, None).await.unwrap();
+
+    let edited = [
+        Migration {
+            version: 1,
+            description: "create widgets",
+            sql: "CREATE TABLE widgets (id INTEGER PRIMARY KEY, name TEXT)",
+        },
+        Migration {
+            version: 2,
+            description: "index widgets",
+            sql: "CREATE INDEX idx_widgets_id ON widgets(id)",
+        },
+    ];
+    match migrations::apply(&pool, &edited, None).await {
+        Err(DbError::MigrationError(message)) => assert!(message.contains("migration 1")),
+        other => panic!("expected a checksum mismatch, got {:?}", other),
+    }
+
+    // Nothing after the mismatch was applied
+    let status = migrations::status(&pool).await.unwrap();
+    assert_eq!(status.current_version, 1);
+}
+
+/// Writer that collects formatted log output for assertions
+#[derive(Clone, Default)]
+struct LogCapture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl LogCapture {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl std::io::Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_log_sampler_demotes_status_updates() {
+    let store = test_store()
+        .await
+        .with_log_sampler(|operation| operation != "update_status");
+    let capture = LogCapture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    let logs = capture.contents();
+    let line = |message: &str| {
+        logs.lines()
+            .find(|line| line.contains(message))
+            .unwrap_or_else(|| panic!("no {:?} log in {}", message, logs))
+            .to_string()
+    };
+    assert!(line("Created container").contains(" INFO "));
+    let status = line("Updated container status");
+    assert!(status.contains("DEBUG"), "{}", status);
+    assert!(!status.contains(" INFO "), "{}", status);
+}
+
+#[tokio::test]
+async fn test_logs_carry_structured_fields() {
+    let store = test_store().await;
+    let capture = LogCapture::default();
+    let writer = capture.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let request_id = uuid::Uuid::new_v4();
+    store
+        .with_request_id(request_id)
+        .create("web", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    let logs = capture.contents();
+    let line = logs
+        .lines()
+        .find(|line| line.contains("Created container"))
+        .expect("create log");
+    assert!(line.contains("container.name=web"), "{}", line);
+    assert!(
+        line.contains(&format!("request_id={}", request_id)),
+        "{}",
+        line
+    );
+}
+
+#[tokio::test]
+async fn test_list_unassigned() {
+    let store = test_store().await;
+    for name in ["web", "db", "cache"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    store
+        .assign_node("db", Some(uuid::Uuid::new_v4()))
+        .await
+        .unwrap();
+
+    let names: Vec<String> = store
+        .list_unassigned()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name.into())
+        .collect();
+    assert_eq!(names, vec!["web", "cache"]);
+
+    store.assign_node("db", None).await.unwrap();
+    assert_eq!(store.list_unassigned().await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn test_transition_status() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    assert_eq!(
+        store
+            .transition_status("web", ContainerStatus::Starting)
+            .await
+            .unwrap(),
+        ContainerStatus::Stopped
+    );
+    assert!(matches!(
+        store
+            .transition_status("web", ContainerStatus::Frozen)
+            .await,
+        Err(DbError::InvalidTransition {
+            from: ContainerStatus::Starting,
+            to: ContainerStatus::Frozen,
+            ..
+        })
+    ));
+    assert!(matches!(
+        store
+            .transition_status("missing", ContainerStatus::Starting)
+            .await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_racing_transitions_stay_consistent() {
+    let config = PoolConfig {
+        max_connections: 8,
+        ..test_pool_config()
+    };
+    let store = test_store_with(&config).await;
+
+    for round in 0..20 {
+        let name = format!("web-{}", round);
+        store.create(&name, "alpine", &test_config()).await.unwrap();
+        store
+            .update_status(&name, ContainerStatus::Running)
+            .await
+            .unwrap();
+
+        // Stopping and failing race: whichever lands first, Error is reachable
+        // from Stopping but Stopping is not reachable from Error
+        let stop = {
+            let store = store.clone();
+            let name = name.clone();
+            tokio::spawn(async move {
+                store
+                    .transition_status(&name, ContainerStatus::Stopping)
+                    .await
+            })
+        };
+        let fail = {
+            let store = store.clone();
+            let name = name.clone();
+            tokio::spawn(
+                async move { store.transition_status(&name, ContainerStatus::Error).await },
+            )
+        };
+        let stop = stop.await.unwrap();
+        let fail = fail.await.unwrap();
+
+        assert!(fail.is_ok(), "{:?}", fail);
+        match stop {
+            Ok(from) => assert_eq!(from, ContainerStatus::Running),
+            Err(DbError::InvalidTransition { from, .. }) => {
+                assert_eq!(from, ContainerStatus::Error)
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(
+            store.get_by_name(&name).await.unwrap().status,
+            ContainerStatus::Error
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_create_enforces_template_min_disk() {
+    let store = test_store().await;
+    let templates = TemplateStore::new(store.pool().clone());
+    let gib = 1024 * 1024 * 1024;
+    templates.register("ubuntu", Some(2 * gib)).await.unwrap();
+
+    let mut small = test_config();
+    small.disk_limit = Some(DiskLimit::Bytes(gib));
+    match store.create("web", "ubuntu", &small).await {
+        Err(DbError::Validation(errors)) => {
+            assert!(errors
+                .iter()
+                .any(|e| e.message.contains("template minimum")))
+        }
+        other => panic!("expected Validation, got {:?}", other.map(|c| c.name)),
+    }
+    assert!(!store.exists("web").await.unwrap());
+
+    let mut large = test_config();
+    large.disk_limit = Some(DiskLimit::Bytes(4 * gib));
+    store.create("web", "ubuntu", &large).await.unwrap();
+
+    // Unregistered templates only get the global floor
+    store.create("db", "alpine", &small).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_template_registry() {
+    let store = test_store().await;
+    let templates = TemplateStore::new(store.pool().clone());
+
+    templates.register("ubuntu", Some(1 << 31)).await.unwrap();
+    templates.register("alpine", None).await.unwrap();
+    templates.register("ubuntu", Some(1 << 32)).await.unwrap();
+
+    assert_eq!(
+        templates.get("ubuntu").await.unwrap().min_disk_bytes,
+        Some(1 << 32)
+    );
+    let names: Vec<String> = templates
+        .list()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(names, vec!["alpine", "ubuntu"]);
+
+    templates.remove("alpine").await.unwrap();
+    assert!(matches!(
+        templates.get("alpine").await,
+        Err(DbError::TemplateNotFound(_))
+    ));
+    assert!(matches!(
+        templates.register("Ubuntu", None).await,
+        Err(DbError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_validate_create_is_a_dry_run() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    assert_eq!(
+        store
+            .validate_create("api", "alpine", &test_config())
+            .await
+            .unwrap(),
+        Ok(())
+    );
+
+    let mut config = test_config();
+    config.cpu_limit = Some(0);
+    let errors = store
+        .validate_create("web", "alpine", &config)
+        .await
+        .unwrap()
+        .unwrap_err();
+    let codes: Vec<&str> = errors.iter().map(|e| e.code.as_ref()).collect();
+    assert!(codes.contains(&"NAME_TAKEN"), "{:?}", codes);
+    assert!(codes.contains(&"CPU_OUT_OF_RANGE"), "{:?}", codes);
+
+    // Nothing was written, even through a read-only view
+    let errors = store
+        .read_only_view()
+        .validate_create("api", "alpine", &config)
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(store.list().await.unwrap().len(), 1);
+    assert!(!store.exists("api").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_memory_disk_policy_applies_to_writes() {
+    let store = test_store().await;
+    let mut config = test_config();
+    config.memory_limit = Some(2 * 1024 * 1024 * 1024);
+    config.disk_limit = Some(DiskLimit::Bytes(1024 * 1024 * 1024));
+
+    match store.create("web", "alpine", &config).await {
+        Err(DbError::Validation(errors)) => assert_eq!(errors[0].code, "DISK_BELOW_MEMORY"),
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+
+    let store = store.with_memory_disk_policy(MemoryDiskPolicy::Warn);
+    store.create("web", "alpine", &config).await.unwrap();
+    store.create("api", "alpine", &test_config()).await.unwrap();
+    store.update_config("api", &config).await.unwrap();
+
+    let templates = TemplateStore::new(store.pool().clone());
+    templates.register("alpine", None).await.unwrap();
+    let specs = [("db".to_string(), "alpine".to_string(), config)];
+    store.create_many(&templates, &specs).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_drain_node() {
+    let store = test_store().await;
+    let node = uuid::Uuid::new_v4();
+    let other = uuid::Uuid::new_v4();
+    for name in ["a", "b", "c", "d"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    for name in ["a", "b", "c"] {
+        store.assign_node(name, Some(node)).await.unwrap();
+    }
+    store.assign_node("d", Some(other)).await.unwrap();
+    store
+        .update_status("a", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    assert_eq!(store.drain_node(&node).await.unwrap(), 3);
+
+    assert!(store.list_by_node(&node).await.unwrap().is_empty());
+    assert_eq!(store.list_unassigned().await.unwrap().len(), 3);
+    assert_eq!(store.list_by_node(&other).await.unwrap().len(), 1);
+    assert_eq!(
+        store.get_by_name("a").await.unwrap().status,
+        ContainerStatus::Running
+    );
+
+    let events = store.list_events("b").await.unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, crate::events::EVENT_DRAIN);
+
+    assert_eq!(store.drain_node(&node).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_list_where_config() {
+    let store = test_store().await;
+    let gib: u64 = 1024 * 1024 * 1024;
+    for (name, cpus, memory) in [
+        ("small", 1, gib / 2),
+        ("medium", 2, 2 * gib),
+        ("large", 4, 8 * gib),
+    ] {
+        let mut config = test_config();
+        config.cpu_limit = Some(cpus);
+        config.memory_limit = Some(memory);
+        store.create(name, "alpine", &config).await.unwrap();
+    }
+    let mut unlimited = test_config();
+    unlimited.memory_limit = None;
+    store
+        .create("unlimited", "alpine", &unlimited)
+        .await
+        .unwrap();
+
+    let names = |containers: Vec<models::Container>| -> Vec<String> {
+        containers.into_iter().map(|c| c.name.into()).collect()
+    };
+
+    let big = store
+        .list_where_config(ConfigField::MemoryLimit, CmpOp::Gt, gib as i64)
+        .await
+        .unwrap();
+    assert_eq!(names(big), vec!["medium", "large"]);
+
+    let dual = store
+        .list_where_config(ConfigField::CpuLimit, CmpOp::Eq, 2)
+        .await
+        .unwrap();
+    assert_eq!(names(dual), vec!["medium", "unlimited"]);
+
+    // An "unlimited" disk is text, not a size larger than every number
+    let mut shared = test_config();
+    shared.disk_limit = Some(DiskLimit::Unlimited);
+    store.create("shared", "alpine", &shared).await.unwrap();
+    for op in [CmpOp::Gt, CmpOp::Ne, CmpOp::Lt] {
+        let matched = store
+            .list_where_config(ConfigField::DiskLimit, op, gib as i64)
+            .await
+            .unwrap();
+        assert!(!names(matched).contains(&"shared".to_string()));
+    }
+}
+
+#[tokio::test]
+async fn test_reconcile_statuses() {
+    let store = test_store().await;
+    let node = uuid::Uuid::new_v4();
+    for name in ["web", "db", "cache"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+        store.assign_node(name, Some(node)).await.unwrap();
+    }
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    let observed = vec![
+        ("web".to_string(), ContainerStatus::Running),
+        ("db".to_string(), ContainerStatus::Running),
+        ("cache".to_string(), ContainerStatus::Error),
+        ("ghost".to_string(), ContainerStatus::Running),
+    ];
+    let report = store.reconcile_statuses(&node, &observed).await.unwrap();
+
+    assert_eq!(report.unknown, ["ghost"]);
+    assert_eq!(
+        report.changes,
+        vec![
+            crate::StatusChange {
+                name: "db".to_string(),
+                from: ContainerStatus::Stopped,
+                to: ContainerStatus::Running,
+            },
+            crate::StatusChange {
+                name: "cache".to_string(),
+                from: ContainerStatus::Stopped,
+                to: ContainerStatus::Error,
+            },
+        ]
+    );
+    assert_eq!(
+        store.get_by_name("db").await.unwrap().status,
+        ContainerStatus::Running
+    );
+    assert_eq!(store.list_events("db").await.unwrap().len(), 1);
+    assert!(store.list_events("web").await.unwrap().is_empty());
+    assert!(!store.exists("ghost").await.unwrap());
+
+    // A second pass finds nothing left to fix
+    let report = store.reconcile_statuses(&node, &observed).await.unwrap();
+    assert!(report.changes.is_empty());
+    assert_eq!(report.unknown, ["ghost"]);
+}
+
+#[tokio::test]
+async fn test_try_get_by_name() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    assert_eq!(
+        store.try_get_by_name("web").await.unwrap().map(|c| c.name),
+        Some(ContainerName::new("web").unwrap())
+    );
+    assert!(store.try_get_by_name("missing").await.unwrap().is_none());
+
+    store.pool().close().await;
+    assert!(matches!(
+        store.try_get_by_name("web").await,
+        Err(DbError::Sqlx(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_versioned_config_revert() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let mut second = test_config();
+    second.cpu_limit = Some(4);
+    let mut third = test_config();
+    third.cpu_limit = Some(8);
+    store.update_config_versioned("web", &second).await.unwrap();
+    store.update_config_versioned("web", &third).await.unwrap();
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        Some(8)
+    );
+
+    // Reverting would change the CPU limit, which a running container refuses
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    assert!(matches!(
+        store.revert_config("web").await,
+        Err(DbError::ContainerBusy { .. })
+    ));
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        Some(8)
+    );
+    store
+        .update_status("web", ContainerStatus::Stopped)
+        .await
+        .unwrap();
+
+    let restored = store.revert_config("web").await.unwrap();
+    assert_eq!(restored.cpu_limit, Some(4));
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        Some(4)
+    );
+
+    store.revert_config("web").await.unwrap();
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        test_config().cpu_limit
+    );
+    assert!(matches!(
+        store.revert_config("web").await,
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_config_history_is_capped() {
+    let store = test_store().await.with_config_history_depth(2);
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    for cpus in 3..=6 {
+        let mut config = test_config();
+        config.cpu_limit = Some(cpus);
+        store.update_config_versioned("web", &config).await.unwrap();
+    }
+
+    let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM config_history")
+        .fetch_one(store.pool())
+        .await
+        .unwrap();
+    assert_eq!(kept, 2);
+    assert_eq!(store.revert_config("web").await.unwrap().cpu_limit, Some(5));
+    assert_eq!(store.revert_config("web").await.unwrap().cpu_limit, Some(4));
+    assert!(store.revert_config("web").await.is_err());
+}
+
+#[tokio::test]
+async fn test_validate_batch_flags_invalid_indices() {
+    let mut specs: Vec<(String, String, ContainerConfig)> = (0..100)
+        .map(|i| (format!("web-{}", i), "alpine".to_string(), test_config()))
+        .collect();
+    specs[3].0 = "Bad Name".to_string();
+    specs[42].2.cpu_limit = Some(0);
+    specs[99].1 = String::new();
+
+    let failures = crate::validate_batch(&specs).await;
+    let indices: Vec<usize> = failures.iter().map(|(index, _)| *index).collect();
+    assert_eq!(indices, vec![3, 42, 99]);
+    assert_eq!(failures[1].1[0].code, "CPU_OUT_OF_RANGE");
+
+    assert!(crate::validate_batch(&[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_create_many_rejects_unregistered_template() {
+    let store = test_store().await;
+    let templates = TemplateStore::new(store.pool().clone());
+    templates.register("alpine", None).await.unwrap();
+
+    let mut specs: Vec<(String, String, ContainerConfig)> = (0..3)
+        .map(|i| (format!("web-{}", i), "alpine".to_string(), test_config()))
+        .collect();
+    specs[1].1 = "ubuntu".to_string();
+
+    match store.create_many(&templates, &specs).await {
+        Err(DbError::InvalidData(message)) => {
+            assert!(message.contains("web-1"));
+            assert!(message.contains("ubuntu"));
+            assert!(!message.contains("web-0"));
+        }
+        other => panic!("expected InvalidData, got {:?}", other.map(|c| c.len())),
+    }
+    assert!(store.list().await.unwrap().is_empty());
+
+    // Registered, but web-1 is below its disk minimum
+    templates
+        .register("ubuntu", Some(20 * 1024 * 1024 * 1024))
+        .await
+        .unwrap();
+    match store.create_many(&templates, &specs).await {
+        Err(DbError::InvalidData(message)) => {
+            assert!(message.contains("web-1"));
+            assert!(!message.contains("not registered"));
+        }
+        other => panic!("expected InvalidData, got {:?}", other.map(|c| c.len())),
+    }
+
+    templates.register("ubuntu", None).await.unwrap();
+    let created = store.create_many(&templates, &specs).await.unwrap();
+    assert_eq!(created.len(), 3);
+    assert_eq!(store.get_by_name("web-1").await.unwrap().template, "ubuntu");
+}
+
+#[tokio::test]
+async fn test_swap_names() {
+    let store = test_store().await;
+    let blue = store
+        .create("blue", "alpine", &test_config())
+        .await
+        .unwrap();
+    let green = store
+        .create("green", "alpine", &test_config())
+        .await
+        .unwrap();
+
+    store.swap_names("blue", "green").await.unwrap();
+
+    assert_eq!(store.get_by_name("blue").await.unwrap().id, green.id);
+    assert_eq!(store.get_by_name("green").await.unwrap().id, blue.id);
+    assert_eq!(store.list().await.unwrap().len(), 2);
+
+    assert!(matches!(
+        store.swap_names("blue", "missing").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+    assert_eq!(store.get_by_name("blue").await.unwrap().id, green.id);
+}
+
+#[tokio::test]
+async fn test_get_or_create_on_node() {
+    let store = test_store().await;
+    let node = uuid::Uuid::new_v4();
+
+    let created = store
+        .get_or_create_on_node("web", "alpine", test_config(), node)
+        .await
+        .unwrap();
+    assert_eq!(created.node_id, Some(node));
+    assert_eq!(store.get_by_name("web").await.unwrap().node_id, Some(node));
+
+    // An existing container keeps its placement
+    let again = store
+        .get_or_create_on_node("web", "alpine", test_config(), uuid::Uuid::new_v4())
+        .await
+        .unwrap();
+    assert_eq!(again.id, created.id);
+    assert_eq!(again.node_id, Some(node));
+}
+
+#[tokio::test]
+async fn test_check_integrity_flags_corrupt_rows() {
+    use crate::IssueKind;
+
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    insert_raw(&store, "broken-config", "stopped", "{not json").await;
+    let config = serde_json::to_string(&test_config()).unwrap();
+    insert_raw(&store, "bad-status", "exploded", &config).await;
+    let node = uuid::Uuid::new_v4();
+    store.assign_node("web", Some(node)).await.unwrap();
+
+    let issues = store.check_integrity().await.unwrap();
+    let found: Vec<(&str, IssueKind)> = issues
+        .iter()
+        .map(|issue| (issue.name.as_str(), issue.kind))
+        .collect();
+    assert!(found.contains(&("broken-config", IssueKind::Undecodable)));
+    assert!(found.contains(&("bad-status", IssueKind::InvalidStatus)));
+    assert!(!found.contains(&("bad-status", IssueKind::Undecodable)));
+    assert!(!found.iter().any(|(name, _)| *name == "web"));
+
+    let issues = store
+        .check_integrity_against(&std::collections::HashSet::new())
+        .await
+        .unwrap();
+    assert!(issues
+        .iter()
+        .any(|issue| issue.name == "web" && issue.kind == IssueKind::OrphanedNode));
+}
+
+#[tokio::test]
+async fn test_config_json_round_trips_unknown_fields() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let mut value = serde_json::to_value(test_config()).unwrap();
+    value["future_field"] = serde_json::json!({"enabled": true});
+    let json = serde_json::to_string(&value).unwrap();
+
+    store.set_config_json("web", &json, false).await.unwrap();
+    assert_eq!(store.get_config_json("web").await.unwrap(), json);
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.cpu_limit,
+        Some(2)
+    );
+
+    let mut bad = value.clone();
+    bad["cpu_limit"] = serde_json::json!(0);
+    let bad = serde_json::to_string(&bad).unwrap();
+    assert!(matches!(
+        store.set_config_json("web", &bad, false).await,
+        Err(DbError::Validation(_))
+    ));
+    store.set_config_json("web", &bad, true).await.unwrap();
+    assert_eq!(store.get_config_json("web").await.unwrap(), bad);
+
+    assert!(store
+        .set_config_json("web", "not json", true)
+        .await
+        .is_err());
+    assert!(matches!(
+        store.get_config_json("missing").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_patch_config_merges_environment() {
+    let store = test_store().await;
+    let mut config = test_config();
+    config.environment = vec![
+        ("HOME".to_string(), "/root".to_string()),
+        ("LOG".to_string(), "info".to_string()),
+    ];
+    store.create("web", "alpine", &config).await.unwrap();
+
+    let patch = models::ConfigPatch {
+        cpu_limit: Some(4),
+        environment: Some(vec![
+            ("LOG".to_string(), "debug".to_string()),
+            ("PORT".to_string(), "8080".to_string()),
+        ]),
+        ..Default::default()
+    };
+    let patched = store.patch_config("web", &patch).await.unwrap();
+    assert_eq!(patched.cpu_limit, Some(4));
+
+    let stored = store.get_by_name("web").await.unwrap().config;
+    assert_eq!(stored.memory_limit, config.memory_limit);
+    assert_eq!(
+        stored.environment,
+        vec![
+            ("HOME".to_string(), "/root".to_string()),
+            ("LOG".to_string(), "debug".to_string()),
+            ("PORT".to_string(), "8080".to_string()),
+        ]
+    );
+
+    let bad = models::ConfigPatch {
+        environment: Some(vec![("1BAD".to_string(), "x".to_string())]),
+        ..Default::default()
+    };
+    assert!(matches!(
+        store.patch_config("web", &bad).await,
+        Err(DbError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_label_selectors() {
+    let store = test_store().await;
+    for (name, env, team) in [
+        ("web", "prod", "infra"),
+        ("api", "prod", "app"),
+        ("db", "staging", "infra"),
+    ] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+        store.set_label(name, "env", env).await.unwrap();
+        store.set_label(name, "team", team).await.unwrap();
+    }
+    let selector = |entries: &[(&str, &str)]| -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let names = |containers: Vec<models::Container>| -> Vec<String> {
+        containers.into_iter().map(|c| c.name.into()).collect()
+    };
+
+    let prod = selector(&[("env", "prod")]);
+    assert_eq!(store.count_by_selector(&prod).await.unwrap(), 2);
+    assert_eq!(
+        names(store.list_by_selector(&prod).await.unwrap()),
+        vec!["web", "api"]
+    );
+
+    let prod_infra = selector(&[("env", "prod"), ("team", "infra")]);
+    assert_eq!(store.count_by_selector(&prod_infra).await.unwrap(), 1);
+    assert_eq!(
+        names(store.list_by_selector(&prod_infra).await.unwrap()),
+        vec!["web"]
+    );
+
+    let none = selector(&[("env", "dev")]);
+    assert_eq!(store.count_by_selector(&none).await.unwrap(), 0);
+    assert!(store.list_by_selector(&none).await.unwrap().is_empty());
+
+    assert_eq!(store.count_by_selector(&[]).await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_updated_at_monotonic_under_clock_skew() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    // Another node with a clock an hour ahead wrote the row last
+    let ahead = chrono::Utc::now() + chrono::Duration::hours(1);
+    sqlx::query("UPDATE containers SET updated_at = ?1 WHERE name = ?2")
+        .bind(crate::store::format_timestamp(&ahead))
+        .bind("web")
+        .execute(store.pool())
+        .await
+        .unwrap();
+    let stored = store.get_by_name("web").await.unwrap().updated_at;
+
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    let first = store.get_by_name("web").await.unwrap().updated_at;
+    assert_eq!(first, stored + chrono::Duration::microseconds(1));
+
+    store.set_label("web", "env", "prod").await.unwrap();
+    store.touch("web").await.unwrap();
+    let second = store.get_by_name("web").await.unwrap().updated_at;
+    assert!(second > first);
+
+    let claimed = store
+        .claim_next_unassigned(Uuid::new_v4())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(claimed.updated_at > second);
+}
+
+#[tokio::test]
+async fn test_updated_at_monotonic_under_concurrent_writers() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    let ahead = chrono::Utc::now() + chrono::Duration::hours(1);
+    sqlx::query("UPDATE containers SET updated_at = ?1 WHERE name = ?2")
+        .bind(crate::store::format_timestamp(&ahead))
+        .bind("web")
+        .execute(store.pool())
+        .await
+        .unwrap();
+    let stored = store.get_by_name("web").await.unwrap().updated_at;
+
+    // Each write lands 1µs past the last; a lost read would repeat a value
+    let writes = 12;
+    let results = futures::future::join_all((0..writes).map(|i| {
+        let store = store.clone();
+        async move {
+            match i % 4 {
+                0 => store.touch("web").await,
+                1 => store.set_label("web", "n", &i.to_string()).await,
+                2 => store.set_description("web", Some("busy")).await,
+                _ => {
+                    store
+                        .set_desired_status("web", ContainerStatus::Running)
+                        .await
+                }
+            }
+        }
+    }))
+    .await;
+    assert!(results.iter().all(Result::is_ok), "{:?}", results);
+
+    let last = store.get_by_name("web").await.unwrap().updated_at;
+    assert_eq!(last, stored + chrono::Duration::microseconds(writes));
+}
+
+#[tokio::test]
+async fn test_get_with_events() {
+    let store = test_store().await;
+    let created = store.create("web", "alpine", &test_config()).await.unwrap();
+    for reason in ["first", "second", "third"] {
+        store.set_error("web", reason).await.unwrap();
+    }
+
+    let (container, events) = store.get_with_events("web", 2).await.unwrap();
+    assert_eq!(container.id, created.id);
+    assert_eq!(container.status, ContainerStatus::Error);
+    assert_eq!(
+        events
+            .iter()
+            .map(|e| e.message.as_deref().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["third", "second"]
+    );
+    assert!(events.iter().all(|e| e.container_id == created.id));
+
+    assert!(matches!(
+        store.get_with_events("missing", 2).await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_existing_names() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store.create("db", "alpine", &test_config()).await.unwrap();
+
+    let names: Vec<String> = ["web", "api", "db", "cache"]
+        .iter()
+        .map(|n| n.to_string())
+        .collect();
+    let existing = store.existing_names(&names).await.unwrap();
+    assert_eq!(
+        existing,
+        ["web", "db"].iter().map(|n| n.to_string()).collect()
+    );
+
+    assert!(store.existing_names(&[]).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_claim_next_unassigned_concurrent() {
+    let config = PoolConfig {
+        max_connections: 8,
+        ..test_pool_config()
+    };
+    let store = test_store_with(&config).await;
+    for i in 0..5 {
+        store
+            .create(&format!("ct-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                let node_id = Uuid::new_v4();
+                let mut claimed = Vec::new();
+                while let Some(container) = store.claim_next_unassigned(node_id).await.unwrap() {
+                    assert_eq!(container.node_id, Some(node_id));
+                    claimed.push(container.name);
+                }
+                claimed
+            })
+        })
+        .collect();
+
+    let mut claimed = Vec::new();
+    for handle in handles {
+        claimed.extend(handle.await.unwrap());
+    }
+    claimed.sort();
+    assert_eq!(claimed, vec!["ct-0", "ct-1", "ct-2", "ct-3", "ct-4"]);
+
+    assert!(store.list_unassigned().await.unwrap().is_empty());
+    assert!(store
+        .claim_next_unassigned(Uuid::new_v4())
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_distribute_unassigned_round_robin() {
+    let store = test_store().await;
+    for i in 0..5 {
+        store
+            .create(&format!("ct-{}", i), "alpine", &test_config())
+            .await
+            .unwrap();
+    }
+    let nodes = [Uuid::new_v4(), Uuid::new_v4()];
+
+    let placements = store.distribute_unassigned(&nodes).await.unwrap();
+    assert_eq!(placements.len(), 5);
+    assert_eq!(store.count_by_node(&nodes[0]).await.unwrap(), 3);
+    assert_eq!(store.count_by_node(&nodes[1]).await.unwrap(), 2);
+    assert!(store.list_unassigned().await.unwrap().is_empty());
+    for (container_id, node_id) in &placements {
+        let container = store.get_by_id(container_id).await.unwrap();
+        assert_eq!(container.node_id, Some(*node_id));
+    }
+
+    assert!(matches!(
+        store.distribute_unassigned(&[]).await,
+        Err(DbError::InvalidData(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_get_or_create_deterministic_id() {
+    let store = test_store().await;
+
+    let first = store
+        .get_or_create_deterministic("web", "alpine", test_config())
+        .await
+        .unwrap();
+    assert_eq!(first.id, store.deterministic_id("web", "alpine"));
+
+    store.delete("web").await.unwrap();
+    let second = store
+        .get_or_create_deterministic("web", "alpine", test_config())
+        .await
+        .unwrap();
+    assert_eq!(second.id, first.id);
+
+    let other = store
+        .clone()
+        .with_id_namespace(Uuid::new_v4())
+        .deterministic_id("web", "alpine");
+    assert_ne!(other, first.id);
+}
+
+#[tokio::test]
+async fn test_sync_is_rerunnable() {
+    let source = test_store().await;
+    for name in ["web", "api", "db"] {
+        source.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    source.set_label("web", "env", "prod").await.unwrap();
+    let mut containers = source.list().await.unwrap();
+
+    let store = test_store().await;
+    let report = store.sync(&containers).await.unwrap();
+    assert_eq!(
+        (report.created, report.updated, report.unchanged),
+        (3, 0, 0)
+    );
+
+    let report = store.sync(&containers).await.unwrap();
+    assert_eq!(
+        (report.created, report.updated, report.unchanged),
+        (0, 0, 3)
+    );
+
+    containers[1].config.cpu_limit = Some(8);
+    let report = store.sync(&containers).await.unwrap();
+    assert_eq!(
+        (report.created, report.updated, report.unchanged),
+        (0, 1, 2)
+    );
+    assert_eq!(
+        store
+            .get_by_name(&containers[1].name)
+            .await
+            .unwrap()
+            .config
+            .cpu_limit,
+        Some(8)
+    );
+
+    containers[2].config.cpu_limit = Some(0);
+    containers[0].config.cpu_limit = Some(4);
+    match store.sync(&containers).await {
+        Err(DbError::InvalidData(message)) => assert!(message.starts_with("Entry 2")),
+        other => panic!("expected invalid entry, got {:?}", other),
+    }
+    assert_eq!(
+        store
+            .get_by_name(&containers[0].name)
+            .await
+            .unwrap()
+            .config
+            .cpu_limit,
+        Some(2)
+    );
+}
+
+#[tokio::test]
+async fn test_limit_changes_rejected_while_active() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    let mut config = test_config();
+    config.memory_limit = Some(1024 * 1024 * 1024);
+
+    store.update_config("web", &config).await.unwrap();
+
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    config.memory_limit = Some(2 * 1024 * 1024 * 1024);
+    assert!(matches!(
+        store.update_config("web", &config).await,
+        Err(DbError::ContainerBusy {
+            status: ContainerStatus::Running,
+            ..
+        })
+    ));
+    let patch = models::ConfigPatch {
+        cpu_limit: Some(8),
+        ..Default::default()
+    };
+    assert!(matches!(
+        store.patch_config("web", &patch).await,
+        Err(DbError::ContainerBusy { .. })
+    ));
+
+    // Changes that leave the limits alone still go through
+    let mut env_only = test_config();
+    env_only.memory_limit = Some(1024 * 1024 * 1024);
+    env_only
+        .environment
+        .push(("LOG".to_string(), "debug".to_string()));
+    store.update_config("web", &env_only).await.unwrap();
+    store
+        .set_description("web", Some("frontend"))
+        .await
+        .unwrap();
+    store.set_label("web", "env", "prod").await.unwrap();
+    assert_eq!(
+        store.get_by_name("web").await.unwrap().config.memory_limit,
+        Some(1024 * 1024 * 1024)
+    );
+}
+
+#[tokio::test]
+async fn test_find_duplicates() {
+    let store = test_store().await;
+    let mut reordered = test_config();
+    reordered
+        .environment
+        .insert(0, ("LOG".to_string(), "info".to_string()));
+    let mut original = test_config();
+    original
+        .environment
+        .push(("LOG".to_string(), "info".to_string()));
+
+    store.create("web-1", "alpine", &original).await.unwrap();
+    store.create("api", "alpine", &test_config()).await.unwrap();
+    store.create("web-2", "alpine", &reordered).await.unwrap();
+
+    let groups = store.find_duplicates().await.unwrap();
+    assert_eq!(groups.len(), 1);
+    assert_eq!(
+        groups[0]
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["web-1", "web-2"]
+    );
+
+    // Groups come out in the creation order of their first member
+    store
+        .create("db-1", "alpine", &test_config())
+        .await
+        .unwrap();
+    let groups = store.find_duplicates().await.unwrap();
+    let firsts: Vec<_> = groups.iter().map(|g| g[0].name.as_str()).collect();
+    assert_eq!(firsts, vec!["web-1", "api"]);
+    assert_eq!(groups[1].len(), 2);
+}
+
+#[tokio::test]
+async fn test_prune_events() {
+    let store = test_store().await;
+    for name in ["web", "db"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+        for i in 0..5 {
+            store
+                .set_error(name, &format!("failure {}", i))
+                .await
+                .unwrap();
+        }
+    }
+    let web = store.get_by_name("web").await.unwrap();
+    let old = chrono::Utc::now() - chrono::Duration::days(30);
+    for i in 0..3 {
+        sqlx::query(
+            "INSERT INTO container_events (container_id, event_type, message, created_at)
+             VALUES (?1, 'error', ?2, ?3)",
+        )
+        .bind(web.id.to_string())
+        .bind(format!("ancient {}", i))
+        .bind(crate::store::format_timestamp(&old))
+        .execute(store.pool())
+        .await
+        .unwrap();
+    }
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(7);
+    assert_eq!(store.prune_events(cutoff).await.unwrap(), 3);
+    assert_eq!(store.list_events("web").await.unwrap().len(), 5);
+
+    assert_eq!(store.prune_events_keep_last(2).await.unwrap(), 6);
+    for name in ["web", "db"] {
+        let messages: Vec<String> = store
+            .list_events(name)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.message.unwrap())
+            .collect();
+        assert_eq!(messages, vec!["failure 4", "failure 3"]);
+    }
+}
+
+#[tokio::test]
+async fn test_archive_and_restore() {
+    let store = test_store().await;
+    let mut config = test_config();
+    config.hostname = Some("web.internal".to_string());
+    let web = store.create("web", "alpine", &config).await.unwrap();
+    store.set_label("web", "env", "prod").await.unwrap();
+
+    let data = store.archive("web").await.unwrap();
+    let archived: serde_json::Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(archived["name"], "web");
+    assert!(matches!(
+        store.get_by_name("web").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+    assert!(store.list().await.unwrap().is_empty());
+
+    let restored = store.restore_archived(&web.id).await.unwrap();
+    assert_eq!(restored.id, web.id);
+    let stored = store.get_by_name("web").await.unwrap();
+    assert_eq!(
+        serde_json::to_value(&stored.config).unwrap(),
+        serde_json::to_value(config.normalized()).unwrap()
+    );
+    assert_eq!(stored.labels.get("env").map(String::as_str), Some("prod"));
+
+    assert!(matches!(
+        store.restore_archived(&web.id).await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_archive_restore_refuses_taken_name() {
+    let store = test_store().await;
+    let web = store.create("web", "alpine", &test_config()).await.unwrap();
+    store.archive("web").await.unwrap();
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    assert!(matches!(
+        store.restore_archived(&web.id).await,
+        Err(DbError::ContainerAlreadyExists(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_archive_restore_revalidates() {
+    let store = test_store().await;
+    let web = store.create("web", "alpine", &test_config()).await.unwrap();
+    let data = store.archive("web").await.unwrap();
+
+    // An archive edited, or written under older rules, is checked like create
+    let mut archived: serde_json::Value = serde_json::from_str(&data).unwrap();
+    archived["name"] = "not a name!".into();
+    archived["config"]["cpu_limit"] = 0.into();
+    sqlx::query("UPDATE archived_containers SET data = ?1 WHERE id = ?2")
+        .bind(archived.to_string())
+        .bind(web.id.to_string())
+        .execute(store.pool())
+        .await
+        .unwrap();
+
+    match store.restore_archived(&web.id).await {
+        Err(DbError::Validation(errors)) => {
+            let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+            assert!(fields.contains(&"name"));
+            assert!(fields.contains(&"cpu_limit"));
+        }
+        other => panic!("expected Validation, got {:?}", other.map(|c| c.name)),
+    }
+    assert!(store.list().await.unwrap().is_empty());
+    let kept: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM archived_containers")
+        .fetch_one(store.pool())
+        .await
+        .unwrap();
+    assert_eq!(kept, 1);
+}
+
+#[tokio::test]
+async fn test_lookup_by_container_name() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+
+    let name = ContainerName::new("Web").unwrap();
+    assert!(store.exists(&name).await.unwrap());
+    assert_eq!(store.get_by_name(&name).await.unwrap().name, "web");
+    store.delete(name).await.unwrap();
+    assert!(!store.exists("web").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_delete_removes_dependent_rows() {
+    let store = test_store().await;
+    let web = store.create("web", "alpine", &test_config()).await.unwrap();
+    store.create("db", "alpine", &test_config()).await.unwrap();
+    for name in ["web", "db"] {
+        store.set_error(name, "disk full").await.unwrap();
+        let mut config = test_config();
+        config.cpu_limit = Some(4);
+        store.update_config_versioned(name, &config).await.unwrap();
+    }
+
+    store.delete("web").await.unwrap();
+
+    for table in ["container_events", "config_history"] {
+        let count: i64 = sqlx::query_scalar(&format!(
+            "SELECT COUNT(*) FROM {} WHERE container_id = ?1",
+            table
+        ))
+        .bind(web.id.to_string())
+        .fetch_one(store.pool())
+        .await
+        .unwrap();
+        assert_eq!(count, 0, "{} rows left behind", table);
+    }
+    assert_eq!(store.list_events("db").await.unwrap().len(), 1);
+    assert!(matches!(
+        store.delete("web").await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_foreign_keys_enforced() {
+    let insert_orphan = |pool: sqlx::SqlitePool| async move {
+        sqlx::query(
+            "INSERT INTO container_events (container_id, event_type, created_at)
+             VALUES (?1, 'error', ?2)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(crate::store::format_timestamp(&chrono::Utc::now()))
+        .execute(&pool)
+        .await
+    };
+
+    let store = test_store().await;
+    let err = insert_orphan(store.pool().clone()).await.unwrap_err();
+    assert!(err.to_string().contains("FOREIGN KEY"), "{}", err);
+
+    let unenforced = test_store_with(&PoolConfig {
+        foreign_keys: false,
+        ..test_pool_config()
+    })
+    .await;
+    insert_orphan(unenforced.pool().clone()).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_distinct_templates() {
+    let store = test_store().await;
+    assert!(store.distinct_templates().await.unwrap().is_empty());
+
+    for (name, template) in [("web", "ubuntu"), ("api", "alpine"), ("db", "ubuntu")] {
+        store.create(name, template, &test_config()).await.unwrap();
+    }
+    assert_eq!(
+        store.distinct_templates().await.unwrap(),
+        vec!["alpine", "ubuntu"]
+    );
+}
+
+#[tokio::test]
+async fn test_desired_status_pending_until_reconciled() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store.create("db", "alpine", &test_config()).await.unwrap();
+    assert!(store.list_pending_transitions().await.unwrap().is_empty());
+
+    store
+        .set_desired_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    let pending = store.list_pending_transitions().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].container.name, "web");
+    assert_eq!(pending[0].container.status, ContainerStatus::Stopped);
+    assert_eq!(pending[0].desired_status, ContainerStatus::Running);
+
+    // The agent works through the lifecycle; still pending midway
+    store
+        .update_status("web", ContainerStatus::Starting)
+        .await
+        .unwrap();
+    assert_eq!(store.list_pending_transitions().await.unwrap().len(), 1);
+    store
+        .update_status("web", ContainerStatus::Running)
+        .await
+        .unwrap();
+    assert!(store.list_pending_transitions().await.unwrap().is_empty());
+
+    assert!(matches!(
+        store
+            .set_desired_status("missing", ContainerStatus::Running)
+            .await,
+        Err(DbError::ContainerNotFound(_))
+    ));
+
+    // An unknown desired status decodes like any stored status
+    sqlx::query("UPDATE containers SET desired_status = 'exploded' WHERE name = 'db'")
+        .execute(store.pool())
+        .await
+        .unwrap();
+    assert!(matches!(
+        store.list_pending_transitions().await,
+        Err(DbError::Corrupt(_))
+    ));
+    let lenient = store.clone().with_status_decoding(StatusDecoding::Lenient);
+    let pending = lenient.list_pending_transitions().await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].desired_status, ContainerStatus::Error);
+}
+
+#[tokio::test]
+async fn test_max_containers_per_node() {
+    let store = test_store().await.with_max_containers_per_node(2);
+    for name in ["a", "b", "c", "d"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    let node = Uuid::new_v4();
+
+    store.assign_node("a", Some(node)).await.unwrap();
+    assert!(store.claim_next_unassigned(node).await.unwrap().is_some());
+    assert_eq!(store.count_by_node(&node).await.unwrap(), 2);
+
+    assert!(matches!(
+        store.assign_node("c", Some(node)).await,
+        Err(DbError::Validation(_))
+    ));
+    assert!(matches!(
+        store.claim_next_unassigned(node).await,
+        Err(DbError::Validation(_))
+    ));
+    assert_eq!(store.count_by_node(&node).await.unwrap(), 2);
+    assert!(store.get_by_name("c").await.unwrap().node_id.is_none());
+
+    // Re-assigning a container already on the node is not new placement
+    store.assign_node("a", Some(node)).await.unwrap();
+    store.assign_node("c", Some(Uuid::new_v4())).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_stream_events_ndjson() {
+    use futures::TryStreamExt;
+
+    let store = test_store().await;
+    let since = chrono::Utc::now();
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    for i in 0..3 {
+        store
+            .set_error("web", &format!("failure {}", i))
+            .await
+            .unwrap();
+    }
+
+    let lines: Vec<String> = store
+        .stream_events_ndjson(since)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(lines.len(), 3);
+
+    let events: Vec<models::ContainerEvent> = lines
+        .iter()
+        .map(|line| {
+            assert!(line.ends_with('\n') && !line.trim_end().contains('\n'));
+            serde_json::from_str(line).unwrap()
+        })
+        .collect();
+    let messages: Vec<_> = events.iter().filter_map(|e| e.message.as_deref()).collect();
+    assert_eq!(messages, ["failure 0", "failure 1", "failure 2"]);
+
+    let later = chrono::Utc::now() + chrono::Duration::seconds(1);
+    let none: Vec<String> = store
+        .stream_events_ndjson(later)
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn test_list_lenient_reports_bad_rows() {
+    let store = test_store().await;
+    for name in ["a", "b", "c"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    insert_raw(&store, "broken", "stopped", "{not json").await;
+
+    assert!(store.list().await.is_err());
+
+    let (containers, errors) = store.list_lenient().await.unwrap();
+    let names: Vec<_> = containers.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "c"]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].name, "broken");
+    assert!(matches!(errors[0].error, DbError::Serialization(_)));
+}
+
+#[tokio::test]
+async fn test_create_if_missing() {
+    let dir = std::env::temp_dir().join(format!("arm_hypervisor_db_{}", Uuid::new_v4()));
+    let path = dir.join("containers.db");
+    let config = PoolConfig {
+        url: format!("sqlite://{}", path.display()),
+        ..PoolConfig::default()
+    };
+    assert!(!config.create_if_missing);
+
+    assert!(matches!(
+        create_pool(&config).await,
+        Err(DbError::InvalidData(_))
+    ));
+    assert!(!dir.exists());
+
+    let creating = PoolConfig {
+        create_if_missing: true,
+        ..config.clone()
+    };
+    create_pool(&creating).await.unwrap().close().await;
+    assert!(path.exists());
+
+    // Once the file exists the default config opens it
+    create_pool(&config).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_status_age_buckets() {
+    let store = test_store().await;
+    let hour = Duration::from_secs(3600);
+    let day = 24 * hour;
+    for (name, age) in [
+        ("a", 2 * hour),
+        ("b", 2 * day),
+        ("c", 10 * day),
+        ("d", Duration::ZERO),
+    ] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+        store
+            .update_status(name, ContainerStatus::Stopped)
+            .await
+            .unwrap();
+        let updated_at = chrono::Utc::now() - chrono::Duration::from_std(age).unwrap();
+        sqlx::query("UPDATE containers SET updated_at = ?1 WHERE name = ?2")
+            .bind(crate::store::format_timestamp(&updated_at))
+            .bind(name)
+            .execute(store.pool())
+            .await
+            .unwrap();
+    }
+    store.create("e", "alpine", &test_config()).await.unwrap();
+    store
+        .update_status("e", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    let buckets = [hour, day, 7 * day, 30 * day];
+    let counts = store
+        .status_age_buckets(ContainerStatus::Stopped, &buckets)
+        .await
+        .unwrap();
+    assert_eq!(
+        counts,
+        vec![(hour, 3), (day, 2), (7 * day, 1), (30 * day, 0)]
+    );
+
+    let running = store
+        .status_age_buckets(ContainerStatus::Running, &[Duration::ZERO])
+        .await
+        .unwrap();
+    assert_eq!(running, vec![(Duration::ZERO, 1)]);
+    assert!(store
+        .status_age_buckets(ContainerStatus::Frozen, &[])
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_find_by_ip() {
+    let store = test_store().await;
+    for (name, ipv4) in [("web", "10.0.0.5/24"), ("db", "10.0.0.50")] {
+        let mut config = test_config();
+        config.network_interfaces = vec![models::ContainerNetworkInterface {
+            name: "eth0".to_string(),
+            bridge: "lxcbr0".to_string(),
+            ipv4: Some(ipv4.to_string()),
+            ipv6: Some("fd00::5".to_string()).filter(|_| name == "db"),
+            mac: None,
+        }];
+        store.create(name, "alpine", &config).await.unwrap();
+    }
+
+    let found = store.find_by_ip("10.0.0.5").await.unwrap().unwrap();
+    assert_eq!(found.name, "web");
+    let found = store.find_by_ip("10.0.0.50").await.unwrap().unwrap();
+    assert_eq!(found.name, "db");
+    let found = store.find_by_ip("fd00::5").await.unwrap().unwrap();
+    assert_eq!(found.name, "db");
+
+    assert!(store.find_by_ip("10.0.0.6").await.unwrap().is_none());
+    assert!(matches!(
+        store.find_by_ip("10.0.0.5/24").await,
+        Err(DbError::Validation(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_tx_store_rolls_back_as_a_unit() {
+    let store = test_store().await;
+    for name in ["a", "b"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    let node = Uuid::new_v4();
+
+    let mut tx = store.begin().await.unwrap();
+    let claimed = tx.claim_next_unassigned(node).await.unwrap().unwrap();
+    assert_eq!(claimed.name, "a");
+    tx.assign_node("b", Some(node)).await.unwrap();
+    tx.record_event("a", crate::events::EVENT_STATUS, Some("placed"))
+        .await
+        .unwrap();
+    assert_eq!(tx.get_by_name("b").await.unwrap().node_id, Some(node));
+    tx.rollback().await.unwrap();
+
+    assert_eq!(store.count_by_node(&node).await.unwrap(), 0);
+    assert!(store.list_events("a").await.unwrap().is_empty());
+
+    let mut tx = store.begin().await.unwrap();
+    tx.assign_node("b", Some(node)).await.unwrap();
+    tx.record_event("b", crate::events::EVENT_STATUS, Some("placed"))
+        .await
+        .unwrap();
+    tx.commit().await.unwrap();
+
+    assert_eq!(store.count_by_node(&node).await.unwrap(), 1);
+    assert_eq!(store.list_events("b").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_rename_template() {
+    let store = test_store().await;
+    for name in ["web", "db", "cache"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    store.create("app", "ubuntu", &test_config()).await.unwrap();
+
+    assert_eq!(store.rename_template("alpine", "alpine3").await.unwrap(), 3);
+    assert_eq!(store.list_by_template("alpine3").await.unwrap().len(), 3);
+    assert_eq!(
+        store.distinct_templates().await.unwrap(),
+        ["alpine3", "ubuntu"]
+    );
+
+    assert_eq!(store.rename_template("alpine", "alpine3").await.unwrap(), 0);
+    assert!(matches!(
+        store.rename_template("ubuntu", "Ubuntu Linux").await,
+        Err(DbError::Validation(_))
+    ));
+    assert_eq!(store.get_by_name("app").await.unwrap().template, "ubuntu");
+}
+
+#[test]
+fn test_statement_cache_capacity_reaches_connect_options() {
+    let config = PoolConfig {
+        statement_cache_capacity: 7,
+        ..test_pool_config()
+    };
+    let options = crate::pool::connect_options(&config).unwrap();
+    assert!(format!("{:?}", options).contains("statement_cache_capacity: 7"));
+}
+
+#[tokio::test]
+async fn test_pool_serves_queries_with_and_without_test_before_acquire() {
+    for test_before_acquire in [false, true] {
+        let store = test_store_with(&PoolConfig {
+            max_connections: 1,
+            test_before_acquire,
+            ..test_pool_config()
+        })
+        .await;
+        // Each call acquires the single connection again, pinging it first
+        // when the flag is on
+        for i in 0..3 {
+            store
+                .create(&format!("ct-{}", i), "alpine", &test_config())
+                .await
+                .unwrap();
+        }
+        assert_eq!(store.list().await.unwrap().len(), 3);
+    }
+}
+
+/// Repeated `get_by_name` latency with and without the statement cache
+///
+/// Run with `cargo test -p database --features bench-tests -- --nocapture`.
+#[cfg(feature = "bench-tests")]
+#[tokio::test]
+async fn bench_get_by_name_statement_cache() {
+    const LOOKUPS: u32 = 2_000;
+
+    for capacity in [100, 0] {
+        let store = test_store_with(&PoolConfig {
+            statement_cache_capacity: capacity,
+            max_connections: 1,
+            ..test_pool_config()
+        })
+        .await;
+        store.create("web", "alpine", &test_config()).await.unwrap();
+
+        let started = std::time::Instant::now();
+        for _ in 0..LOOKUPS {
+            store.get_by_name("web").await.unwrap();
+        }
+        let elapsed = started.elapsed();
+        eprintln!(
+            "statement_cache_capacity={}: {:?} per get_by_name",
+            capacity,
+            elapsed / LOOKUPS
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_list_invalid() {
+    let store = test_store().await;
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    let mut config = test_config();
+    config.cpu_limit = Some(1_000);
+    insert_raw(
+        &store,
+        "greedy",
+        "stopped",
+        &serde_json::to_string(&config).unwrap(),
+    )
+    .await;
+
+    insert_raw(
+        &store,
+        "bogus",
+        "exploded",
+        &serde_json::to_string(&config).unwrap(),
+    )
+    .await;
+
+    let invalid = store.list_invalid().await.unwrap();
+    assert_eq!(invalid.len(), 2);
+    match &invalid[0] {
+        InvalidContainer::Rejected { container, errors } => {
+            assert_eq!(container.name, "greedy");
+            let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+            assert_eq!(fields, ["cpu_limit"]);
+        }
+        other => panic!("expected a rejected container, got {:?}", other),
+    }
+    match &invalid[1] {
+        InvalidContainer::Undecodable { name, detail, .. } => {
+            assert_eq!(name, "bogus");
+            assert!(detail.contains("exploded"), "{}", detail);
+        }
+        other => panic!("expected an undecodable row, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_single_flight_coalesces_lookups() {
+    use std::sync::atomic::Ordering;
+
+    let store = test_store_with(&PoolConfig {
+        max_connections: 1,
+        ..test_pool_config()
+    })
+    .await
+    .with_single_flight(true);
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    store.name_lookups.store(0, Ordering::SeqCst);
+
+    // Hold the only connection so every lookup starts before the first ends
+    let conn = store.pool().acquire().await.unwrap();
+    let lookups = futures::future::join_all((0..20).map(|_| store.get_by_name("web")));
+    let release = async {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(conn);
+    };
+    let (results, ()) = tokio::join!(lookups, release);
+    assert!(results
+        .iter()
+        .all(|result| result.as_ref().unwrap().name == "web"));
+    assert_eq!(store.name_lookups.load(Ordering::SeqCst), 1);
+
+    // A finished lookup is not reused
+    store.get_by_name("web").await.unwrap();
+    assert!(store.try_get_by_name("db").await.unwrap().is_none());
+    assert_eq!(store.name_lookups.load(Ordering::SeqCst), 3);
+
+    let plain = store.clone().with_single_flight(false);
+    plain.name_lookups.store(0, Ordering::SeqCst);
+    futures::future::join_all((0..3).map(|_| plain.get_by_name("web"))).await;
+    assert_eq!(plain.name_lookups.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_list_updated_since() {
+    let store = test_store().await;
+    for name in ["a", "b", "c", "d"] {
+        store.create(name, "alpine", &test_config()).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    let checkpoint = chrono::Utc::now();
+    tokio::time::sleep(Duration::from_millis(5)).await;
+
+    store.set_label("c", "env", "prod").await.unwrap();
+    store
+        .update_status("a", ContainerStatus::Running)
+        .await
+        .unwrap();
+
+    let changed = store.list_updated_since(checkpoint).await.unwrap();
+    let names: Vec<_> = changed.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["c", "a"]);
+
+    let latest = changed.last().unwrap().updated_at;
+    assert!(store.list_updated_since(latest).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_updated_at_index_serves_incremental_queries() {
+    let store = test_store().await;
+    let checkpoint = chrono::Utc::now();
+    store.create("web", "alpine", &test_config()).await.unwrap();
+    assert_eq!(store.list_updated_since(checkpoint).await.unwrap().len(), 1);
+
+    let plan: Vec<(i64, i64, i64, String)> = sqlx::query_as(
+        "EXPLAIN QUERY PLAN SELECT id FROM containers WHERE updated_at > ?1 ORDER BY updated_at",
+    )
+    .bind(crate::store::format_timestamp(&checkpoint))
+    .fetch_all(store.pool())
+    .await
+    .unwrap();
+    assert!(
+        plan.iter()
+            .any(|(_, _, _, detail)| detail.contains("idx_containers_updated_at")),
+        "{:?}",
+        plan
+    );
+}
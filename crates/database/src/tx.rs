@@ -0,0 +1,122 @@
+use sqlx::{Sqlite, Transaction};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::error::DbError;
+use crate::events::record_event;
+use crate::store::{id_for_name, ContainerStore, NameScope, CONTAINER_COLUMNS};
+use models::Container;
+
+/// A `ContainerStore` bound to one transaction
+///
+/// Every method runs on the held transaction, so a sequence such as claim,
+/// assign and record commits or rolls back as a unit. Dropping the handle
+/// without calling `commit` rolls back. Settings such as the per-node cap
+/// and status decoding come from the store that began it.
+pub struct TxContainerStore {
+    store: ContainerStore,
+    tx: Transaction<'static, Sqlite>,
+}
+
+impl ContainerStore {
+    /// Start a transaction and return a store handle bound to it
+    pub async fn begin(&self) -> Result<TxContainerStore, DbError> {
+        let tx = self
+            .timed(async { Ok(self.writer()?.begin().await?) })
+            .await?;
+        Ok(TxContainerStore {
+            store: self.clone(),
+            tx,
+        })
+    }
+}
+
+impl TxContainerStore {
+    /// Read a container as this transaction sees it
+    pub async fn get_by_name(&mut self, name: impl AsRef<str>) -> Result<Container, DbError> {
+        let name = name.as_ref();
+        let Self { store, tx } = self;
+        store
+            .timed(async {
+                if store.name_scope() == NameScope::PerTemplate {
+                    return Err(DbError::InvalidData(
+                        "Names are unique per template; look up by name and template".to_string(),
+                    ));
+                }
+
+                let row = sqlx::query(&format!(
+                    "SELECT {} FROM containers WHERE name = ?1",
+                    CONTAINER_COLUMNS
+                ))
+                .bind(name)
+                .fetch_optional(&mut **tx)
+                .await?;
+
+                match row {
+                    Some(row) => store.to_container(&row),
+                    None => Err(DbError::ContainerNotFound(name.to_string())),
+                }
+            })
+            .await
+    }
+
+    /// See `ContainerStore::claim_next_unassigned`
+    pub async fn claim_next_unassigned(
+        &mut self,
+        node_id: Uuid,
+    ) -> Result<Option<Container>, DbError> {
+        let Self { store, tx } = self;
+        let claimed = store
+            .timed(store.claim_next_unassigned_on(tx, node_id))
+            .await?;
+
+        if let Some(container) = &claimed {
+            debug!(container.name = %container.name, %node_id, "Claimed container in transaction");
+        }
+        Ok(claimed)
+    }
+
+    /// See `ContainerStore::assign_node`
+    pub async fn assign_node(
+        &mut self,
+        name: impl AsRef<str>,
+        node_id: Option<Uuid>,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        let Self { store, tx } = self;
+        store.timed(store.assign_node_on(tx, name, node_id)).await?;
+
+        debug!(container.name = %name, ?node_id, "Assigned container to node in transaction");
+        Ok(())
+    }
+
+    /// Append an event to a container's history
+    pub async fn record_event(
+        &mut self,
+        name: impl AsRef<str>,
+        event_type: &str,
+        message: Option<&str>,
+    ) -> Result<(), DbError> {
+        let name = name.as_ref();
+        let Self { store, tx } = self;
+        store
+            .timed(async {
+                let id = id_for_name(&mut **tx, name).await?;
+
+                record_event(tx, &id, event_type, message).await
+            })
+            .await
+    }
+
+    pub async fn commit(self) -> Result<(), DbError> {
+        self.tx.commit().await?;
+        info!("Committed store transaction");
+        Ok(())
+    }
+
+    pub async fn rollback(self) -> Result<(), DbError> {
+        self.tx.rollback().await?;
+        info!("Rolled back store transaction");
+        Ok(())
+    }
+}
@@ -1,20 +1,204 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::template::Template;
+use crate::units::{deserialize_optional_bytes, format_bytes, parse_bytes};
+use crate::validation::{
+    validate_container_config, validate_container_name_with, NamePolicy, ValidationError, Validator,
+};
+
+/// Environment keys containing any of these (case-insensitively) hold secrets
+pub const SECRET_ENV_PATTERNS: &[&str] = &["PASSWORD", "SECRET", "TOKEN", "KEY"];
+
+/// Replacement for redacted environment values
+pub const REDACTED: &str = "***";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Container {
     pub id: Uuid,
-    pub name: String,
+    /// Read as written; archives and replicas may carry names from a looser
+    /// `NamePolicy`, and the store validates before inserting
+    #[serde(deserialize_with = "deserialize_stored_name")]
+    pub name: ContainerName,
     pub status: ContainerStatus,
     pub template: String,
     pub node_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub config: ContainerConfig,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl Container {
+    /// Strong HTTP entity tag, quoted and ready for an `ETag` header
+    ///
+    /// Hashes the id, `updated_at` and status. Every store write bumps
+    /// `updated_at`, so the tag changes exactly when the stored row does.
+    pub fn etag(&self) -> String {
+        let key = format!(
+            "{}|{}|{}",
+            self.id,
+            self.updated_at
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true),
+            self.status
+        );
+        format!("\"{:016x}\"", fnv1a(key.as_bytes()))
+    }
+
+    /// Copy with secret environment values masked, using `SECRET_ENV_PATTERNS`
+    pub fn redacted(&self) -> Container {
+        self.redacted_with(SECRET_ENV_PATTERNS)
+    }
+
+    /// Copy with the values of env entries whose key contains any of `patterns` masked
+    pub fn redacted_with(&self, patterns: &[&str]) -> Container {
+        Container {
+            config: self.config.redacted_with(patterns),
+            ..self.clone()
+        }
+    }
+}
+
+fn deserialize_stored_name<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ContainerName, D::Error> {
+    String::deserialize(deserializer).map(ContainerName::new_unchecked)
+}
+
+/// `environment` with the values of keys containing any of `patterns` masked
+fn redact_environment(
+    environment: &[(String, String)],
+    patterns: &[&str],
+) -> Vec<(String, String)> {
+    environment
+        .iter()
+        .map(|(key, value)| {
+            let upper = key.to_uppercase();
+            let secret = patterns
+                .iter()
+                .any(|pattern| upper.contains(&pattern.to_uppercase()));
+            let value = if secret { REDACTED } else { value.as_str() };
+            (key.clone(), value.to_string())
+        })
+        .collect()
+}
+
+/// A container name that has passed `validate_container_name`
+///
+/// Construction trims surrounding whitespace and lowercases ASCII letters
+/// before validating, so `" Web-1 "` becomes `web-1`. Deserializing goes
+/// through the same path. Names validated elsewhere, such as rows written
+/// under a looser `NamePolicy`, are wrapped with `new_unchecked`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ContainerName(String);
+
+impl ContainerName {
+    pub fn new(name: &str) -> Result<Self, ValidationError> {
+        Self::with_policy(name, &NamePolicy::default())
+    }
+
+    /// Normalize and validate under a deployment-specific policy
+    ///
+    /// Uppercase letters are kept when the policy allows them.
+    pub fn with_policy(name: &str, policy: &NamePolicy) -> Result<Self, ValidationError> {
+        let name = name.trim();
+        let name = if policy.allow_uppercase {
+            name.to_string()
+        } else {
+            name.to_ascii_lowercase()
+        };
+        validate_container_name_with(&name, policy)?;
+        Ok(ContainerName(name))
+    }
+
+    /// Wrap a name without normalizing or validating it
+    ///
+    /// For names that were checked when written, under whatever policy was in
+    /// force, so stored rows load unchanged.
+    pub fn new_unchecked(name: impl Into<String>) -> Self {
+        ContainerName(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::ops::Deref for ContainerName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<&str> for ContainerName {
+    type Error = ValidationError;
+
+    fn try_from(name: &str) -> Result<Self, Self::Error> {
+        Self::new(name)
+    }
+}
+
+impl TryFrom<String> for ContainerName {
+    type Error = ValidationError;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::new(&name)
+    }
+}
+
+impl From<ContainerName> for String {
+    fn from(name: ContainerName) -> Self {
+        name.0
+    }
+}
+
+impl AsRef<str> for ContainerName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for ContainerName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+impl PartialEq<&str> for ContainerName {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for ContainerName {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<ContainerName> for &str {
+    fn eq(&self, other: &ContainerName) -> bool {
+        *self == other.0
+    }
+}
+
+impl fmt::Display for ContainerName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum ContainerStatus {
     Stopped,
@@ -25,14 +209,363 @@ pub enum ContainerStatus {
     Error,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl ContainerStatus {
+    /// Lowercase string form, matching the serde representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContainerStatus::Stopped => "stopped",
+            ContainerStatus::Starting => "starting",
+            ContainerStatus::Running => "running",
+            ContainerStatus::Stopping => "stopping",
+            ContainerStatus::Frozen => "frozen",
+            ContainerStatus::Error => "error",
+        }
+    }
+
+    /// Whether the container holds live resources (running or frozen)
+    pub fn is_active(&self) -> bool {
+        matches!(self, ContainerStatus::Running | ContainerStatus::Frozen)
+    }
+
+    /// Whether the lifecycle allows moving from this status to `next`
+    ///
+    /// Any status may fail into `Error`; an errored container can be reset
+    /// to `Stopped` or started again.
+    pub fn can_transition_to(&self, next: &ContainerStatus) -> bool {
+        use ContainerStatus::*;
+        matches!(
+            (self, next),
+            (Stopped, Starting)
+                | (Starting, Running)
+                | (Starting, Stopped)
+                | (Running, Stopping)
+                | (Running, Frozen)
+                | (Frozen, Running)
+                | (Frozen, Stopping)
+                | (Stopping, Stopped)
+                | (Error, Stopped)
+                | (Error, Starting)
+        ) || (*next == Error && *self != Error)
+    }
+}
+
+impl fmt::Display for ContainerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ContainerStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stopped" => Ok(ContainerStatus::Stopped),
+            "starting" => Ok(ContainerStatus::Starting),
+            "running" => Ok(ContainerStatus::Running),
+            "stopping" => Ok(ContainerStatus::Stopping),
+            "frozen" => Ok(ContainerStatus::Frozen),
+            "error" => Ok(ContainerStatus::Error),
+            other => Err(format!("Unknown container status: {}", other)),
+        }
+    }
+}
+
+/// Resource limits and runtime settings, stored as JSON on each container row
+///
+/// A stored config is only rewritten when that container's config changes,
+/// so every shape ever written must keep deserializing:
+/// - a renamed field keeps its old name as a `#[serde(alias)]`
+/// - a new field is an `Option` or carries `#[serde(default)]`
+/// - fields are never removed while rows may still hold them
+///
+/// No field has been renamed yet. Memory and disk sizes may also be written
+/// as strings such as `"512MiB"`; they are always serialized as byte counts.
+///
+/// `Debug` masks secret environment values. `Serialize` is the stored form
+/// and keeps them; serialize `redacted()` when the output leaves the store.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ContainerConfig {
+    #[serde(default)]
     pub cpu_limit: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_optional_bytes")]
     pub memory_limit: Option<u64>, // in bytes
-    pub disk_limit: Option<u64>,   // in bytes
+    /// CPUs the scheduler reserves; may burst up to `cpu_limit`. `None`
+    /// reserves the full limit, as configs written before requests existed do
+    #[serde(default)]
+    pub cpu_request: Option<u32>,
+    /// Memory in bytes the scheduler reserves; `None` reserves `memory_limit`
+    #[serde(default, deserialize_with = "deserialize_optional_bytes")]
+    pub memory_request: Option<u64>,
+    /// `None` leaves the deployment default in place
+    #[serde(default)]
+    pub disk_limit: Option<DiskLimit>,
+    #[serde(default)]
     pub network_interfaces: Vec<ContainerNetworkInterface>,
     pub rootfs_path: String,
+    #[serde(default)]
     pub environment: Vec<(String, String)>,
+    /// DNS-visible hostname; `None` uses the container name
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+// Debug output ends up in logs, so it never shows secret values
+impl fmt::Debug for ContainerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ContainerConfig")
+            .field("cpu_limit", &self.cpu_limit)
+            .field("memory_limit", &self.memory_limit)
+            .field("cpu_request", &self.cpu_request)
+            .field("memory_request", &self.memory_request)
+            .field("disk_limit", &self.disk_limit)
+            .field("network_interfaces", &self.network_interfaces)
+            .field("rootfs_path", &self.rootfs_path)
+            .field(
+                "environment",
+                &redact_environment(&self.environment, SECRET_ENV_PATTERNS),
+            )
+            .field("hostname", &self.hostname)
+            .finish()
+    }
+}
+
+impl ContainerConfig {
+    /// Copy with secret environment values masked, using `SECRET_ENV_PATTERNS`
+    pub fn redacted(&self) -> ContainerConfig {
+        self.redacted_with(SECRET_ENV_PATTERNS)
+    }
+
+    /// Copy with the values of env entries whose key contains any of `patterns` masked
+    pub fn redacted_with(&self, patterns: &[&str]) -> ContainerConfig {
+        ContainerConfig {
+            environment: redact_environment(&self.environment, patterns),
+            ..self.clone()
+        }
+    }
+
+    /// Build a config from flat `KEY=VALUE` pairs, as passed by CLI and CI users
+    ///
+    /// Keys are `CPU`, `MEM` (or `MEMORY`), `DISK` (a size or `unlimited`),
+    /// `ROOTFS`, and `ENV_<NAME>` for environment variables. Sizes take binary
+    /// `K`/`M`/`G`/`T` suffixes. Unknown keys and unparseable values are all
+    /// reported; if every pair parses, the config is validated as usual.
+    pub fn from_kv_pairs(pairs: &[(&str, &str)]) -> Result<ContainerConfig, Vec<ValidationError>> {
+        let mut config = ContainerConfig {
+            cpu_limit: None,
+            memory_limit: None,
+            disk_limit: None,
+            network_interfaces: Vec::new(),
+            rootfs_path: String::new(),
+            environment: Vec::new(),
+            hostname: None,
+            cpu_request: None,
+            memory_request: None,
+        };
+        let mut validator = Validator::new();
+        let size = |field: &str, value: &str| {
+            parse_bytes(value).map_err(|mut e| {
+                e.field = field.to_string();
+                e
+            })
+        };
+
+        for (key, value) in pairs {
+            let parsed = match key.to_ascii_uppercase().as_str() {
+                "CPU" => value
+                    .trim()
+                    .parse()
+                    .map(|cpu| config.cpu_limit = Some(cpu))
+                    .map_err(|_| {
+                        ValidationError::new(
+                            "cpu_limit",
+                            "INVALID_NUMBER",
+                            format!("Invalid CPU count '{}'", value),
+                        )
+                    }),
+                "MEM" | "MEMORY" => {
+                    size("memory_limit", value).map(|bytes| config.memory_limit = Some(bytes))
+                }
+                "DISK" if value.eq_ignore_ascii_case("unlimited") => {
+                    config.disk_limit = Some(DiskLimit::Unlimited);
+                    Ok(())
+                }
+                "DISK" => size("disk_limit", value)
+                    .map(|bytes| config.disk_limit = Some(DiskLimit::Bytes(bytes))),
+                "ROOTFS" => {
+                    config.rootfs_path = value.to_string();
+                    Ok(())
+                }
+                _ => match key.strip_prefix("ENV_") {
+                    Some(name) => {
+                        config
+                            .environment
+                            .push((name.to_string(), value.to_string()));
+                        Ok(())
+                    }
+                    None => Err(ValidationError::new(
+                        *key,
+                        "UNKNOWN_KEY",
+                        format!("Unknown config key '{}'", key),
+                    )),
+                },
+            };
+            validator.check(parsed);
+        }
+
+        validator.finish()?;
+        validate_container_config(&config)?;
+        Ok(config)
+    }
+}
+
+/// Human-readable summary of a config's resource limits, from `ContainerConfig::limits`
+///
+/// Renders as e.g. `cpu=2 memory=512MiB disk=unlimited`; unset limits show
+/// as `default`.
+pub struct LimitsView<'a>(&'a ContainerConfig);
+
+impl ContainerConfig {
+    pub fn limits(&self) -> LimitsView<'_> {
+        LimitsView(self)
+    }
+
+    /// CPUs to reserve when placing: the request, else the limit
+    pub fn effective_cpu_request(&self) -> Option<u32> {
+        self.cpu_request.or(self.cpu_limit)
+    }
+
+    /// Memory to reserve when placing: the request, else the limit
+    pub fn effective_memory_request(&self) -> Option<u64> {
+        self.memory_request.or(self.memory_limit)
+    }
+
+    /// The same config with its lists in a canonical order
+    ///
+    /// Environment entries are sorted by key and network interfaces by name,
+    /// so configs that differ only in ordering serialize identically. The
+    /// store saves configs in this form. The sort is stable: entries sharing
+    /// a key keep their order, so the last one still wins.
+    pub fn normalized(&self) -> ContainerConfig {
+        let mut normalized = self.clone();
+        normalized.environment.sort_by(|a, b| a.0.cmp(&b.0));
+        // Full JSON breaks ties, should two interfaces share a name
+        normalized
+            .network_interfaces
+            .sort_by_cached_key(|interface| {
+                (
+                    interface.name.clone(),
+                    serde_json::to_string(interface).unwrap_or_default(),
+                )
+            });
+        normalized
+    }
+
+    /// Serialized names of the fields that differ from `other`, sorted
+    ///
+    /// Both sides are normalized first, so reordered lists are not changes.
+    pub fn diff(&self, other: &ContainerConfig) -> Vec<String> {
+        let as_map = |config: &ContainerConfig| match serde_json::to_value(config.normalized()) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        let (ours, theirs) = (as_map(self), as_map(other));
+
+        ours.into_iter()
+            .filter(|(field, value)| theirs.get(field) != Some(value))
+            .map(|(field, _)| field)
+            .collect()
+    }
+}
+
+impl fmt::Display for LimitsView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let config = self.0;
+        match config.cpu_limit {
+            Some(cpu) => write!(f, "cpu={}", cpu)?,
+            None => f.write_str("cpu=default")?,
+        }
+        match config.memory_limit {
+            Some(bytes) => write!(f, " memory={}", format_bytes(bytes))?,
+            None => f.write_str(" memory=default")?,
+        }
+        match config.disk_limit {
+            Some(DiskLimit::Bytes(bytes)) => write!(f, " disk={}", format_bytes(bytes)),
+            Some(DiskLimit::Unlimited) => f.write_str(" disk=unlimited"),
+            None => f.write_str(" disk=default"),
+        }
+    }
+}
+
+/// Stable hash of a config, for spotting duplicated definitions
+///
+/// Taken over `ContainerConfig::normalized`, so list order does not matter.
+/// Hashed with FNV-1a over the JSON, which stays the same across builds and
+/// Rust releases.
+pub fn config_fingerprint(config: &ContainerConfig) -> String {
+    let json = serde_json::to_vec(&config.normalized()).unwrap_or_default();
+    format!("{:016x}", fnv1a(&json))
+}
+
+/// 64-bit FNV-1a; stable across builds and platforms, unlike `DefaultHasher`
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// An explicit disk cap, or none at all
+///
+/// Serialized as a byte count or the string `"unlimited"`, so configs stored
+/// when this was a plain `Option<u64>` still read back as `Bytes`. Their
+/// nulls meant no cap and are rewritten to `"unlimited"` by migration 13.
+/// Sizes written as text, e.g. `"10GiB"`, are accepted too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskLimit {
+    /// No cap, e.g. backed by a shared pool
+    Unlimited,
+    Bytes(u64),
+}
+
+impl DiskLimit {
+    /// The cap in bytes, or `None` when unlimited
+    pub fn bytes(&self) -> Option<u64> {
+        match self {
+            DiskLimit::Unlimited => None,
+            DiskLimit::Bytes(bytes) => Some(*bytes),
+        }
+    }
+}
+
+impl Serialize for DiskLimit {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DiskLimit::Unlimited => serializer.serialize_str("unlimited"),
+            DiskLimit::Bytes(bytes) => serializer.serialize_u64(*bytes),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DiskLimit {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            Keyword(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(DiskLimit::Bytes(bytes)),
+            Repr::Keyword(keyword) if keyword == "unlimited" => Ok(DiskLimit::Unlimited),
+            Repr::Keyword(other) => parse_bytes(&other).map(DiskLimit::Bytes).map_err(|_| {
+                serde::de::Error::custom(format!(
+                    "invalid disk limit {:?}, expected a size or \"unlimited\"",
+                    other
+                ))
+            }),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -44,11 +577,54 @@ pub struct ContainerNetworkInterface {
     pub mac: Option<String>,
 }
 
+/// An entry in a container's history (status changes, errors, placement)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContainerEvent {
+    pub id: i64,
+    pub container_id: Uuid,
+    pub event_type: String,
+    pub message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateContainerRequest {
     pub name: String,
-    pub template: String,
+    /// Validated as the request is parsed
+    pub template: Template,
     pub config: ContainerConfig,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// A partial config update; absent fields keep their stored value
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigPatch {
+    pub cpu_limit: Option<u32>,
+    #[serde(deserialize_with = "deserialize_optional_bytes")]
+    pub memory_limit: Option<u64>,
+    pub disk_limit: Option<DiskLimit>,
+    /// Merged key by key into the stored environment, see `merge_environment`
+    pub environment: Option<Vec<(String, String)>>,
+}
+
+// Like `ContainerConfig`, never shows secret environment values
+impl fmt::Debug for ConfigPatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConfigPatch")
+            .field("cpu_limit", &self.cpu_limit)
+            .field("memory_limit", &self.memory_limit)
+            .field("disk_limit", &self.disk_limit)
+            .field(
+                "environment",
+                &self
+                    .environment
+                    .as_deref()
+                    .map(|environment| redact_environment(environment, SECRET_ENV_PATTERNS)),
+            )
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,3 +636,362 @@ pub struct ContainerResponse {
 pub struct ContainerListResponse {
     pub containers: Vec<Container>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_name_construction() {
+        assert_eq!(ContainerName::new("web-1").unwrap(), "web-1");
+        assert_eq!(ContainerName::new(" Web-1 ").unwrap(), "web-1");
+
+        assert_eq!(ContainerName::new("").unwrap_err().code, "NAME_EMPTY");
+        assert_eq!(ContainerName::new("web_1").unwrap_err().field, "name");
+        assert!(ContainerName::try_from("-web").is_err());
+
+        let dotted = NamePolicy {
+            allow_dots: true,
+            ..NamePolicy::default()
+        };
+        assert!(ContainerName::new("web.prod").is_err());
+        assert_eq!(
+            ContainerName::with_policy("web.prod", &dotted).unwrap(),
+            "web.prod"
+        );
+    }
+
+    #[test]
+    fn test_container_name_serde_validates() {
+        let name = ContainerName::new("web-1").unwrap();
+        let json = serde_json::to_string(&name).unwrap();
+        assert_eq!(json, "\"web-1\"");
+        assert_eq!(serde_json::from_str::<ContainerName>(&json).unwrap(), name);
+
+        assert_eq!(
+            serde_json::from_str::<ContainerName>("\"WEB\"").unwrap(),
+            "web"
+        );
+        assert!(serde_json::from_str::<ContainerName>("\"web_1\"").is_err());
+    }
+
+    #[test]
+    fn test_container_keeps_stored_name() {
+        let mut json = serde_json::to_value(container_with_env(vec![])).unwrap();
+        json["name"] = "Web_1".into();
+        let container: Container = serde_json::from_value(json).unwrap();
+        assert_eq!(container.name, "Web_1");
+        assert_eq!(serde_json::to_value(&container).unwrap()["name"], "Web_1");
+    }
+
+    fn container_with_env(environment: Vec<(&str, &str)>) -> Container {
+        Container {
+            id: Uuid::new_v4(),
+            name: ContainerName::new("web").unwrap(),
+            status: ContainerStatus::Stopped,
+            template: "alpine".to_string(),
+            node_id: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            config: ContainerConfig {
+                cpu_limit: None,
+                memory_limit: None,
+                disk_limit: None,
+                network_interfaces: vec![],
+                rootfs_path: "/var/lib/lxc/web/rootfs".to_string(),
+                environment: environment
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+                hostname: None,
+                cpu_request: None,
+                memory_request: None,
+            },
+            labels: BTreeMap::new(),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_config_from_kv_pairs() {
+        let config = ContainerConfig::from_kv_pairs(&[
+            ("CPU", "2"),
+            ("MEM", "512M"),
+            ("DISK", "5G"),
+            ("ROOTFS", "/var/lib/lxc/web/rootfs"),
+            ("ENV_LOG", "debug"),
+        ])
+        .unwrap();
+        assert_eq!(config.cpu_limit, Some(2));
+        assert_eq!(config.memory_limit, Some(512 * 1024 * 1024));
+        assert_eq!(
+            config.disk_limit,
+            Some(DiskLimit::Bytes(5 * 1024 * 1024 * 1024))
+        );
+        assert_eq!(
+            config.environment,
+            vec![("LOG".to_string(), "debug".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_config_from_kv_pairs_rejects_bad_input() {
+        let errors = ContainerConfig::from_kv_pairs(&[
+            ("MEM", "xyz"),
+            ("ROOTFS", "/var/lib/lxc/web/rootfs"),
+            ("COLOR", "blue"),
+        ])
+        .unwrap_err();
+        let codes: Vec<_> = errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.code.as_ref()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![("memory_limit", "INVALID_SIZE"), ("COLOR", "UNKNOWN_KEY")]
+        );
+
+        // Parsed values still go through the usual range checks
+        let errors =
+            ContainerConfig::from_kv_pairs(&[("MEM", "1K"), ("ROOTFS", "/rootfs")]).unwrap_err();
+        assert_eq!(errors[0].code, "MEMORY_OUT_OF_RANGE");
+    }
+
+    #[test]
+    fn test_config_deserializes_older_shapes() {
+        // Written before requests, hostnames and the `unlimited` disk limit
+        let config: ContainerConfig = serde_json::from_str(
+            r#"{"cpu_limit": 2, "memory_limit": 536870912, "disk_limit": 10737418240,
+                "network_interfaces": [], "rootfs_path": "/var/lib/lxc/web/rootfs",
+                "environment": [["HOME", "/root"]]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.cpu_limit, Some(2));
+        assert_eq!(config.memory_limit, Some(512 * 1024 * 1024));
+        assert_eq!(
+            config.disk_limit,
+            Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024))
+        );
+        assert_eq!(config.cpu_request, None);
+        assert_eq!(config.memory_request, None);
+        assert_eq!(config.hostname, None);
+
+        // Before migration 13 a null disk limit meant unlimited; the row
+        // still loads until the migration rewrites it
+        let config: ContainerConfig = serde_json::from_str(
+            r#"{"cpu_limit": null, "memory_limit": null, "disk_limit": null,
+                "network_interfaces": [], "rootfs_path": "/var/lib/lxc/db/rootfs",
+                "environment": []}"#,
+        )
+        .unwrap();
+        assert_eq!(config.disk_limit, None);
+    }
+
+    #[test]
+    fn test_limits_display() {
+        let mut config = container_with_env(vec![]).config;
+        assert_eq!(
+            config.limits().to_string(),
+            "cpu=default memory=default disk=default"
+        );
+
+        config.cpu_limit = Some(2);
+        config.memory_limit = Some(512 * 1024 * 1024);
+        config.disk_limit = Some(DiskLimit::Unlimited);
+        assert_eq!(
+            config.limits().to_string(),
+            "cpu=2 memory=512MiB disk=unlimited"
+        );
+    }
+
+    #[test]
+    fn test_config_fingerprint_ignores_order() {
+        let a = container_with_env(vec![("HOME", "/root"), ("LOG", "info")]).config;
+        let mut b = container_with_env(vec![("LOG", "info"), ("HOME", "/root")]).config;
+        assert_eq!(config_fingerprint(&a), config_fingerprint(&b));
+
+        let interface = |name: &str| ContainerNetworkInterface {
+            name: name.to_string(),
+            bridge: "br0".to_string(),
+            ipv4: None,
+            ipv6: None,
+            mac: None,
+        };
+        let mut c = a.clone();
+        c.network_interfaces = vec![interface("eth0"), interface("eth1")];
+        b.network_interfaces = vec![interface("eth1"), interface("eth0")];
+        assert_eq!(config_fingerprint(&b), config_fingerprint(&c));
+
+        c.cpu_limit = Some(2);
+        assert_ne!(config_fingerprint(&b), config_fingerprint(&c));
+    }
+
+    #[test]
+    fn test_config_diff() {
+        let a = container_with_env(vec![("HOME", "/root"), ("LOG", "info")]).config;
+        let mut b = container_with_env(vec![("LOG", "info"), ("HOME", "/root")]).config;
+        assert!(a.diff(&b).is_empty());
+
+        b.cpu_limit = Some(4);
+        b.environment.push(("PORT".to_string(), "80".to_string()));
+        assert_eq!(a.diff(&b), vec!["cpu_limit", "environment"]);
+    }
+
+    #[test]
+    fn test_normalized_serializes_identically() {
+        let interface = |name: &str| ContainerNetworkInterface {
+            name: name.to_string(),
+            bridge: "br0".to_string(),
+            ipv4: None,
+            ipv6: None,
+            mac: None,
+        };
+        let mut a = container_with_env(vec![("HOME", "/root"), ("LOG", "info")]).config;
+        let mut b = container_with_env(vec![("LOG", "info"), ("HOME", "/root")]).config;
+        a.network_interfaces = vec![interface("eth0"), interface("eth1")];
+        b.network_interfaces = vec![interface("eth1"), interface("eth0")];
+        assert_ne!(
+            serde_json::to_string(&a).unwrap(),
+            serde_json::to_string(&b).unwrap()
+        );
+
+        assert_eq!(
+            serde_json::to_string(&a.normalized()).unwrap(),
+            serde_json::to_string(&b.normalized()).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_string(&a.normalized().normalized()).unwrap(),
+            serde_json::to_string(&a.normalized()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_normalized_keeps_duplicate_key_order() {
+        let config = container_with_env(vec![("B", "x"), ("A", "2"), ("A", "1")]).config;
+        let normalized = config.normalized();
+        let env: Vec<(&str, &str)> = normalized
+            .environment
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(env, [("A", "2"), ("A", "1"), ("B", "x")]);
+    }
+
+    #[test]
+    fn test_redacted_masks_secrets() {
+        let container = container_with_env(vec![
+            ("DB_PASSWORD", "hunter2"),
+            ("api_token", "abc123"),
+            ("HOME", "/root"),
+        ]);
+
+        let redacted = container.redacted();
+        assert_eq!(
+            redacted.config.environment,
+            vec![
+                ("DB_PASSWORD".to_string(), REDACTED.to_string()),
+                ("api_token".to_string(), REDACTED.to_string()),
+                ("HOME".to_string(), "/root".to_string()),
+            ]
+        );
+        // The original is untouched
+        assert_eq!(container.config.environment[0].1, "hunter2");
+
+        let custom = container.redacted_with(&["HOME"]);
+        assert_eq!(custom.config.environment[0].1, "hunter2");
+        assert_eq!(custom.config.environment[2].1, REDACTED);
+    }
+
+    #[test]
+    fn test_config_sizes_from_strings() {
+        let config: ContainerConfig = serde_json::from_str(
+            r#"{"rootfs_path": "/r", "memory_limit": "512MiB", "memory_request": "256MiB", "disk_limit": "10G"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.memory_limit, Some(512 * 1024 * 1024));
+        assert_eq!(config.memory_request, Some(256 * 1024 * 1024));
+        assert_eq!(config.disk_limit, Some(DiskLimit::Bytes(10 << 30)));
+
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["memory_limit"], 512 * 1024 * 1024);
+        assert_eq!(json["disk_limit"], 10u64 << 30);
+
+        let config: ContainerConfig =
+            serde_json::from_str(r#"{"rootfs_path": "/r", "memory_limit": 512}"#).unwrap();
+        assert_eq!(config.memory_limit, Some(512));
+        assert!(serde_json::from_str::<ContainerConfig>(
+            r#"{"rootfs_path": "/r", "memory_limit": "abc"}"#
+        )
+        .is_err());
+
+        let patch: ConfigPatch =
+            serde_json::from_str(r#"{"memory_limit": "1G", "disk_limit": "20GiB"}"#).unwrap();
+        assert_eq!(patch.memory_limit, Some(1 << 30));
+        assert_eq!(patch.disk_limit, Some(DiskLimit::Bytes(20 << 30)));
+    }
+
+    #[test]
+    fn test_disk_limit_serde() {
+        let parse = |json: &str| serde_json::from_str::<Option<DiskLimit>>(json).unwrap();
+        // Configs stored before DiskLimit existed hold a plain number or null
+        assert_eq!(parse("1073741824"), Some(DiskLimit::Bytes(1073741824)));
+        assert_eq!(parse("null"), None);
+        assert_eq!(parse("\"unlimited\""), Some(DiskLimit::Unlimited));
+        assert_eq!(parse("\"10GiB\""), Some(DiskLimit::Bytes(10 << 30)));
+        assert!(serde_json::from_str::<DiskLimit>("\"lots\"").is_err());
+
+        assert_eq!(
+            serde_json::to_string(&DiskLimit::Unlimited).unwrap(),
+            "\"unlimited\""
+        );
+        assert_eq!(serde_json::to_string(&DiskLimit::Bytes(5)).unwrap(), "5");
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use ContainerStatus::*;
+        assert!(Stopped.can_transition_to(&Starting));
+        assert!(Running.can_transition_to(&Frozen));
+        assert!(Frozen.can_transition_to(&Running));
+        assert!(Running.can_transition_to(&Error));
+        assert!(Error.can_transition_to(&Stopped));
+
+        assert!(!Stopped.can_transition_to(&Running));
+        assert!(!Stopped.can_transition_to(&Stopped));
+        assert!(!Stopping.can_transition_to(&Running));
+        assert!(!Error.can_transition_to(&Error));
+    }
+
+    #[test]
+    fn test_status_is_active() {
+        use ContainerStatus::*;
+        assert!(Running.is_active());
+        assert!(Frozen.is_active());
+        for status in [Stopped, Starting, Stopping, Error] {
+            assert!(!status.is_active());
+        }
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let container = container_with_env(vec![("DB_PASSWORD", "hunter2"), ("HOME", "/root")]);
+        let output = format!("{:?}", container);
+        assert!(!output.contains("hunter2"));
+        assert!(output.contains("/root"));
+
+        let output = format!("{:?}", container.config);
+        assert!(!output.contains("hunter2"));
+        assert!(output.contains("/root"));
+        let patch = ConfigPatch {
+            environment: Some(vec![("API_TOKEN".to_string(), "abc123".to_string())]),
+            ..ConfigPatch::default()
+        };
+        assert!(!format!("{:?}", patch).contains("abc123"));
+
+        // The stored form keeps the value; redacted() is for output
+        let json = serde_json::to_string(&container.config).unwrap();
+        assert!(json.contains("hunter2"));
+        let json = serde_json::to_string(&container.config.redacted()).unwrap();
+        assert!(!json.contains("hunter2"));
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(feature = "actix")]
+use actix_web::http::StatusCode;
+#[cfg(feature = "actix")]
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::validation::{ValidationError, ValidationErrors};
+
+#[derive(Debug, Error)]
+pub enum ModelError {
+    #[error("Validation failed: {0}")]
+    ValidationError(ValidationErrors),
+
+    #[error("Invalid name: {0}")]
+    InvalidName(String),
+
+    #[error("Invalid resource limit: {0}")]
+    InvalidResourceLimit(String),
+
+    #[error("Internal error: {0}")]
+    InternalError(String),
+}
+
+impl From<Vec<ValidationError>> for ModelError {
+    fn from(errors: Vec<ValidationError>) -> Self {
+        ModelError::ValidationError(ValidationErrors(errors))
+    }
+}
+
+/// JSON body returned for API errors
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorBody {
+    pub error: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<ValidationError>,
+}
+
+impl ErrorBody {
+    pub fn new(error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "actix")]
+impl ResponseError for ModelError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ModelError::ValidationError(_)
+            | ModelError::InvalidName(_)
+            | ModelError::InvalidResourceLimit(_) => StatusCode::BAD_REQUEST,
+            ModelError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut body = ErrorBody::new(self.to_string());
+        if let ModelError::ValidationError(errors) = self {
+            body.fields = errors.0.clone();
+        }
+        HttpResponse::build(self.status_code()).json(body)
+    }
+}
+
+#[cfg(all(test, feature = "actix"))]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_validation_error_becomes_bad_request() {
+        let error: actix_web::Error = ModelError::from(vec![ValidationError::new(
+            "cpu_limit",
+            "CPU_OUT_OF_RANGE",
+            "CPU limit must be between 1 and 128 cores",
+        )])
+        .into();
+
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let bytes = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        assert!(body["error"]
+            .as_str()
+            .unwrap()
+            .contains("CPU limit must be between 1 and 128 cores"));
+        assert_eq!(body["fields"][0]["code"], "CPU_OUT_OF_RANGE");
+    }
+
+    #[test]
+    fn test_internal_error_becomes_server_error() {
+        let error = ModelError::InternalError("boom".to_string());
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            ModelError::InvalidName("Web".to_string()).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::container::{
+    Container, ContainerConfig, ContainerName, ContainerNetworkInterface, ContainerStatus,
+    DiskLimit,
+};
+use crate::units::parse_bytes;
+use crate::validation::{
+    validate_container_config, validate_description, validate_label, validate_template,
+    ValidationError, Validator,
+};
+
+/// A container as an external import or sync document describes it
+///
+/// Looser than `Container`: the name may need normalizing, the status is
+/// text, and sizes may be byte counts or text such as `"512MiB"`. A missing id,
+/// status or timestamp gets the value a fresh container would have. Turn
+/// one into a `Container` with `canonicalize_import`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RawContainer {
+    pub id: Option<Uuid>,
+    pub name: String,
+    pub status: Option<String>,
+    pub template: String,
+    pub node_id: Option<Uuid>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub cpu_limit: Option<u32>,
+    pub cpu_request: Option<u32>,
+    pub memory_limit: Option<RawSize>,
+    pub memory_request: Option<RawSize>,
+    /// A size or `unlimited`
+    pub disk_limit: Option<RawSize>,
+    pub network_interfaces: Vec<ContainerNetworkInterface>,
+    pub rootfs_path: String,
+    pub environment: Vec<(String, String)>,
+    pub hostname: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub description: Option<String>,
+}
+
+/// A size in an import document: a byte count, or text such as `"512MiB"`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum RawSize {
+    Bytes(u64),
+    Text(String),
+}
+
+impl From<u64> for RawSize {
+    fn from(bytes: u64) -> Self {
+        RawSize::Bytes(bytes)
+    }
+}
+
+impl From<&str> for RawSize {
+    fn from(text: &str) -> Self {
+        RawSize::Text(text.to_string())
+    }
+}
+
+/// Normalize, parse and validate an imported container in one pass
+///
+/// The name is trimmed and lowercased, the status is matched
+/// case-insensitively and sizes go through `parse_bytes`. Every field is
+/// then checked as `create` would, and every problem is returned together
+/// rather than stopping at the first.
+pub fn canonicalize_import(raw: RawContainer) -> Result<Container, Vec<ValidationError>> {
+    let mut validator = Validator::new();
+    let size = |field: &str, value: &str| {
+        parse_bytes(value).map_err(|mut e| {
+            e.field = field.to_string();
+            e
+        })
+    };
+
+    let name = ContainerName::new(&raw.name);
+    if let Err(e) = &name {
+        validator.check(Err(e.clone()));
+    }
+
+    let status = match raw.status.as_deref() {
+        None => Ok(ContainerStatus::Stopped),
+        Some(status) => status
+            .trim()
+            .to_ascii_lowercase()
+            .parse()
+            .map_err(|message: String| ValidationError::new("status", "INVALID_STATUS", message)),
+    };
+    if let Err(e) = &status {
+        validator.check(Err(e.clone()));
+    }
+
+    let template = raw.template.trim().to_string();
+    validator.check(validate_template(&template));
+
+    let mut parse_size = |field: &str, value: Option<&RawSize>| match value {
+        None => None,
+        Some(RawSize::Bytes(bytes)) => Some(*bytes),
+        Some(RawSize::Text(text)) => match size(field, text) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                validator.check(Err(e));
+                None
+            }
+        },
+    };
+    let memory_limit = parse_size("memory_limit", raw.memory_limit.as_ref());
+    let memory_request = parse_size("memory_request", raw.memory_request.as_ref());
+    let disk_limit = match &raw.disk_limit {
+        Some(RawSize::Text(text)) if text.trim().eq_ignore_ascii_case("unlimited") => {
+            Some(DiskLimit::Unlimited)
+        }
+        value => parse_size("disk_limit", value.as_ref()).map(DiskLimit::Bytes),
+    };
+
+    let config = ContainerConfig {
+        cpu_limit: raw.cpu_limit,
+        memory_limit,
+        cpu_request: raw.cpu_request,
+        memory_request,
+        disk_limit,
+        network_interfaces: raw.network_interfaces,
+        rootfs_path: raw.rootfs_path,
+        environment: raw.environment,
+        hostname: raw.hostname,
+    };
+    validator.check_all(validate_container_config(&config));
+
+    for (key, value) in &raw.labels {
+        validator.check(validate_label(key, value));
+    }
+    if let Some(description) = &raw.description {
+        validator.check(validate_description(description));
+    }
+
+    validator.finish()?;
+
+    let now = Utc::now();
+    let created_at = raw.created_at.unwrap_or(now);
+    Ok(Container {
+        id: raw.id.unwrap_or_else(Uuid::new_v4),
+        // Both are Ok: any error was returned by `finish` above
+        name: name.unwrap_or_else(|_| ContainerName::new_unchecked(String::new())),
+        status: status.unwrap_or(ContainerStatus::Stopped),
+        template,
+        node_id: raw.node_id,
+        created_at,
+        updated_at: raw.updated_at.unwrap_or(created_at),
+        config,
+        labels: raw.labels,
+        description: raw.description,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize_messy_import() {
+        let raw = RawContainer {
+            name: "  Web-1 ".to_string(),
+            status: Some("Running".to_string()),
+            template: "alpine".to_string(),
+            cpu_limit: Some(2),
+            memory_limit: Some("512MiB".into()),
+            disk_limit: Some("10G".into()),
+            rootfs_path: "/var/lib/lxc/web-1/rootfs".to_string(),
+            ..RawContainer::default()
+        };
+
+        let container = canonicalize_import(raw).unwrap();
+        assert_eq!(container.name, "web-1");
+        assert_eq!(container.status, ContainerStatus::Running);
+        assert_eq!(container.config.memory_limit, Some(512 * 1024 * 1024));
+        assert_eq!(
+            container.config.disk_limit,
+            Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024))
+        );
+        assert_eq!(container.updated_at, container.created_at);
+
+        let unlimited = canonicalize_import(RawContainer {
+            name: "db".to_string(),
+            template: "alpine".to_string(),
+            disk_limit: Some("Unlimited".into()),
+            rootfs_path: "/var/lib/lxc/db/rootfs".to_string(),
+            ..RawContainer::default()
+        })
+        .unwrap();
+        assert_eq!(unlimited.status, ContainerStatus::Stopped);
+        assert_eq!(unlimited.config.disk_limit, Some(DiskLimit::Unlimited));
+    }
+
+    #[test]
+    fn test_canonicalize_numeric_sizes() {
+        let raw: RawContainer = serde_json::from_value(serde_json::json!({
+            "name": "web",
+            "template": "alpine",
+            "memory_limit": 536870912,
+            "memory_request": "256MiB",
+            "disk_limit": 10737418240u64,
+            "rootfs_path": "/var/lib/lxc/web/rootfs"
+        }))
+        .unwrap();
+        assert_eq!(raw.memory_limit, Some(RawSize::Bytes(536870912)));
+
+        let container = canonicalize_import(raw).unwrap();
+        assert_eq!(container.config.memory_limit, Some(512 * 1024 * 1024));
+        assert_eq!(container.config.memory_request, Some(256 * 1024 * 1024));
+        assert_eq!(
+            container.config.disk_limit,
+            Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024))
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_reports_every_error() {
+        let raw = RawContainer {
+            name: "bad name!".to_string(),
+            status: Some("paused".to_string()),
+            template: "alpine".to_string(),
+            cpu_limit: Some(0),
+            memory_limit: Some("lots".into()),
+            rootfs_path: "/var/lib/lxc/web/rootfs".to_string(),
+            ..RawContainer::default()
+        };
+
+        let errors = canonicalize_import(raw).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        for field in ["name", "status", "cpu_limit", "memory_limit"] {
+            assert!(fields.contains(&field), "missing {} in {:?}", field, fields);
+        }
+    }
+}
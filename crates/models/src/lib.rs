@@ -1,19 +1,36 @@
 pub mod cluster;
 pub mod container;
+pub mod error;
+pub mod import;
+pub mod lint;
 pub mod network;
 pub mod node;
+pub mod schema;
 pub mod storage;
+pub mod template;
+pub mod units;
+pub mod validation;
 
 pub use cluster::*;
 pub use container::{
-    Container, ContainerConfig, ContainerListResponse, ContainerNetworkInterface,
-    ContainerResponse, ContainerStatus, CreateContainerRequest,
+    config_fingerprint, fnv1a, ConfigPatch, Container, ContainerConfig, ContainerEvent,
+    ContainerListResponse, ContainerName, ContainerNetworkInterface, ContainerResponse,
+    ContainerStatus, CreateContainerRequest, DiskLimit, LimitsView, REDACTED, SECRET_ENV_PATTERNS,
 };
+pub use error::{ErrorBody, ModelError};
+pub use import::{canonicalize_import, RawContainer, RawSize};
+pub use lint::{lint_config, LintSeverity, LintWarning};
 pub use network::{
     Bridge, CreateBridgeRequest, InterfaceStatus, InterfaceType, NetworkInterface,
     NetworkListResponse,
 };
-pub use node::{JoinClusterRequest, Node, NodeListResponse, NodeResources, NodeStatus};
+pub use node::{
+    HostResources, JoinClusterRequest, Node, NodeListResponse, NodeResources, NodeStatus,
+};
+pub use schema::container_config_schema;
 pub use storage::{
     CreateStoragePoolRequest, StoragePool, StoragePoolListResponse, StorageType, Volume,
 };
+pub use template::{Template, TemplateInfo};
+pub use units::{deserialize_optional_bytes, format_bytes, parse_bytes};
+pub use validation::*;
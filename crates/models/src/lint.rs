@@ -0,0 +1,173 @@
+use serde::Serialize;
+
+use crate::container::ContainerConfig;
+use crate::units::format_bytes;
+
+/// Memory per core above which a config looks CPU-starved
+pub const MAX_MEMORY_PER_CPU: u64 = 16 * 1024 * 1024 * 1024; // 16GB
+/// Memory per core below which a config looks memory-starved
+pub const MIN_MEMORY_PER_CPU: u64 = 128 * 1024 * 1024; // 128MB
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Info,
+    Warning,
+}
+
+/// A non-fatal advisory about a config that is valid but suspect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintWarning {
+    pub field: String,
+    pub code: &'static str,
+    pub severity: LintSeverity,
+    pub message: String,
+}
+
+impl LintWarning {
+    fn new(
+        field: &str,
+        code: &'static str,
+        severity: LintSeverity,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            field: field.to_string(),
+            code,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Advisories for a config; unlike `validate_*`, these never block creation
+pub fn lint_config(config: &ContainerConfig) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    if let (Some(cpus), Some(memory)) = (config.cpu_limit, config.memory_limit) {
+        let per_cpu = memory / u64::from(cpus.max(1));
+        if per_cpu > MAX_MEMORY_PER_CPU {
+            warnings.push(LintWarning::new(
+                "memory_limit",
+                "MEMORY_CPU_IMBALANCE",
+                LintSeverity::Warning,
+                format!(
+                    "{} bytes of memory for {} CPU(s) is unusually high; consider more CPUs",
+                    memory, cpus
+                ),
+            ));
+        } else if per_cpu < MIN_MEMORY_PER_CPU {
+            warnings.push(LintWarning::new(
+                "memory_limit",
+                "MEMORY_CPU_IMBALANCE",
+                LintSeverity::Warning,
+                format!(
+                    "{} bytes of memory for {} CPU(s) is unusually low; consider more memory",
+                    memory, cpus
+                ),
+            ));
+        }
+    }
+
+    if let (Some(memory), Some(disk)) = (
+        config.memory_limit,
+        config.disk_limit.and_then(|limit| limit.bytes()),
+    ) {
+        if disk < memory {
+            warnings.push(LintWarning::new(
+                "disk_limit",
+                "DISK_BELOW_MEMORY",
+                LintSeverity::Warning,
+                format!(
+                    "{} of disk cannot hold a snapshot of {} of memory",
+                    format_bytes(disk),
+                    format_bytes(memory)
+                ),
+            ));
+        }
+    }
+
+    if config.cpu_limit.is_none() && config.memory_limit.is_none() {
+        warnings.push(LintWarning::new(
+            "cpu_limit",
+            "NO_RESOURCE_LIMITS",
+            LintSeverity::Info,
+            "Neither CPU nor memory is limited",
+        ));
+    }
+
+    if config.network_interfaces.is_empty() {
+        warnings.push(LintWarning::new(
+            "network_interfaces",
+            "NO_NETWORK",
+            LintSeverity::Info,
+            "Container has no network interfaces",
+        ));
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::ContainerNetworkInterface;
+
+    fn balanced_config() -> ContainerConfig {
+        ContainerConfig {
+            cpu_limit: Some(2),
+            memory_limit: Some(2 * 1024 * 1024 * 1024),
+            disk_limit: Some(crate::container::DiskLimit::Bytes(10 * 1024 * 1024 * 1024)),
+            network_interfaces: vec![ContainerNetworkInterface {
+                name: "eth0".to_string(),
+                bridge: "lxcbr0".to_string(),
+                ipv4: None,
+                ipv6: None,
+                mac: None,
+            }],
+            rootfs_path: "/var/lib/lxc/web/rootfs".to_string(),
+            environment: vec![],
+            hostname: None,
+            cpu_request: None,
+            memory_request: None,
+        }
+    }
+
+    #[test]
+    fn test_balanced_config_has_no_warnings() {
+        assert!(lint_config(&balanced_config()).is_empty());
+    }
+
+    #[test]
+    fn test_disk_below_memory() {
+        let mut config = balanced_config();
+        config.disk_limit = Some(crate::container::DiskLimit::Bytes(1024 * 1024 * 1024));
+        let warnings = lint_config(&config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "DISK_BELOW_MEMORY");
+    }
+
+    #[test]
+    fn test_memory_cpu_imbalance() {
+        let mut config = balanced_config();
+        config.cpu_limit = Some(1);
+        config.memory_limit = Some(64 * 1024 * 1024 * 1024);
+        config.disk_limit = Some(crate::container::DiskLimit::Unlimited);
+
+        let warnings = lint_config(&config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "MEMORY_CPU_IMBALANCE");
+        assert_eq!(warnings[0].severity, LintSeverity::Warning);
+    }
+
+    #[test]
+    fn test_missing_network_is_informational() {
+        let mut config = balanced_config();
+        config.network_interfaces.clear();
+
+        let warnings = lint_config(&config);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, "NO_NETWORK");
+        assert_eq!(warnings[0].severity, LintSeverity::Info);
+    }
+}
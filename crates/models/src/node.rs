@@ -33,6 +33,25 @@ pub struct NodeResources {
     pub disk_used: u64,    // in bytes
 }
 
+impl NodeResources {
+    /// What is still free on the node, for `fits_host`
+    pub fn available(&self) -> HostResources {
+        HostResources {
+            cpu_cores: self.cpu_cores,
+            memory_bytes: self.memory_total.saturating_sub(self.memory_used),
+            disk_bytes: self.disk_total.saturating_sub(self.disk_used),
+        }
+    }
+}
+
+/// Capacity a host can give a single container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostResources {
+    pub cpu_cores: u32,
+    pub memory_bytes: u64,
+    pub disk_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeListResponse {
     pub nodes: Vec<Node>,
@@ -0,0 +1,155 @@
+use serde_json::{json, Value};
+
+use crate::validation::{
+    MAX_CPU_LIMIT, MAX_DISK_LIMIT, MAX_ENVIRONMENT_VARS, MAX_HOSTNAME_LEN, MAX_INTERFACE_NAME_LEN,
+    MAX_MEMORY_LIMIT, MAX_NETWORK_INTERFACES, MIN_CPU_LIMIT, MIN_DISK_LIMIT, MIN_MEMORY_LIMIT,
+};
+
+/// Sizes as text, in the forms `parse_bytes` accepts, e.g. `512M` or `10GiB`
+const SIZE_PATTERN: &str = r"^\s*[0-9]+\s*([bB]|[kKmMgGtT]([iI][bB])?)?\s*$";
+
+/// JSON Schema (draft-07) for `ContainerConfig`
+///
+/// Bounds come from the same constants the validators use, so the schema
+/// cannot drift from what `validate_container_config` accepts. Sizes may be
+/// byte counts or text; text sizes are only bounds-checked after parsing.
+pub fn container_config_schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "ContainerConfig",
+        "type": "object",
+        "required": ["rootfs_path"],
+        "properties": {
+            "cpu_limit": {
+                "type": ["integer", "null"],
+                "minimum": MIN_CPU_LIMIT,
+                "maximum": MAX_CPU_LIMIT
+            },
+            "memory_limit": {
+                "description": "Memory limit in bytes, or a size such as \"512MiB\"",
+                "oneOf": [
+                    {
+                        "type": "integer",
+                        "minimum": MIN_MEMORY_LIMIT,
+                        "maximum": MAX_MEMORY_LIMIT
+                    },
+                    { "type": "string", "pattern": SIZE_PATTERN },
+                    { "type": "null" }
+                ]
+            },
+            "cpu_request": {
+                "description": "CPUs reserved for scheduling, at most cpu_limit",
+                "type": ["integer", "null"],
+                "minimum": MIN_CPU_LIMIT,
+                "maximum": MAX_CPU_LIMIT
+            },
+            "memory_request": {
+                "description": "Memory reserved for scheduling, at most memory_limit",
+                "oneOf": [
+                    {
+                        "type": "integer",
+                        "minimum": MIN_MEMORY_LIMIT,
+                        "maximum": MAX_MEMORY_LIMIT
+                    },
+                    { "type": "string", "pattern": SIZE_PATTERN },
+                    { "type": "null" }
+                ]
+            },
+            "disk_limit": {
+                "description": "Disk limit in bytes, a size such as \"10GiB\", or \"unlimited\"",
+                "oneOf": [
+                    {
+                        "type": "integer",
+                        "minimum": MIN_DISK_LIMIT,
+                        "maximum": MAX_DISK_LIMIT
+                    },
+                    { "type": "string", "pattern": SIZE_PATTERN },
+                    { "const": "unlimited" },
+                    { "type": "null" }
+                ]
+            },
+            "network_interfaces": {
+                "type": "array",
+                "maxItems": MAX_NETWORK_INTERFACES,
+                "items": {
+                    "type": "object",
+                    "required": ["name", "bridge"],
+                    "properties": {
+                        "name": {
+                            "type": "string",
+                            "minLength": 1,
+                            "maxLength": MAX_INTERFACE_NAME_LEN
+                        },
+                        "bridge": { "type": "string", "minLength": 1 },
+                        "ipv4": { "type": ["string", "null"] },
+                        "ipv6": { "type": ["string", "null"] },
+                        "mac": { "type": ["string", "null"] }
+                    }
+                }
+            },
+            "rootfs_path": {
+                "type": "string",
+                "pattern": "^/"
+            },
+            "environment": {
+                "description": "KEY, value pairs",
+                "type": "array",
+                "maxItems": MAX_ENVIRONMENT_VARS,
+                "items": {
+                    "type": "array",
+                    "items": [{ "type": "string" }, { "type": "string" }],
+                    "minItems": 2,
+                    "maxItems": 2
+                }
+            },
+            "hostname": {
+                "description": "RFC 1123 hostname; defaults to the container name",
+                "type": ["string", "null"],
+                "maxLength": MAX_HOSTNAME_LEN
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::container::ContainerConfig;
+
+    #[test]
+    fn test_schema_reflects_validation_bounds() {
+        let schema = container_config_schema();
+
+        let cpu = &schema["properties"]["cpu_limit"];
+        assert_eq!(cpu["minimum"], json!(MIN_CPU_LIMIT));
+        assert_eq!(cpu["maximum"], json!(MAX_CPU_LIMIT));
+        assert_eq!(
+            schema["properties"]["disk_limit"]["oneOf"][0]["minimum"],
+            json!(MIN_DISK_LIMIT)
+        );
+
+        assert_eq!(schema["required"], json!(["rootfs_path"]));
+    }
+
+    #[test]
+    fn test_schema_allows_text_sizes_serde_accepts() {
+        let schema = container_config_schema();
+        let config: ContainerConfig = serde_json::from_value(json!({
+            "rootfs_path": "/r",
+            "memory_limit": "512MiB",
+            "memory_request": "256M",
+            "disk_limit": "10G"
+        }))
+        .unwrap();
+        assert_eq!(config.memory_limit, Some(512 << 20));
+
+        for field in ["memory_limit", "memory_request", "disk_limit"] {
+            let forms = schema["properties"][field]["oneOf"].as_array().unwrap();
+            assert!(
+                forms.contains(&json!({ "type": "string", "pattern": SIZE_PATTERN })),
+                "{} has no text form",
+                field
+            );
+        }
+    }
+}
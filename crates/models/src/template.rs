@@ -0,0 +1,105 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::validation::{validate_template, ValidationError};
+
+/// A template known to the registry, with its provisioning requirements
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    pub name: String,
+    /// Smallest disk the template's rootfs fits on, in bytes
+    pub min_disk_bytes: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A template name that has passed `validate_template`
+///
+/// The only ways in are `TryFrom` and deserializing, both of which validate,
+/// so holding one means the name is well-formed. Serialized as a plain string.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Template(String);
+
+impl Template {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<&str> for Template {
+    type Error = ValidationError;
+
+    fn try_from(template: &str) -> Result<Self, Self::Error> {
+        validate_template(template)?;
+        Ok(Template(template.to_string()))
+    }
+}
+
+impl TryFrom<String> for Template {
+    type Error = ValidationError;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        validate_template(&template)?;
+        Ok(Template(template))
+    }
+}
+
+impl From<Template> for String {
+    fn from(template: Template) -> Self {
+        template.0
+    }
+}
+
+impl AsRef<str> for Template {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for Template {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Template {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl fmt::Display for Template {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_try_from() {
+        let template = Template::try_from("alpine").unwrap();
+        assert_eq!(template.as_str(), "alpine");
+        assert_eq!(template.to_string(), "alpine");
+
+        let err = Template::try_from("Alpine").unwrap_err();
+        assert_eq!(err.field, "template");
+        assert_eq!(err.code, "TEMPLATE_INVALID_START");
+    }
+
+    #[test]
+    fn test_template_deserialize_validates() {
+        let template: Template = serde_json::from_str("\"ubuntu-22.04\"").unwrap();
+        assert_eq!(template, "ubuntu-22.04");
+        assert_eq!(
+            serde_json::to_string(&template).unwrap(),
+            "\"ubuntu-22.04\""
+        );
+
+        assert!(serde_json::from_str::<Template>("\"Alpine\"").is_err());
+    }
+}
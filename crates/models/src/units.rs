@@ -0,0 +1,126 @@
+use serde::{Deserialize, Deserializer};
+
+use crate::validation::ValidationError;
+
+const UNITS: &[(&str, u32)] = &[("TiB", 40), ("GiB", 30), ("MiB", 20), ("KiB", 10)];
+
+/// Parse a byte count with an optional binary suffix
+///
+/// `512M`, `512MiB` and `536870912` are the same size. Suffixes are
+/// case-insensitive; `K`/`M`/`G`/`T` always mean powers of 1024.
+pub fn parse_bytes(s: &str) -> Result<u64, ValidationError> {
+    let invalid = || {
+        ValidationError::new(
+            "size",
+            "INVALID_SIZE",
+            format!(
+                "Invalid size '{}', expected e.g. 512M, 5GiB or a byte count",
+                s
+            ),
+        )
+    };
+
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let shift = match suffix.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KIB" => 10,
+        "M" | "MIB" => 20,
+        "G" | "GIB" => 30,
+        "T" | "TIB" => 40,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Format a byte count in the largest binary unit it fills, e.g. `512MiB`
+///
+/// Whole amounts print without decimals; others get one, so `1.5GiB`.
+pub fn format_bytes(n: u64) -> String {
+    for (unit, shift) in UNITS {
+        let size = 1u64 << shift;
+        if n >= size {
+            return if n.is_multiple_of(size) {
+                format!("{}{}", n / size, unit)
+            } else {
+                format!("{:.1}{}", n as f64 / size as f64, unit)
+            };
+        }
+    }
+    format!("{}B", n)
+}
+
+/// `deserialize_with` helper for optional sizes written as bytes or text
+///
+/// Accepts an integer byte count, anything `parse_bytes` understands such
+/// as `"512MiB"`, or null. Serialization is untouched and stays numeric.
+pub fn deserialize_optional_bytes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<Repr>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Repr::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Repr::Text(text)) => parse_bytes(&text)
+            .map(Some)
+            .map_err(|e| serde::de::Error::custom(e.message)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("512MiB").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_bytes("5G").unwrap(), 5 * 1024 * 1024 * 1024);
+        assert_eq!(parse_bytes("4096").unwrap(), 4096);
+        assert_eq!(parse_bytes("2k").unwrap(), 2048);
+
+        for bad in ["5X", "MiB", "", "-1G", "99999999999T"] {
+            let err = parse_bytes(bad).unwrap_err();
+            assert_eq!(err.code, "INVALID_SIZE", "{:?}", bad);
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct SizeField {
+        #[serde(default, deserialize_with = "deserialize_optional_bytes")]
+        size: Option<u64>,
+    }
+
+    #[test]
+    fn test_deserialize_optional_bytes() {
+        let parse = |json: &str| serde_json::from_str::<SizeField>(json).map(|s| s.size);
+        assert_eq!(parse(r#"{"size": 512}"#).unwrap(), Some(512));
+        assert_eq!(
+            parse(r#"{"size": "512MiB"}"#).unwrap(),
+            Some(512 * 1024 * 1024)
+        );
+        assert_eq!(parse(r#"{"size": null}"#).unwrap(), None);
+        assert_eq!(parse("{}").unwrap(), None);
+
+        let err = parse(r#"{"size": "abc"}"#).unwrap_err();
+        assert!(err.to_string().contains("Invalid size 'abc'"), "{}", err);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512 * 1024 * 1024), "512MiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024 / 2), "1.5GiB");
+        assert_eq!(format_bytes(1024), "1KiB");
+        assert_eq!(format_bytes(100), "100B");
+        assert_eq!(parse_bytes(&format_bytes(10 << 30)).unwrap(), 10 << 30);
+    }
+}
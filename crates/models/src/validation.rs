@@ -0,0 +1,1336 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::container::{ContainerConfig, ContainerNetworkInterface, DiskLimit};
+use crate::node::HostResources;
+use crate::units::format_bytes;
+
+pub const MAX_CONTAINER_NAME_LEN: usize = 64;
+pub const MAX_TEMPLATE_LEN: usize = 64;
+pub const MIN_CPU_LIMIT: u32 = 1;
+pub const MAX_CPU_LIMIT: u32 = 128;
+pub const MIN_MEMORY_LIMIT: u64 = 32 * 1024 * 1024; // 32MB
+pub const MAX_MEMORY_LIMIT: u64 = 1024 * 1024 * 1024 * 1024; // 1TB
+pub const MIN_DISK_LIMIT: u64 = 100 * 1024 * 1024; // 100MB
+pub const MAX_DISK_LIMIT: u64 = 16 * 1024 * 1024 * 1024 * 1024; // 16TB
+pub const MAX_INTERFACE_NAME_LEN: usize = 15; // IFNAMSIZ - 1
+pub const MAX_LABEL_KEY_LEN: usize = 63;
+pub const MAX_LABEL_VALUE_LEN: usize = 63;
+pub const MAX_DESCRIPTION_LEN: usize = 256;
+pub const MAX_REASON_LEN: usize = 1024;
+pub const MAX_CONFIG_BYTES: usize = 64 * 1024; // 64KB of serialized JSON
+pub const MAX_ENVIRONMENT_VARS: usize = 256;
+pub const MAX_NETWORK_INTERFACES: usize = 16;
+pub const MAX_HOSTNAME_LEN: usize = 253;
+pub const MAX_HOSTNAME_LABEL_LEN: usize = 63;
+
+/// A single field that failed validation
+///
+/// `code` is a stable identifier (e.g. `NAME_TOO_LONG`) for clients to branch
+/// on; `message` is the human-readable default. Codes are borrowed when
+/// built here and owned when deserialized from a response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationError {
+    pub field: String,
+    pub code: Cow<'static, str>,
+    pub message: String,
+}
+
+impl ValidationError {
+    pub fn new(field: impl Into<String>, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            code: Cow::Borrowed(code),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// A list of validation failures, displayed as one `; `-separated line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl From<Vec<ValidationError>> for ValidationErrors {
+    fn from(errors: Vec<ValidationError>) -> Self {
+        Self(errors)
+    }
+}
+
+/// Collects the results of several checks so every failure is reported at once
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<ValidationError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, result: Result<(), ValidationError>) -> &mut Self {
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+        self
+    }
+
+    /// Merge the errors of an aggregate validator
+    pub fn check_all(&mut self, result: Result<(), Vec<ValidationError>>) -> &mut Self {
+        if let Err(errors) = result {
+            self.errors.extend(errors);
+        }
+        self
+    }
+
+    pub fn finish(self) -> Result<(), Vec<ValidationError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+}
+
+/// Which container names a deployment accepts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePolicy {
+    pub max_len: usize,
+    pub allow_uppercase: bool,
+    pub allow_dots: bool,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        Self {
+            max_len: MAX_CONTAINER_NAME_LEN,
+            allow_uppercase: false,
+            allow_dots: false,
+        }
+    }
+}
+
+impl NamePolicy {
+    /// A single DNS label: at most 63 characters
+    pub fn dns_label() -> Self {
+        Self {
+            max_len: 63,
+            ..Self::default()
+        }
+    }
+
+    fn allows(&self, c: char) -> bool {
+        c.is_ascii_lowercase()
+            || c.is_ascii_digit()
+            || c == '-'
+            || (self.allow_uppercase && c.is_ascii_uppercase())
+            || (self.allow_dots && c == '.')
+    }
+}
+
+/// Lowercase letters, digits and hyphens; must start with a letter or digit
+pub fn validate_container_name(name: &str) -> Result<(), ValidationError> {
+    validate_container_name_with(name, &NamePolicy::default())
+}
+
+/// `validate_container_name` under a deployment-specific policy
+pub fn validate_container_name_with(
+    name: &str,
+    policy: &NamePolicy,
+) -> Result<(), ValidationError> {
+    if name.is_empty() {
+        return Err(ValidationError::new(
+            "name",
+            "NAME_EMPTY",
+            "Container name cannot be empty",
+        ));
+    }
+    if name.len() > policy.max_len {
+        return Err(ValidationError::new(
+            "name",
+            "NAME_TOO_LONG",
+            format!(
+                "Container name must be at most {} characters",
+                policy.max_len
+            ),
+        ));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphanumeric() && policy.allows(c)) {
+        return Err(ValidationError::new(
+            "name",
+            "NAME_INVALID_START",
+            if policy.allow_uppercase {
+                "Container name must start with a letter or digit"
+            } else {
+                "Container name must start with a lowercase letter or digit"
+            },
+        ));
+    }
+    if name.ends_with('-') {
+        return Err(ValidationError::new(
+            "name",
+            "NAME_INVALID_END",
+            "Container name cannot end with a hyphen",
+        ));
+    }
+    if policy.allow_dots && name.ends_with('.') {
+        return Err(ValidationError::new(
+            "name",
+            "NAME_INVALID_END",
+            "Container name cannot end with a dot",
+        ));
+    }
+    if !name.chars().all(|c| policy.allows(c)) {
+        let dots = if policy.allow_dots { ", dots" } else { "" };
+        return Err(ValidationError::new(
+            "name",
+            "NAME_INVALID_CHARS",
+            format!(
+                "Container name may only contain {}, digits{} and hyphens",
+                if policy.allow_uppercase {
+                    "letters"
+                } else {
+                    "lowercase letters"
+                },
+                dots
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Template names like `alpine` or `ubuntu-22.04`
+pub fn validate_template(template: &str) -> Result<(), ValidationError> {
+    if template.is_empty() {
+        return Err(ValidationError::new(
+            "template",
+            "TEMPLATE_EMPTY",
+            "Template cannot be empty",
+        ));
+    }
+    if template.len() > MAX_TEMPLATE_LEN {
+        return Err(ValidationError::new(
+            "template",
+            "TEMPLATE_TOO_LONG",
+            format!("Template must be at most {} characters", MAX_TEMPLATE_LEN),
+        ));
+    }
+    if !template.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit()) {
+        return Err(ValidationError::new(
+            "template",
+            "TEMPLATE_INVALID_START",
+            "Template must start with a lowercase letter or digit",
+        ));
+    }
+    if !template
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(ValidationError::new(
+            "template",
+            "TEMPLATE_INVALID_CHARS",
+            "Template may only contain lowercase letters, digits, '-', '_' and '.'",
+        ));
+    }
+    Ok(())
+}
+
+/// For deployments with a fixed catalog: the template must be one of `allowed`
+///
+/// The charset check from `validate_template` runs first.
+pub fn validate_template_against(template: &str, allowed: &[&str]) -> Result<(), ValidationError> {
+    validate_template(template)?;
+    if !allowed.contains(&template) {
+        return Err(ValidationError::new(
+            "template",
+            "TEMPLATE_UNKNOWN",
+            format!(
+                "Unknown template '{}', expected one of: {}",
+                template,
+                allowed.join(", ")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_cpu_limit(cpu_limit: u32) -> Result<(), ValidationError> {
+    if !(MIN_CPU_LIMIT..=MAX_CPU_LIMIT).contains(&cpu_limit) {
+        return Err(ValidationError::new(
+            "cpu_limit",
+            "CPU_OUT_OF_RANGE",
+            format!(
+                "CPU limit must be between {} and {} cores",
+                MIN_CPU_LIMIT, MAX_CPU_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_memory_limit(memory_limit: u64) -> Result<(), ValidationError> {
+    if !(MIN_MEMORY_LIMIT..=MAX_MEMORY_LIMIT).contains(&memory_limit) {
+        return Err(ValidationError::new(
+            "memory_limit",
+            "MEMORY_OUT_OF_RANGE",
+            format!(
+                "Memory limit must be between {} and {} bytes",
+                MIN_MEMORY_LIMIT, MAX_MEMORY_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Requests share the bounds of the matching limit, set or not
+pub fn validate_cpu_request(cpu_request: u32) -> Result<(), ValidationError> {
+    if !(MIN_CPU_LIMIT..=MAX_CPU_LIMIT).contains(&cpu_request) {
+        return Err(ValidationError::new(
+            "cpu_request",
+            "CPU_OUT_OF_RANGE",
+            format!(
+                "CPU request must be between {} and {} cores",
+                MIN_CPU_LIMIT, MAX_CPU_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_memory_request(memory_request: u64) -> Result<(), ValidationError> {
+    if !(MIN_MEMORY_LIMIT..=MAX_MEMORY_LIMIT).contains(&memory_request) {
+        return Err(ValidationError::new(
+            "memory_request",
+            "MEMORY_OUT_OF_RANGE",
+            format!(
+                "Memory request must be between {} and {} bytes",
+                MIN_MEMORY_LIMIT, MAX_MEMORY_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// `Unlimited` is always accepted; byte counts must be in range
+pub fn validate_disk_limit(disk_limit: &DiskLimit) -> Result<(), ValidationError> {
+    let DiskLimit::Bytes(bytes) = disk_limit else {
+        return Ok(());
+    };
+    if !(MIN_DISK_LIMIT..=MAX_DISK_LIMIT).contains(bytes) {
+        return Err(ValidationError::new(
+            "disk_limit",
+            "DISK_OUT_OF_RANGE",
+            format!(
+                "Disk limit must be between {} and {} bytes",
+                MIN_DISK_LIMIT, MAX_DISK_LIMIT
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// A disk limit must leave room for the template's rootfs
+///
+/// Unset and `Unlimited` limits always fit.
+pub fn validate_disk_for_template(
+    disk_limit: Option<DiskLimit>,
+    template_min: u64,
+) -> Result<(), ValidationError> {
+    match disk_limit.and_then(|limit| limit.bytes()) {
+        Some(disk_limit) if disk_limit < template_min => Err(ValidationError::new(
+            "disk_limit",
+            "DISK_BELOW_TEMPLATE_MIN",
+            format!(
+                "Disk limit of {} bytes is below the template minimum of {} bytes",
+                disk_limit, template_min
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Whether `host` can satisfy a config's limits
+///
+/// Reports the first resource that falls short. Unset limits and an
+/// `Unlimited` disk are not checked.
+pub fn fits_host(config: &ContainerConfig, host: &HostResources) -> Result<(), ValidationError> {
+    if let Some(cpu_limit) = config.cpu_limit {
+        if cpu_limit > host.cpu_cores {
+            return Err(ValidationError::new(
+                "cpu_limit",
+                "CPU_EXCEEDS_HOST",
+                format!(
+                    "{} CPUs requested, the host has {}",
+                    cpu_limit, host.cpu_cores
+                ),
+            ));
+        }
+    }
+    if let Some(memory_limit) = config.memory_limit {
+        if memory_limit > host.memory_bytes {
+            return Err(ValidationError::new(
+                "memory_limit",
+                "MEMORY_EXCEEDS_HOST",
+                format!(
+                    "{} of memory requested, the host has {} available",
+                    format_bytes(memory_limit),
+                    format_bytes(host.memory_bytes)
+                ),
+            ));
+        }
+    }
+    if let Some(disk_limit) = config.disk_limit.and_then(|limit| limit.bytes()) {
+        if disk_limit > host.disk_bytes {
+            return Err(ValidationError::new(
+                "disk_limit",
+                "DISK_EXCEEDS_HOST",
+                format!(
+                    "{} of disk requested, the host has {} available",
+                    format_bytes(disk_limit),
+                    format_bytes(host.disk_bytes)
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Disk must hold at least the container's memory, or a memory-backed
+/// snapshot (or swap out to the rootfs) will not fit
+pub fn validate_memory_disk_relationship(memory: u64, disk: u64) -> Result<(), ValidationError> {
+    if disk < memory {
+        return Err(ValidationError::new(
+            "disk_limit",
+            "DISK_BELOW_MEMORY",
+            format!(
+                "{} of disk is less than the {} of memory",
+                format_bytes(disk),
+                format_bytes(memory)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// A resource request may not exceed the limit it can burst up to
+///
+/// `field` names the request field the error is reported against.
+pub fn validate_request_within_limit(
+    field: &str,
+    request: u64,
+    limit: u64,
+) -> Result<(), ValidationError> {
+    if request > limit {
+        return Err(ValidationError::new(
+            field,
+            "REQUEST_EXCEEDS_LIMIT",
+            format!("Request of {} exceeds the limit of {}", request, limit),
+        ));
+    }
+    Ok(())
+}
+
+/// How `validate_container_config_with` treats disk smaller than memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryDiskPolicy {
+    /// Reject the config
+    #[default]
+    Error,
+    /// Accept it; `lint_config` still reports `DISK_BELOW_MEMORY`
+    Warn,
+}
+
+/// Whether a node holding `current` containers may take another
+pub fn check_node_container_count(current: i64, max: i64) -> Result<(), ValidationError> {
+    if current >= max {
+        return Err(ValidationError::new(
+            "node_id",
+            "NODE_FULL",
+            format!(
+                "Node already holds {} containers, the limit is {}",
+                current, max
+            ),
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_rootfs_path(path: &str) -> Result<(), ValidationError> {
+    if !path.starts_with('/') {
+        return Err(ValidationError::new(
+            "rootfs_path",
+            "ROOTFS_NOT_ABSOLUTE",
+            "Root filesystem path must be absolute",
+        ));
+    }
+    if path.split('/').any(|segment| segment == "..") {
+        return Err(ValidationError::new(
+            "rootfs_path",
+            "ROOTFS_PATH_TRAVERSAL",
+            "Root filesystem path cannot contain '..'",
+        ));
+    }
+    Ok(())
+}
+
+/// Which environment variable names a deployment accepts
+///
+/// The default denies nothing and allows everything. `deny` wins over
+/// `allow` when a key appears in both.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvPolicy {
+    pub deny: Vec<String>,
+    /// When set, only these keys are accepted
+    pub allow: Option<Vec<String>>,
+}
+
+/// Keys must be valid shell identifiers; values cannot contain NUL bytes
+pub fn validate_environment(environment: &[(String, String)]) -> Result<(), ValidationError> {
+    validate_environment_with(environment, &EnvPolicy::default())
+}
+
+/// `validate_environment` under a deployment-specific policy
+pub fn validate_environment_with(
+    environment: &[(String, String)],
+    policy: &EnvPolicy,
+) -> Result<(), ValidationError> {
+    for (key, value) in environment {
+        let valid_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(ValidationError::new(
+                "environment",
+                "ENV_INVALID_KEY",
+                format!("Invalid environment variable name: '{}'", key),
+            ));
+        }
+        if value.contains('\0') {
+            return Err(ValidationError::new(
+                "environment",
+                "ENV_INVALID_VALUE",
+                format!("Environment variable {} contains a NUL byte", key),
+            ));
+        }
+        if policy.deny.contains(key) {
+            return Err(ValidationError::new(
+                "environment",
+                "ENV_DENIED",
+                format!("Environment variable {} is not permitted", key),
+            ));
+        }
+        if let Some(allow) = &policy.allow {
+            if !allow.contains(key) {
+                return Err(ValidationError::new(
+                    "environment",
+                    "ENV_NOT_ALLOWED",
+                    format!("Environment variable {} is not in the allowlist", key),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// RFC 1123 hostname: dot-separated labels of letters, digits and hyphens
+///
+/// Each label is 1-63 characters and cannot start or end with a hyphen; the
+/// whole name is at most 253 characters.
+pub fn validate_hostname(hostname: &str) -> Result<(), ValidationError> {
+    if hostname.is_empty() {
+        return Err(ValidationError::new(
+            "hostname",
+            "HOSTNAME_EMPTY",
+            "Hostname cannot be empty",
+        ));
+    }
+    if hostname.len() > MAX_HOSTNAME_LEN {
+        return Err(ValidationError::new(
+            "hostname",
+            "HOSTNAME_TOO_LONG",
+            format!("Hostname must be at most {} characters", MAX_HOSTNAME_LEN),
+        ));
+    }
+    for label in hostname.split('.') {
+        if label.is_empty() || label.len() > MAX_HOSTNAME_LABEL_LEN {
+            return Err(ValidationError::new(
+                "hostname",
+                "HOSTNAME_INVALID_LABEL",
+                format!(
+                    "Hostname labels must be 1-{} characters: '{}'",
+                    MAX_HOSTNAME_LABEL_LEN, label
+                ),
+            ));
+        }
+        if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            return Err(ValidationError::new(
+                "hostname",
+                "HOSTNAME_INVALID_CHARS",
+                format!(
+                    "Hostname labels may contain only letters, digits and hyphens: '{}'",
+                    label
+                ),
+            ));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(ValidationError::new(
+                "hostname",
+                "HOSTNAME_INVALID_HYPHEN",
+                format!("Hostname labels cannot start or end with '-': '{}'", label),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Apply `overrides` on top of `base`, key by key
+///
+/// Keys already in `base` keep their position and take the override value;
+/// new keys are appended in the order given. When a key repeats, the last
+/// value wins. Both sides are validated first.
+pub fn merge_environment(
+    base: &[(String, String)],
+    overrides: &[(String, String)],
+) -> Result<Vec<(String, String)>, ValidationError> {
+    validate_environment(base)?;
+    validate_environment(overrides)?;
+
+    let mut merged: Vec<(String, String)> = Vec::with_capacity(base.len() + overrides.len());
+    for (key, value) in base.iter().chain(overrides) {
+        match merged.iter_mut().find(|(existing, _)| existing == key) {
+            Some(entry) => entry.1 = value.clone(),
+            None => merged.push((key.clone(), value.clone())),
+        }
+    }
+    Ok(merged)
+}
+
+pub fn validate_description(description: &str) -> Result<(), ValidationError> {
+    if description.chars().count() > MAX_DESCRIPTION_LEN {
+        return Err(ValidationError::new(
+            "description",
+            "DESCRIPTION_TOO_LONG",
+            format!(
+                "Description must be at most {} characters",
+                MAX_DESCRIPTION_LEN
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Free-text reason attached to an error or other event
+pub fn validate_reason(reason: &str) -> Result<(), ValidationError> {
+    if reason.trim().is_empty() {
+        return Err(ValidationError::new(
+            "reason",
+            "REASON_EMPTY",
+            "Reason cannot be empty",
+        ));
+    }
+    if reason.chars().count() > MAX_REASON_LEN {
+        return Err(ValidationError::new(
+            "reason",
+            "REASON_TOO_LONG",
+            format!("Reason must be at most {} characters", MAX_REASON_LEN),
+        ));
+    }
+    Ok(())
+}
+
+/// Label keys and values: alphanumerics plus `-`, `_` and `.`, keys may also use `/`
+pub fn validate_label(key: &str, value: &str) -> Result<(), ValidationError> {
+    let valid_key = !key.is_empty()
+        && key.len() <= MAX_LABEL_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'));
+    if !valid_key {
+        return Err(ValidationError::new(
+            "labels",
+            "LABEL_INVALID_KEY",
+            format!("Invalid label key: '{}'", key),
+        ));
+    }
+
+    let valid_value = value.len() <= MAX_LABEL_VALUE_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_value {
+        return Err(ValidationError::new(
+            "labels",
+            "LABEL_INVALID_VALUE",
+            format!("Invalid value for label {}: '{}'", key, value),
+        ));
+    }
+    Ok(())
+}
+
+/// Parse an IPv4 or IPv6 address, without a prefix length
+pub fn validate_ip_address(ip: &str) -> Result<IpAddr, ValidationError> {
+    ip.parse().map_err(|_| {
+        ValidationError::new("ip", "IP_INVALID", format!("{:?} is not an IP address", ip))
+    })
+}
+
+pub fn validate_network_interface(
+    interface: &ContainerNetworkInterface,
+) -> Result<(), ValidationError> {
+    if interface.name.is_empty() || interface.name.len() > MAX_INTERFACE_NAME_LEN {
+        return Err(ValidationError::new(
+            "network_interfaces",
+            "INTERFACE_INVALID_NAME",
+            format!(
+                "Interface name must be 1-{} characters",
+                MAX_INTERFACE_NAME_LEN
+            ),
+        ));
+    }
+    if interface.bridge.is_empty() {
+        return Err(ValidationError::new(
+            "network_interfaces",
+            "INTERFACE_MISSING_BRIDGE",
+            format!("Interface {} has no bridge", interface.name),
+        ));
+    }
+    Ok(())
+}
+
+/// Reject configs whose serialized JSON exceeds `MAX_CONFIG_BYTES`
+pub fn validate_config_size(config: &ContainerConfig) -> Result<(), ValidationError> {
+    validate_config_size_with(config, MAX_CONFIG_BYTES)
+}
+
+/// Reject oversized lists and configs whose serialized JSON exceeds `max_bytes`
+pub fn validate_config_size_with(
+    config: &ContainerConfig,
+    max_bytes: usize,
+) -> Result<(), ValidationError> {
+    if config.environment.len() > MAX_ENVIRONMENT_VARS {
+        return Err(ValidationError::new(
+            "environment",
+            "TOO_MANY_ENV_VARS",
+            format!(
+                "At most {} environment variables are allowed",
+                MAX_ENVIRONMENT_VARS
+            ),
+        ));
+    }
+    if config.network_interfaces.len() > MAX_NETWORK_INTERFACES {
+        return Err(ValidationError::new(
+            "network_interfaces",
+            "TOO_MANY_INTERFACES",
+            format!(
+                "At most {} network interfaces are allowed",
+                MAX_NETWORK_INTERFACES
+            ),
+        ));
+    }
+
+    let size = serde_json::to_vec(config)
+        .map(|json| json.len())
+        .unwrap_or(usize::MAX);
+    if size > max_bytes {
+        return Err(ValidationError::new(
+            "config",
+            "CONFIG_TOO_LARGE",
+            format!(
+                "Config is {} bytes, the maximum is {} bytes",
+                size, max_bytes
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Run every field check on a config and report all failures together
+pub fn validate_container_config(config: &ContainerConfig) -> Result<(), Vec<ValidationError>> {
+    validate_container_config_with(config, MemoryDiskPolicy::default())
+}
+
+/// `validate_container_config` with a chosen memory/disk policy
+pub fn validate_container_config_with(
+    config: &ContainerConfig,
+    memory_disk: MemoryDiskPolicy,
+) -> Result<(), Vec<ValidationError>> {
+    let mut validator = Validator::new();
+
+    validator.check(validate_config_size(config));
+    if let Some(cpu_limit) = config.cpu_limit {
+        validator.check(validate_cpu_limit(cpu_limit));
+    }
+    if let Some(memory_limit) = config.memory_limit {
+        validator.check(validate_memory_limit(memory_limit));
+    }
+    if let Some(cpu_request) = config.cpu_request {
+        validator.check(validate_cpu_request(cpu_request));
+    }
+    if let Some(memory_request) = config.memory_request {
+        validator.check(validate_memory_request(memory_request));
+    }
+    if let (Some(request), Some(limit)) = (config.cpu_request, config.cpu_limit) {
+        validator.check(validate_request_within_limit(
+            "cpu_request",
+            request.into(),
+            limit.into(),
+        ));
+    }
+    if let (Some(request), Some(limit)) = (config.memory_request, config.memory_limit) {
+        validator.check(validate_request_within_limit(
+            "memory_request",
+            request,
+            limit,
+        ));
+    }
+    if let Some(disk_limit) = &config.disk_limit {
+        validator.check(validate_disk_limit(disk_limit));
+    }
+    validator.check(validate_rootfs_path(&config.rootfs_path));
+    validator.check(validate_environment(&config.environment));
+    if let Some(hostname) = &config.hostname {
+        validator.check(validate_hostname(hostname));
+    }
+    for interface in &config.network_interfaces {
+        validator.check(validate_network_interface(interface));
+    }
+    if let (MemoryDiskPolicy::Error, Some(memory), Some(disk)) = (
+        memory_disk,
+        config.memory_limit,
+        config.disk_limit.and_then(|limit| limit.bytes()),
+    ) {
+        validator.check(validate_memory_disk_relationship(memory, disk));
+    }
+
+    validator.finish()
+}
+
+/// Validate everything needed to create a container
+pub fn validate_create(
+    name: &str,
+    template: &str,
+    config: &ContainerConfig,
+) -> Result<(), Vec<ValidationError>> {
+    validate_create_with(name, template, config, MemoryDiskPolicy::default())
+}
+
+/// `validate_create` with a chosen memory/disk policy
+pub fn validate_create_with(
+    name: &str,
+    template: &str,
+    config: &ContainerConfig,
+    memory_disk: MemoryDiskPolicy,
+) -> Result<(), Vec<ValidationError>> {
+    let mut validator = Validator::new();
+    validator
+        .check(validate_container_name(name))
+        .check(validate_template(template))
+        .check_all(validate_container_config_with(config, memory_disk));
+    validator.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_config() -> ContainerConfig {
+        ContainerConfig {
+            cpu_limit: Some(2),
+            memory_limit: Some(512 * 1024 * 1024),
+            disk_limit: Some(DiskLimit::Bytes(10 * 1024 * 1024 * 1024)),
+            network_interfaces: vec![],
+            rootfs_path: "/var/lib/lxc/web/rootfs".to_string(),
+            environment: vec![("HOME".to_string(), "/root".to_string())],
+            hostname: None,
+            cpu_request: None,
+            memory_request: None,
+        }
+    }
+
+    /// Assert a validation failure's field and code together
+    fn assert_error(result: Result<(), ValidationError>, field: &str, code: &str) {
+        let err = result.unwrap_err();
+        assert_eq!((err.field.as_str(), err.code.as_ref()), (field, code));
+    }
+
+    #[test]
+    fn test_name_policy() {
+        let name = "a".repeat(64);
+        assert!(validate_container_name(&name).is_ok());
+        assert_error(
+            validate_container_name_with(&name, &NamePolicy::dns_label()),
+            "name",
+            "NAME_TOO_LONG",
+        );
+
+        let permissive = NamePolicy {
+            allow_uppercase: true,
+            allow_dots: true,
+            ..NamePolicy::default()
+        };
+        assert!(validate_container_name_with("Web", &permissive).is_ok());
+        assert!(validate_container_name_with("web.prod", &permissive).is_ok());
+        assert_error(
+            validate_container_name_with("web.", &permissive),
+            "name",
+            "NAME_INVALID_END",
+        );
+        assert_error(
+            validate_container_name_with("web_1", &permissive),
+            "name",
+            "NAME_INVALID_CHARS",
+        );
+    }
+
+    #[test]
+    fn test_container_name_validation() {
+        for name in ["web", "web-1", "1web"] {
+            assert!(
+                validate_container_name(name).is_ok(),
+                "{} should be valid",
+                name
+            );
+        }
+        assert_error(validate_container_name(""), "name", "NAME_EMPTY");
+        assert_error(validate_container_name("Web"), "name", "NAME_INVALID_START");
+        assert_error(
+            validate_container_name("-web"),
+            "name",
+            "NAME_INVALID_START",
+        );
+        assert_error(validate_container_name("web-"), "name", "NAME_INVALID_END");
+        assert_error(
+            validate_container_name("web_1"),
+            "name",
+            "NAME_INVALID_CHARS",
+        );
+        assert_error(
+            validate_container_name("web.1"),
+            "name",
+            "NAME_INVALID_CHARS",
+        );
+        assert_error(
+            validate_container_name(&"a".repeat(65)),
+            "name",
+            "NAME_TOO_LONG",
+        );
+    }
+
+    #[test]
+    fn test_template_validation() {
+        assert!(validate_template("ubuntu-22.04").is_ok());
+        assert_error(
+            validate_template("Alpine"),
+            "template",
+            "TEMPLATE_INVALID_START",
+        );
+        assert_error(validate_template(""), "template", "TEMPLATE_EMPTY");
+    }
+
+    #[test]
+    fn test_template_allowlist_validation() {
+        let catalog = ["alpine", "debian-12", "ubuntu-22.04"];
+        assert!(validate_template_against("debian-12", &catalog).is_ok());
+
+        let err = validate_template_against("alpne", &catalog).unwrap_err();
+        assert_eq!(err.code, "TEMPLATE_UNKNOWN");
+        assert_eq!(
+            err.message,
+            "Unknown template 'alpne', expected one of: alpine, debian-12, ubuntu-22.04"
+        );
+        assert_error(
+            validate_template_against("Alpine", &catalog),
+            "template",
+            "TEMPLATE_INVALID_START",
+        );
+    }
+
+    #[test]
+    fn test_resource_limit_validation() {
+        assert!(validate_cpu_limit(4).is_ok());
+        assert_error(validate_cpu_limit(0), "cpu_limit", "CPU_OUT_OF_RANGE");
+        assert_error(
+            validate_memory_limit(1024),
+            "memory_limit",
+            "MEMORY_OUT_OF_RANGE",
+        );
+        assert_error(
+            validate_disk_limit(&DiskLimit::Bytes(MIN_DISK_LIMIT - 1)),
+            "disk_limit",
+            "DISK_OUT_OF_RANGE",
+        );
+    }
+
+    #[test]
+    fn test_disk_limit_validation() {
+        assert!(validate_disk_limit(&DiskLimit::Unlimited).is_ok());
+        assert!(validate_disk_limit(&DiskLimit::Bytes(MIN_DISK_LIMIT)).is_ok());
+        assert_error(
+            validate_disk_limit(&DiskLimit::Bytes(MAX_DISK_LIMIT + 1)),
+            "disk_limit",
+            "DISK_OUT_OF_RANGE",
+        );
+    }
+
+    #[test]
+    fn test_config_field_validation() {
+        assert_error(
+            validate_rootfs_path("rootfs"),
+            "rootfs_path",
+            "ROOTFS_NOT_ABSOLUTE",
+        );
+        assert_error(
+            validate_environment(&[("1BAD".to_string(), "x".to_string())]),
+            "environment",
+            "ENV_INVALID_KEY",
+        );
+    }
+
+    #[test]
+    fn test_request_within_limit() {
+        let mut config = valid_config();
+        config.cpu_request = Some(1);
+        config.memory_request = Some(512 * 1024 * 1024);
+        assert!(validate_container_config(&config).is_ok());
+
+        config.cpu_request = Some(4);
+        config.memory_request = Some(1024 * 1024 * 1024);
+        let errors = validate_container_config(&config).unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, vec!["cpu_request", "memory_request"]);
+        assert!(errors.iter().all(|e| e.code == "REQUEST_EXCEEDS_LIMIT"));
+    }
+
+    #[test]
+    fn test_request_bounds_without_limit() {
+        let mut config = valid_config();
+        config.cpu_limit = None;
+        config.memory_limit = None;
+        config.cpu_request = Some(2);
+        config.memory_request = Some(512 * 1024 * 1024);
+        assert!(validate_container_config(&config).is_ok());
+
+        config.cpu_request = Some(0);
+        config.memory_request = Some(MAX_MEMORY_LIMIT + 1);
+        let errors = validate_container_config(&config).unwrap_err();
+        let codes: Vec<_> = errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.code.as_ref()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("cpu_request", "CPU_OUT_OF_RANGE"),
+                ("memory_request", "MEMORY_OUT_OF_RANGE"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_hostname() {
+        assert!(validate_hostname("web-01.internal.example.com").is_ok());
+        assert_error(
+            validate_hostname(&format!("{}.example.com", "a".repeat(64))),
+            "hostname",
+            "HOSTNAME_INVALID_LABEL",
+        );
+        assert_error(
+            validate_hostname("-web.example.com"),
+            "hostname",
+            "HOSTNAME_INVALID_HYPHEN",
+        );
+
+        let mut config = valid_config();
+        config.hostname = Some("web_01".to_string());
+        let errors = validate_container_config(&config).unwrap_err();
+        assert_eq!(errors[0].field, "hostname");
+    }
+
+    fn env(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_environment_policy_denies_key() {
+        let policy = EnvPolicy {
+            deny: vec!["LD_PRELOAD".to_string()],
+            ..EnvPolicy::default()
+        };
+        assert_error(
+            validate_environment_with(&env(&[("LD_PRELOAD", "/tmp/x.so")]), &policy),
+            "environment",
+            "ENV_DENIED",
+        );
+        assert!(validate_environment_with(&env(&[("HOME", "/root")]), &policy).is_ok());
+        assert!(validate_environment(&env(&[("LD_PRELOAD", "/tmp/x.so")])).is_ok());
+    }
+
+    #[test]
+    fn test_environment_policy_enforces_allowlist() {
+        let policy = EnvPolicy {
+            allow: Some(vec!["HOME".to_string(), "PATH".to_string()]),
+            ..EnvPolicy::default()
+        };
+        assert!(
+            validate_environment_with(&env(&[("HOME", "/root"), ("PATH", "/bin")]), &policy)
+                .is_ok()
+        );
+        assert_error(
+            validate_environment_with(&env(&[("HOME", "/root"), ("SECRET", "x")]), &policy),
+            "environment",
+            "ENV_NOT_ALLOWED",
+        );
+    }
+
+    #[test]
+    fn test_merge_environment_overrides_existing_key() {
+        let merged = merge_environment(
+            &env(&[("LOG", "info"), ("HOME", "/root")]),
+            &env(&[("LOG", "debug")]),
+        )
+        .unwrap();
+        assert_eq!(merged, env(&[("LOG", "debug"), ("HOME", "/root")]));
+    }
+
+    #[test]
+    fn test_merge_environment_adds_new_key() {
+        let merged = merge_environment(
+            &env(&[("HOME", "/root")]),
+            &env(&[("PORT", "80"), ("PORT", "8080")]),
+        )
+        .unwrap();
+        assert_eq!(merged, env(&[("HOME", "/root"), ("PORT", "8080")]));
+    }
+
+    #[test]
+    fn test_merge_environment_keeps_unrelated_keys() {
+        let base = env(&[("HOME", "/root"), ("PATH", "/bin"), ("LOG", "info")]);
+        let merged = merge_environment(&base, &env(&[("LOG", "warn")])).unwrap();
+        assert_eq!(merged[0], base[0]);
+        assert_eq!(merged[1], base[1]);
+
+        assert_error(
+            merge_environment(&base, &env(&[("1BAD", "x")])).map(|_| ()),
+            "environment",
+            "ENV_INVALID_KEY",
+        );
+    }
+
+    #[test]
+    fn test_label_validation() {
+        assert!(validate_label("app.kubernetes.io/name", "web").is_ok());
+        assert!(validate_label("tier", "").is_ok());
+        assert_error(validate_label("", "web"), "labels", "LABEL_INVALID_KEY");
+        assert_error(
+            validate_label("env", "a b"),
+            "labels",
+            "LABEL_INVALID_VALUE",
+        );
+    }
+
+    #[test]
+    fn test_description_validation() {
+        assert!(validate_description("staging web frontend").is_ok());
+        assert!(validate_description(&"é".repeat(MAX_DESCRIPTION_LEN)).is_ok());
+        assert_error(
+            validate_description(&"a".repeat(MAX_DESCRIPTION_LEN + 1)),
+            "description",
+            "DESCRIPTION_TOO_LONG",
+        );
+    }
+
+    #[test]
+    fn test_fits_host() {
+        let host = HostResources {
+            cpu_cores: 4,
+            memory_bytes: 8 * 1024 * 1024 * 1024,
+            disk_bytes: 100 * 1024 * 1024 * 1024,
+        };
+        let mut config = valid_config();
+        config.cpu_limit = Some(4);
+        config.memory_limit = Some(8 * 1024 * 1024 * 1024);
+        assert!(fits_host(&config, &host).is_ok());
+
+        config.memory_limit = Some(16 * 1024 * 1024 * 1024);
+        let err = fits_host(&config, &host).unwrap_err();
+        assert_eq!(
+            (err.field.as_str(), err.code.as_ref()),
+            ("memory_limit", "MEMORY_EXCEEDS_HOST")
+        );
+        assert_eq!(
+            err.message,
+            "16GiB of memory requested, the host has 8GiB available"
+        );
+
+        config.memory_limit = Some(1024 * 1024 * 1024);
+        config.cpu_limit = Some(8);
+        assert_error(fits_host(&config, &host), "cpu_limit", "CPU_EXCEEDS_HOST");
+    }
+
+    #[test]
+    fn test_ip_address_validation() {
+        assert!(validate_ip_address("10.0.0.5").is_ok());
+        assert!(validate_ip_address("fd00::5").is_ok());
+        assert_error(
+            validate_ip_address("10.0.0.5/24").map(|_| ()),
+            "ip",
+            "IP_INVALID",
+        );
+        assert_error(
+            validate_ip_address("10.0.0").map(|_| ()),
+            "ip",
+            "IP_INVALID",
+        );
+    }
+
+    #[test]
+    fn test_memory_disk_relationship() {
+        let gib = 1024 * 1024 * 1024;
+        assert!(validate_memory_disk_relationship(gib, 10 * gib).is_ok());
+        assert!(validate_memory_disk_relationship(gib, gib).is_ok());
+        assert_error(
+            validate_memory_disk_relationship(2 * gib, gib),
+            "disk_limit",
+            "DISK_BELOW_MEMORY",
+        );
+
+        let mut config = valid_config();
+        config.memory_limit = Some(2 * gib);
+        config.disk_limit = Some(DiskLimit::Bytes(gib));
+        let errors = validate_container_config(&config).unwrap_err();
+        assert_eq!(errors[0].code, "DISK_BELOW_MEMORY");
+        assert!(validate_container_config_with(&config, MemoryDiskPolicy::Warn).is_ok());
+
+        config.disk_limit = Some(DiskLimit::Unlimited);
+        assert!(validate_container_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_node_container_count() {
+        assert!(check_node_container_count(0, 1).is_ok());
+        assert!(check_node_container_count(9, 10).is_ok());
+        assert_error(check_node_container_count(10, 10), "node_id", "NODE_FULL");
+        assert_error(check_node_container_count(0, 0), "node_id", "NODE_FULL");
+    }
+
+    #[test]
+    fn test_disk_for_template_validation() {
+        let min = 2 * 1024 * 1024 * 1024;
+        assert!(validate_disk_for_template(Some(DiskLimit::Bytes(min)), min).is_ok());
+        assert!(
+            validate_disk_for_template(Some(DiskLimit::Bytes(4 * 1024 * 1024 * 1024)), min).is_ok()
+        );
+        assert!(validate_disk_for_template(Some(DiskLimit::Unlimited), min).is_ok());
+        assert!(validate_disk_for_template(None, min).is_ok());
+        assert_error(
+            validate_disk_for_template(Some(DiskLimit::Bytes(MIN_DISK_LIMIT)), min),
+            "disk_limit",
+            "DISK_BELOW_TEMPLATE_MIN",
+        );
+    }
+
+    #[test]
+    fn test_reason_validation() {
+        assert!(validate_reason("lxc-start exited with status 1").is_ok());
+        assert_error(validate_reason("  "), "reason", "REASON_EMPTY");
+        assert_error(
+            validate_reason(&"a".repeat(MAX_REASON_LEN + 1)),
+            "reason",
+            "REASON_TOO_LONG",
+        );
+    }
+
+    #[test]
+    fn test_config_size_validation() {
+        assert!(validate_config_size(&valid_config()).is_ok());
+
+        let mut config = valid_config();
+        config.environment = (0..MAX_ENVIRONMENT_VARS + 1)
+            .map(|i| (format!("VAR_{}", i), "x".to_string()))
+            .collect();
+        assert_error(
+            validate_config_size(&config),
+            "environment",
+            "TOO_MANY_ENV_VARS",
+        );
+
+        let mut config = valid_config();
+        config.environment = vec![("BLOB".to_string(), "x".repeat(MAX_CONFIG_BYTES))];
+        assert_error(validate_config_size(&config), "config", "CONFIG_TOO_LARGE");
+        assert!(validate_config_size_with(&config, 2 * MAX_CONFIG_BYTES).is_ok());
+    }
+
+    #[test]
+    fn test_validator_collects_all_errors() {
+        let mut validator = Validator::new();
+        validator
+            .check(validate_container_name("Bad Name"))
+            .check(validate_template("alpine"))
+            .check(validate_cpu_limit(0))
+            .check(validate_memory_limit(1));
+
+        let errors = validator.finish().unwrap_err();
+        let codes: Vec<_> = errors.iter().map(|e| e.code.as_ref()).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "NAME_INVALID_START",
+                "CPU_OUT_OF_RANGE",
+                "MEMORY_OUT_OF_RANGE"
+            ]
+        );
+
+        // Clients read back what the API serialized
+        let json = serde_json::to_string(&errors).unwrap();
+        let decoded: Vec<ValidationError> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, errors);
+
+        let message = ValidationErrors(errors).to_string();
+        assert!(message.starts_with("name: "));
+        assert_eq!(message.matches("; ").count(), 2);
+    }
+
+    #[test]
+    fn test_validate_create() {
+        assert!(validate_create("web", "alpine", &valid_config()).is_ok());
+
+        let mut config = valid_config();
+        config.cpu_limit = Some(0);
+        config.rootfs_path = "relative".to_string();
+        let errors = validate_create("web", "alpine", &config).unwrap_err();
+        assert_eq!(errors.len(), 2);
+
+        let gib = 1024 * 1024 * 1024;
+        let mut config = valid_config();
+        config.memory_limit = Some(2 * gib);
+        config.disk_limit = Some(DiskLimit::Bytes(gib));
+        assert!(validate_create("web", "alpine", &config).is_err());
+        assert!(validate_create_with("web", "alpine", &config, MemoryDiskPolicy::Warn).is_ok());
+    }
+}